@@ -1,11 +1,166 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use rusoto_core::proto::xml::error::XmlErrorDeserializer;
+use rusoto_core::proto::xml::util::{find_start_element, XmlResponse};
 use rusoto_core::RusotoError;
 use rusoto_s3::{
     CreateBucketError, CreateBucketOutput, CreateBucketRequest, DeleteBucketError,
-    DeleteBucketRequest, HeadBucketError, HeadBucketRequest, ListBucketsError, ListBucketsOutput,
-    S3Client, S3,
+    DeleteBucketReplicationError, DeleteBucketReplicationRequest, DeleteBucketRequest,
+    DeleteBucketTaggingError, DeleteBucketTaggingRequest, DeleteBucketWebsiteError,
+    DeleteBucketWebsiteRequest, GetBucketEncryptionError, GetBucketEncryptionRequest,
+    GetBucketLocationRequest, GetBucketNotificationConfigurationError,
+    GetBucketNotificationConfigurationRequest, GetBucketReplicationError,
+    GetBucketReplicationRequest, GetBucketTaggingError, GetBucketTaggingRequest,
+    GetBucketVersioningRequest, GetBucketWebsiteError, GetBucketWebsiteRequest,
+    GetObjectLockConfigurationError, GetObjectLockConfigurationRequest, HeadBucketError,
+    HeadBucketRequest, ListBucketsError, ListBucketsOutput, PutBucketEncryptionError,
+    PutBucketEncryptionRequest, PutBucketNotificationConfigurationError,
+    PutBucketNotificationConfigurationRequest, PutBucketReplicationError,
+    PutBucketReplicationRequest, PutBucketTaggingError, PutBucketTaggingRequest,
+    PutBucketWebsiteError, PutBucketWebsiteRequest, PutObjectLockConfigurationError,
+    PutObjectLockConfigurationRequest, S3Client, ServerSideEncryptionConfiguration, Tagging, S3,
 };
 
+use crate::convert_error;
+use crate::encryption::BucketEncryption;
+use crate::lock::DefaultRetention;
+use crate::notification::{
+    from_configuration, has_event_bridge, merge, to_configuration, to_xml, NotificationError,
+    NotificationRule,
+};
+use crate::query::SelectClient;
+use crate::replication::Replication;
+use crate::tagging::{from_tag_set, to_tag_set, validate_tags, Tags, MAX_BUCKET_TAGS};
+use crate::website::Website;
+use serde::Serialize;
+use xml::EventReader;
+
+/// Filters and per bucket details for `show_s3_buckets_with`.
+#[derive(Clone)]
+pub struct BucketFilter {
+    name_pattern: Option<String>, // glob, e.g. `logs-*`
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    region: bool,
+    versioning: bool,
+    encryption: bool,
+    tags: bool,
+    concurrency: usize,
+}
+
+impl Default for BucketFilter {
+    fn default() -> Self {
+        BucketFilter {
+            name_pattern: None,
+            created_after: None,
+            created_before: None,
+            region: false,
+            versioning: false,
+            encryption: false,
+            tags: false,
+            concurrency: 8,
+        }
+    }
+}
+
+impl BucketFilter {
+    pub fn new() -> Self {
+        BucketFilter::default()
+    }
+
+    pub fn name(mut self, pattern: &str) -> Self {
+        self.name_pattern = Some(pattern.to_string());
+        self
+    }
+
+    pub fn created_after(mut self, date: DateTime<Utc>) -> Self {
+        self.created_after = Some(date);
+        self
+    }
+
+    pub fn created_before(mut self, date: DateTime<Utc>) -> Self {
+        self.created_before = Some(date);
+        self
+    }
+
+    pub fn with_region(mut self) -> Self {
+        self.region = true;
+        self
+    }
+
+    pub fn with_versioning(mut self) -> Self {
+        self.versioning = true;
+        self
+    }
+
+    pub fn with_encryption(mut self) -> Self {
+        self.encryption = true;
+        self
+    }
+
+    pub fn with_tags(mut self) -> Self {
+        self.tags = true;
+        self
+    }
+
+    /// Max number of buckets whose details are fetched at the same time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn matches(&self, pattern: &Option<glob::Pattern>, info: &BucketInfo) -> bool {
+        if let Some(pattern) = pattern {
+            if !pattern.matches(&info.name) {
+                return false;
+            }
+        }
+
+        match (info.creation_date, self.created_after, self.created_before) {
+            (None, None, None) => true,
+            (None, _, _) => false,
+            (Some(date), after, before) => {
+                after.is_none_or(|after| date > after) && before.is_none_or(|before| date < before)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Versioning {
+    Unversioned,
+    Enabled,
+    Suspended,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BucketInfo {
+    pub name: String,
+    pub creation_date: Option<DateTime<Utc>>,
+    pub region: Option<String>,
+    pub versioning: Option<Versioning>,
+    pub encryption: Option<BucketEncryption>,
+    pub tags: Option<Tags>,
+    pub errors: Vec<String>, // details that were requested but could not be fetched
+}
+
+impl BucketInfo {
+    fn new(name: String, creation_date: Option<String>) -> Self {
+        BucketInfo {
+            name,
+            creation_date: creation_date
+                .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+            region: None,
+            versioning: None,
+            encryption: None,
+            tags: None,
+            errors: Vec::new(),
+        }
+    }
+}
+
 #[async_trait]
 pub trait Bucket: S3 {
     async fn create_s3_bucket(
@@ -14,6 +169,12 @@ pub trait Bucket: S3 {
         bucket_req: Option<CreateBucketRequest>,
     ) -> Result<CreateBucketOutput, RusotoError<CreateBucketError>>;
 
+    /// Object lock can only be enabled when the bucket is created, it also enables versioning.
+    async fn create_s3_locked_bucket(
+        &self,
+        bucket_name: String,
+    ) -> Result<CreateBucketOutput, RusotoError<CreateBucketError>>;
+
     async fn drop_s3_bucket(
         &self,
         bucket_name: String,
@@ -22,6 +183,96 @@ pub trait Bucket: S3 {
     async fn has_s3_bucket(&self, bucket_name: String) -> Result<(), RusotoError<HeadBucketError>>;
 
     async fn show_s3_buckets(&self) -> Result<ListBucketsOutput, RusotoError<ListBucketsError>>;
+
+    async fn show_s3_buckets_with(
+        &self,
+        filter: BucketFilter,
+    ) -> Result<Vec<BucketInfo>, RusotoError<ListBucketsError>>;
+
+    async fn put_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+        encryption: BucketEncryption,
+    ) -> Result<(), RusotoError<PutBucketEncryptionError>>;
+
+    async fn get_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<BucketEncryption>, RusotoError<GetBucketEncryptionError>>;
+
+    async fn put_s3_bucket_tags(
+        &self,
+        bucket_name: String,
+        tags: Tags,
+    ) -> Result<(), RusotoError<PutBucketTaggingError>>;
+
+    async fn get_s3_bucket_tags(
+        &self,
+        bucket_name: String,
+    ) -> Result<Tags, RusotoError<GetBucketTaggingError>>;
+
+    async fn drop_s3_bucket_tags(
+        &self,
+        bucket_name: String,
+    ) -> Result<(), RusotoError<DeleteBucketTaggingError>>;
+
+    async fn get_s3_bucket_notifications(
+        &self,
+        bucket_name: String,
+    ) -> Result<Vec<NotificationRule>, RusotoError<GetBucketNotificationConfigurationError>>;
+
+    /// Replaces the whole notification configuration with `rules`. `rusoto_s3` 0.45 can't send
+    /// `EventBridgeConfiguration`, so this also turns EventBridge delivery off, see
+    /// `SignedBucket::add_s3_bucket_notification`.
+    async fn put_s3_bucket_notifications(
+        &self,
+        bucket_name: String,
+        rules: Vec<NotificationRule>,
+    ) -> Result<(), RusotoError<PutBucketNotificationConfigurationError>>;
+
+    async fn put_s3_bucket_retention(
+        &self,
+        bucket_name: String,
+        retention: DefaultRetention,
+    ) -> Result<(), RusotoError<PutObjectLockConfigurationError>>;
+
+    async fn get_s3_bucket_retention(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<DefaultRetention>, RusotoError<GetObjectLockConfigurationError>>;
+
+    /// Source and destination buckets must both have versioning enabled.
+    async fn put_s3_bucket_replication(
+        &self,
+        bucket_name: String,
+        replication: Replication,
+    ) -> Result<(), RusotoError<PutBucketReplicationError>>;
+
+    async fn get_s3_bucket_replication(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<Replication>, RusotoError<GetBucketReplicationError>>;
+
+    async fn drop_s3_bucket_replication(
+        &self,
+        bucket_name: String,
+    ) -> Result<(), RusotoError<DeleteBucketReplicationError>>;
+
+    async fn put_s3_bucket_website(
+        &self,
+        bucket_name: String,
+        website: Website,
+    ) -> Result<(), RusotoError<PutBucketWebsiteError>>;
+
+    async fn get_s3_bucket_website(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<Website>, RusotoError<GetBucketWebsiteError>>;
+
+    async fn drop_s3_bucket_website(
+        &self,
+        bucket_name: String,
+    ) -> Result<(), RusotoError<DeleteBucketWebsiteError>>;
 }
 
 #[async_trait]
@@ -47,6 +298,19 @@ impl Bucket for S3Client {
         }
     }
 
+    async fn create_s3_locked_bucket(
+        &self,
+        bucket_name: String,
+    ) -> Result<CreateBucketOutput, RusotoError<CreateBucketError>> {
+        let create_bucket_req = CreateBucketRequest {
+            bucket: bucket_name,
+            object_lock_enabled_for_bucket: Some(true),
+            ..Default::default()
+        };
+
+        self.create_bucket(create_bucket_req).await
+    }
+
     async fn drop_s3_bucket(
         &self,
         bucket_name: String,
@@ -69,4 +333,490 @@ impl Bucket for S3Client {
     async fn show_s3_buckets(&self) -> Result<ListBucketsOutput, RusotoError<ListBucketsError>> {
         self.list_buckets().await
     }
+
+    async fn show_s3_buckets_with(
+        &self,
+        filter: BucketFilter,
+    ) -> Result<Vec<BucketInfo>, RusotoError<ListBucketsError>> {
+        let pattern = match &filter.name_pattern {
+            Some(pattern) => Some(
+                glob::Pattern::new(pattern).map_err(|e| RusotoError::Validation(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let buckets = self
+            .list_buckets()
+            .await?
+            .buckets
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|bucket| Some(BucketInfo::new(bucket.name?, bucket.creation_date)))
+            .filter(|info| filter.matches(&pattern, info))
+            .collect::<Vec<BucketInfo>>();
+
+        Ok(stream::iter(buckets)
+            .map(|info| bucket_details(self, info, &filter))
+            .buffered(filter.concurrency)
+            .collect()
+            .await)
+    }
+
+    async fn put_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+        encryption: BucketEncryption,
+    ) -> Result<(), RusotoError<PutBucketEncryptionError>> {
+        if encryption.bucket_key() == Some(true) {
+            return Err(RusotoError::Validation(
+                "rusoto_s3 can't send BucketKeyEnabled, use `SignedBucket` on `SelectClient`"
+                    .to_string(),
+            ));
+        }
+
+        let put_encryption_req = PutBucketEncryptionRequest {
+            bucket: bucket_name,
+            server_side_encryption_configuration: ServerSideEncryptionConfiguration {
+                rules: vec![encryption.to_rule()],
+            },
+        };
+
+        self.put_bucket_encryption(put_encryption_req).await
+    }
+
+    async fn get_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<BucketEncryption>, RusotoError<GetBucketEncryptionError>> {
+        let get_encryption_req = GetBucketEncryptionRequest {
+            bucket: bucket_name,
+        };
+
+        let output = self.get_bucket_encryption(get_encryption_req).await?;
+
+        Ok(output
+            .server_side_encryption_configuration
+            .and_then(|config| config.rules.iter().find_map(BucketEncryption::from_rule)))
+    }
+
+    async fn put_s3_bucket_tags(
+        &self,
+        bucket_name: String,
+        tags: Tags,
+    ) -> Result<(), RusotoError<PutBucketTaggingError>> {
+        validate_tags(&tags, MAX_BUCKET_TAGS).map_err(RusotoError::Validation)?;
+
+        let put_tagging_req = PutBucketTaggingRequest {
+            bucket: bucket_name,
+            tagging: Tagging {
+                tag_set: to_tag_set(tags),
+            },
+        };
+
+        self.put_bucket_tagging(put_tagging_req).await
+    }
+
+    async fn get_s3_bucket_tags(
+        &self,
+        bucket_name: String,
+    ) -> Result<Tags, RusotoError<GetBucketTaggingError>> {
+        let get_tagging_req = GetBucketTaggingRequest {
+            bucket: bucket_name,
+        };
+
+        let output = self.get_bucket_tagging(get_tagging_req).await?;
+
+        Ok(from_tag_set(output.tag_set))
+    }
+
+    async fn drop_s3_bucket_tags(
+        &self,
+        bucket_name: String,
+    ) -> Result<(), RusotoError<DeleteBucketTaggingError>> {
+        let delete_tagging_req = DeleteBucketTaggingRequest {
+            bucket: bucket_name,
+        };
+
+        self.delete_bucket_tagging(delete_tagging_req).await
+    }
+
+    async fn get_s3_bucket_notifications(
+        &self,
+        bucket_name: String,
+    ) -> Result<Vec<NotificationRule>, RusotoError<GetBucketNotificationConfigurationError>> {
+        let get_notification_req = GetBucketNotificationConfigurationRequest {
+            bucket: bucket_name,
+        };
+
+        let config = self
+            .get_bucket_notification_configuration(get_notification_req)
+            .await?;
+
+        Ok(from_configuration(config))
+    }
+
+    async fn put_s3_bucket_notifications(
+        &self,
+        bucket_name: String,
+        rules: Vec<NotificationRule>,
+    ) -> Result<(), RusotoError<PutBucketNotificationConfigurationError>> {
+        let put_notification_req = PutBucketNotificationConfigurationRequest {
+            bucket: bucket_name,
+            notification_configuration: to_configuration(rules),
+        };
+
+        self.put_bucket_notification_configuration(put_notification_req)
+            .await
+    }
+
+    async fn put_s3_bucket_retention(
+        &self,
+        bucket_name: String,
+        retention: DefaultRetention,
+    ) -> Result<(), RusotoError<PutObjectLockConfigurationError>> {
+        let put_lock_req = PutObjectLockConfigurationRequest {
+            bucket: bucket_name,
+            object_lock_configuration: Some(retention.to_configuration()),
+            ..Default::default()
+        };
+
+        match self.put_object_lock_configuration(put_lock_req).await {
+            Err(e) => Err(e),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn get_s3_bucket_retention(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<DefaultRetention>, RusotoError<GetObjectLockConfigurationError>> {
+        let get_lock_req = GetObjectLockConfigurationRequest {
+            bucket: bucket_name,
+        };
+
+        let output = self.get_object_lock_configuration(get_lock_req).await?;
+
+        Ok(output
+            .object_lock_configuration
+            .and_then(DefaultRetention::from_configuration))
+    }
+
+    async fn put_s3_bucket_replication(
+        &self,
+        bucket_name: String,
+        replication: Replication,
+    ) -> Result<(), RusotoError<PutBucketReplicationError>> {
+        let put_replication_req = PutBucketReplicationRequest {
+            bucket: bucket_name,
+            replication_configuration: replication.to_configuration(),
+            ..Default::default()
+        };
+
+        self.put_bucket_replication(put_replication_req).await
+    }
+
+    async fn get_s3_bucket_replication(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<Replication>, RusotoError<GetBucketReplicationError>> {
+        let get_replication_req = GetBucketReplicationRequest {
+            bucket: bucket_name,
+        };
+
+        let output = self.get_bucket_replication(get_replication_req).await?;
+
+        Ok(output
+            .replication_configuration
+            .map(Replication::from_configuration))
+    }
+
+    async fn drop_s3_bucket_replication(
+        &self,
+        bucket_name: String,
+    ) -> Result<(), RusotoError<DeleteBucketReplicationError>> {
+        let delete_replication_req = DeleteBucketReplicationRequest {
+            bucket: bucket_name,
+        };
+
+        self.delete_bucket_replication(delete_replication_req).await
+    }
+
+    async fn put_s3_bucket_website(
+        &self,
+        bucket_name: String,
+        website: Website,
+    ) -> Result<(), RusotoError<PutBucketWebsiteError>> {
+        let put_website_req = PutBucketWebsiteRequest {
+            bucket: bucket_name,
+            website_configuration: website.to_configuration(),
+        };
+
+        self.put_bucket_website(put_website_req).await
+    }
+
+    async fn get_s3_bucket_website(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<Website>, RusotoError<GetBucketWebsiteError>> {
+        let get_website_req = GetBucketWebsiteRequest {
+            bucket: bucket_name,
+        };
+
+        let output = self.get_bucket_website(get_website_req).await?;
+
+        Ok(Website::from_output(output))
+    }
+
+    async fn drop_s3_bucket_website(
+        &self,
+        bucket_name: String,
+    ) -> Result<(), RusotoError<DeleteBucketWebsiteError>> {
+        let delete_website_req = DeleteBucketWebsiteRequest {
+            bucket: bucket_name,
+        };
+
+        self.delete_bucket_website(delete_website_req).await
+    }
+}
+
+/// Bucket requests `rusoto_s3` 0.45 can't express, signed and sent by `SelectClient` the same way
+/// it sends S3 Select requests.
+#[async_trait]
+pub trait SignedBucket {
+    /// `Bucket::put_s3_bucket_encryption`, S3 Bucket Keys included.
+    async fn put_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+        encryption: BucketEncryption,
+    ) -> Result<(), RusotoError<PutBucketEncryptionError>>;
+
+    /// `Bucket::get_s3_bucket_encryption`, reading whether S3 Bucket Keys are enabled.
+    async fn get_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<BucketEncryption>, RusotoError<GetBucketEncryptionError>>;
+
+    /// Adds `rule` to the current configuration, a rule with the same id is replaced and
+    /// EventBridge delivery is kept. Returns `NotificationError::Overlap` without sending anything
+    /// when `rule` overlaps another rule.
+    async fn add_s3_bucket_notification(
+        &self,
+        bucket_name: String,
+        rule: NotificationRule,
+    ) -> Result<(), NotificationError>;
+}
+
+#[async_trait]
+impl SignedBucket for SelectClient {
+    async fn put_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+        encryption: BucketEncryption,
+    ) -> Result<(), RusotoError<PutBucketEncryptionError>> {
+        let body = encryption
+            .to_xml()
+            .map_err(|e| RusotoError::Validation(e.to_string()))?;
+
+        self.send_bucket_request(
+            "PUT",
+            &bucket_name,
+            "encryption",
+            Some(body),
+            PutBucketEncryptionError::from_response,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_s3_bucket_encryption(
+        &self,
+        bucket_name: String,
+    ) -> Result<Option<BucketEncryption>, RusotoError<GetBucketEncryptionError>> {
+        let response = self
+            .send_bucket_request(
+                "GET",
+                &bucket_name,
+                "encryption",
+                None,
+                GetBucketEncryptionError::from_response,
+            )
+            .await?;
+
+        BucketEncryption::from_xml(&response.body)
+            .map_err(|e| RusotoError::ParseError(e.to_string()))
+    }
+
+    async fn add_s3_bucket_notification(
+        &self,
+        bucket_name: String,
+        rule: NotificationRule,
+    ) -> Result<(), NotificationError> {
+        let current = self
+            .send_bucket_request(
+                "GET",
+                &bucket_name,
+                "notification",
+                None,
+                GetBucketNotificationConfigurationError::from_response,
+            )
+            .await
+            .map_err(|e| convert_error(e, |e| match e {}))?;
+        let event_bridge =
+            has_event_bridge(&current.body).map_err(|e| RusotoError::ParseError(e.to_string()))?;
+        // rusoto's parser for the rules is private, so they are read through `S3Client`
+        let rules = self
+            .s3_client()
+            .get_s3_bucket_notifications(bucket_name.clone())
+            .await
+            .map_err(|e| convert_error(e, |e| match e {}))?;
+
+        let body = to_xml(merge(rules, rule)?, event_bridge)
+            .map_err(|e| RusotoError::Validation(e.to_string()))?;
+        self.send_bucket_request(
+            "PUT",
+            &bucket_name,
+            "notification",
+            Some(body),
+            PutBucketNotificationConfigurationError::from_response,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+async fn bucket_details(s3: &S3Client, mut info: BucketInfo, filter: &BucketFilter) -> BucketInfo {
+    if filter.region {
+        let location_req = GetBucketLocationRequest {
+            bucket: info.name.clone(),
+        };
+
+        match s3.get_bucket_location(location_req).await {
+            Ok(location) => info.region = Some(region_name(location.location_constraint)),
+            Err(e) => info.errors.push(format!("region: {}", e)),
+        }
+    }
+
+    if filter.versioning {
+        let versioning_req = GetBucketVersioningRequest {
+            bucket: info.name.clone(),
+        };
+
+        match s3.get_bucket_versioning(versioning_req).await {
+            Ok(versioning) => {
+                info.versioning = Some(match versioning.status.as_deref() {
+                    Some("Enabled") => Versioning::Enabled,
+                    Some("Suspended") => Versioning::Suspended,
+                    _ => Versioning::Unversioned,
+                })
+            }
+            Err(e) => info.errors.push(format!("versioning: {}", e)),
+        }
+    }
+
+    if filter.encryption {
+        match s3.get_s3_bucket_encryption(info.name.clone()).await {
+            Ok(encryption) => info.encryption = encryption,
+            Err(e) if is_not_configured(&e) => (),
+            Err(e) => info.errors.push(format!("encryption: {}", e)),
+        }
+    }
+
+    if filter.tags {
+        match s3.get_s3_bucket_tags(info.name.clone()).await {
+            Ok(tags) => info.tags = Some(tags),
+            Err(e) if is_not_configured(&e) => info.tags = Some(Tags::new()),
+            Err(e) => info.errors.push(format!("tags: {}", e)),
+        }
+    }
+
+    info
+}
+
+// `GetBucketLocation` answers `null` for us-east-1 and the legacy `EU` for eu-west-1
+fn region_name(location_constraint: Option<String>) -> String {
+    match location_constraint.as_deref() {
+        None | Some("") => String::from("us-east-1"),
+        Some("EU") => String::from("eu-west-1"),
+        Some(region) => region.to_string(),
+    }
+}
+
+// codes S3 answers with a 404 when a bucket has no encryption or tag set configured, other 404s,
+// e.g. `NoSuchBucket`, are errors
+const NOT_CONFIGURED: [&str; 2] = [
+    "ServerSideEncryptionConfigurationNotFoundError",
+    "NoSuchTagSet",
+];
+
+fn is_not_configured<E>(error: &RusotoError<E>) -> bool {
+    let response = match error {
+        RusotoError::Unknown(response) if response.status.as_u16() == 404 => response,
+        _ => return false,
+    };
+
+    let reader = EventReader::new(response.body.as_ref());
+    let mut stack = XmlResponse::new(reader.into_iter().peekable());
+    find_start_element(&mut stack);
+    XmlErrorDeserializer::deserialize("Error", &mut stack)
+        .is_ok_and(|error| NOT_CONFIGURED.contains(&error.code.as_str()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use rusoto_core::request::BufferedHttpResponse;
+    use std::convert::TryInto;
+
+    #[test]
+    fn filter_by_name_and_date() {
+        let filter = BucketFilter::new()
+            .name("logs-*")
+            .created_after(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+        let pattern = Some(glob::Pattern::new("logs-*").unwrap());
+
+        let recent = BucketInfo::new(
+            "logs-2021".to_string(),
+            Some("2021-02-03T16:45:09.000Z".to_string()),
+        );
+        let old = BucketInfo::new(
+            "logs-2019".to_string(),
+            Some("2019-02-03T16:45:09.000Z".to_string()),
+        );
+        let other = BucketInfo::new(
+            "reports".to_string(),
+            Some("2021-02-03T16:45:09.000Z".to_string()),
+        );
+
+        assert!(filter.matches(&pattern, &recent));
+        assert!(!filter.matches(&pattern, &old));
+        assert!(!filter.matches(&pattern, &other));
+    }
+
+    #[test]
+    fn not_configured_errors() {
+        let error = |status: u16, code: &str| {
+            RusotoError::<GetBucketTaggingError>::Unknown(BufferedHttpResponse {
+                status: status.try_into().unwrap(),
+                body: format!("<Error><Code>{}</Code><Message>m</Message></Error>", code).into(),
+                headers: Default::default(),
+            })
+        };
+
+        assert!(is_not_configured(&error(404, "NoSuchTagSet")));
+        assert!(is_not_configured(&error(
+            404,
+            "ServerSideEncryptionConfigurationNotFoundError"
+        )));
+        assert!(!is_not_configured(&error(404, "NoSuchBucket")));
+        assert!(!is_not_configured(&error(403, "NoSuchTagSet")));
+    }
+
+    #[test]
+    fn region_names() {
+        assert_eq!(region_name(None), "us-east-1");
+        assert_eq!(region_name(Some("EU".to_string())), "eu-west-1");
+        assert_eq!(region_name(Some("sa-east-1".to_string())), "sa-east-1");
+    }
 }
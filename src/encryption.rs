@@ -0,0 +1,266 @@
+use rusoto_s3::{
+    ServerSideEncryptionByDefault, ServerSideEncryptionByDefaultSerializer,
+    ServerSideEncryptionRule,
+};
+use serde::Serialize;
+use std::fmt;
+use xml::reader::XmlEvent as ReadEvent;
+use xml::writer::XmlEvent as WriteEvent;
+use xml::{EventReader, EventWriter};
+
+use crate::S3_XMLNS;
+
+const AES256: &str = "AES256";
+const AWS_KMS: &str = "aws:kms";
+
+/// Default encryption applied to new objects in a bucket.
+///
+/// `rusoto_s3` 0.45 has no `BucketKeyEnabled` field, so S3 Bucket Keys are only sent and read
+/// by `SignedBucket`, `Bucket` reads them as `None`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum BucketEncryption {
+    S3,
+    KMS {
+        key_id: Option<String>,   // key id or arn, `None` uses the aws managed key
+        bucket_key: Option<bool>, // `None` leaves S3 Bucket Keys off
+    },
+}
+
+impl BucketEncryption {
+    pub(crate) fn to_rule(&self) -> ServerSideEncryptionRule {
+        let by_default = match self {
+            Self::S3 => ServerSideEncryptionByDefault {
+                sse_algorithm: AES256.to_string(),
+                kms_master_key_id: None,
+            },
+            Self::KMS { key_id, .. } => ServerSideEncryptionByDefault {
+                sse_algorithm: AWS_KMS.to_string(),
+                kms_master_key_id: key_id.clone(),
+            },
+        };
+
+        ServerSideEncryptionRule {
+            apply_server_side_encryption_by_default: Some(by_default),
+        }
+    }
+
+    pub(crate) fn from_rule(rule: &ServerSideEncryptionRule) -> Option<Self> {
+        let by_default = rule.apply_server_side_encryption_by_default.as_ref()?;
+
+        match by_default.sse_algorithm.as_str() {
+            AES256 => Some(Self::S3),
+            AWS_KMS => Some(Self::KMS {
+                key_id: by_default.kms_master_key_id.clone(),
+                bucket_key: None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn bucket_key(&self) -> Option<bool> {
+        match self {
+            Self::S3 => None,
+            Self::KMS { bucket_key, .. } => *bucket_key,
+        }
+    }
+
+    // `ServerSideEncryptionConfiguration` body, with the `BucketKeyEnabled` rusoto can't write
+    pub(crate) fn to_xml(&self) -> Result<Vec<u8>, xml::writer::Error> {
+        let rule = self.to_rule();
+        let mut writer = EventWriter::new(Vec::new());
+
+        writer.write(
+            WriteEvent::start_element("ServerSideEncryptionConfiguration").default_ns(S3_XMLNS),
+        )?;
+        writer.write(WriteEvent::start_element("Rule"))?;
+        if let Some(by_default) = &rule.apply_server_side_encryption_by_default {
+            ServerSideEncryptionByDefaultSerializer::serialize(
+                &mut writer,
+                "ApplyServerSideEncryptionByDefault",
+                by_default,
+            )?;
+        }
+        if let Some(bucket_key) = self.bucket_key() {
+            writer.write(WriteEvent::start_element("BucketKeyEnabled"))?;
+            writer.write(WriteEvent::characters(&bucket_key.to_string()))?;
+            writer.write(WriteEvent::end_element())?;
+        }
+        writer.write(WriteEvent::end_element())?;
+        writer.write(WriteEvent::end_element())?;
+
+        Ok(writer.into_inner())
+    }
+
+    // first rule of a `ServerSideEncryptionConfiguration` body this crate knows
+    pub(crate) fn from_xml(body: &[u8]) -> Result<Option<Self>, xml::reader::Error> {
+        let mut rules = Vec::new();
+        let mut rule = (None, None, None); // algorithm, key id, bucket key
+        let mut element = String::new();
+
+        for event in EventReader::new(body) {
+            match event? {
+                ReadEvent::StartElement { name, .. } => element = name.local_name,
+                ReadEvent::Characters(text) => match element.as_str() {
+                    "SSEAlgorithm" => rule.0 = Some(text),
+                    "KMSMasterKeyID" => rule.1 = Some(text),
+                    "BucketKeyEnabled" => rule.2 = Some(text == "true"),
+                    _ => (),
+                },
+                ReadEvent::EndElement { name } => {
+                    element.clear();
+                    if name.local_name == "Rule" {
+                        rules.push(std::mem::take(&mut rule));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(rules
+            .into_iter()
+            .find_map(
+                |(algorithm, key_id, bucket_key)| match algorithm?.as_str() {
+                    AES256 => Some(Self::S3),
+                    AWS_KMS => Some(Self::KMS {
+                        key_id,
+                        bucket_key: Some(bucket_key.unwrap_or(false)),
+                    }),
+                    _ => None,
+                },
+            ))
+    }
+}
+
+/// Server-side encryption for a single object.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectEncryption {
+    S3,
+    KMS {
+        key_id: Option<String>,
+        context: Option<String>, // base64 encoded json
+    },
+    Customer(CustomerKey),
+}
+
+pub const CUSTOMER_KEY_LENGTH: usize = 32;
+
+/// SSE-C key. S3 never stores it, so the same key must be sent on every read of the object.
+#[derive(Clone, PartialEq)]
+pub struct CustomerKey {
+    key: Vec<u8>,
+}
+
+impl CustomerKey {
+    /// `key` must be the raw 256 bit key, not its base64 encoding.
+    pub fn new(key: Vec<u8>) -> Result<Self, String> {
+        if key.len() != CUSTOMER_KEY_LENGTH {
+            return Err(format!(
+                "customer key has {} bytes, SSE-C needs a {} byte key",
+                key.len(),
+                CUSTOMER_KEY_LENGTH
+            ));
+        }
+
+        Ok(CustomerKey { key })
+    }
+
+    pub fn algorithm(&self) -> String {
+        AES256.to_string()
+    }
+
+    pub fn key(&self) -> String {
+        base64::encode(&self.key)
+    }
+
+    pub fn key_md5(&self) -> String {
+        base64::encode(*md5::compute(&self.key))
+    }
+}
+
+// the key itself never shows up in logs, only its digest
+impl fmt::Debug for CustomerKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomerKey")
+            .field("algorithm", &self.algorithm())
+            .field("key_md5", &self.key_md5())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn customer_key_headers() {
+        let key = CustomerKey::new(vec![0u8; 32]).unwrap();
+
+        assert_eq!(key.algorithm(), "AES256");
+        assert_eq!(key.key(), "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=");
+        assert_eq!(key.key_md5(), "cLyPS3KoaSFGi/joRB3OUQ==");
+        assert_eq!(
+            format!("{:?}", ObjectEncryption::Customer(key)),
+            "Customer(CustomerKey { algorithm: \"AES256\", key_md5: \"cLyPS3KoaSFGi/joRB3OUQ==\" })"
+        );
+    }
+
+    #[test]
+    fn customer_key_length() {
+        assert!(CustomerKey::new(vec![0u8; 16]).is_err());
+        assert!(CustomerKey::new(Vec::new()).is_err());
+        assert!(CustomerKey::new(vec![0u8; 33]).is_err());
+    }
+
+    #[test]
+    fn bucket_encryption_rule() {
+        let kms = BucketEncryption::KMS {
+            key_id: Some("key-id".to_string()),
+            bucket_key: None,
+        };
+        let rule = kms.to_rule();
+
+        assert_eq!(
+            rule.apply_server_side_encryption_by_default
+                .as_ref()
+                .unwrap()
+                .sse_algorithm,
+            "aws:kms"
+        );
+        assert_eq!(BucketEncryption::from_rule(&rule), Some(kms));
+        assert_eq!(
+            BucketEncryption::from_rule(&BucketEncryption::S3.to_rule()),
+            Some(BucketEncryption::S3)
+        );
+    }
+
+    #[test]
+    fn bucket_key_xml() {
+        let kms = BucketEncryption::KMS {
+            key_id: Some("key-id".to_string()),
+            bucket_key: Some(true),
+        };
+        let body = String::from_utf8(kms.to_xml().unwrap()).unwrap();
+
+        assert!(body.ends_with(
+            "<ServerSideEncryptionConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Rule><ApplyServerSideEncryptionByDefault><KMSMasterKeyID>key-id</KMSMasterKeyID><SSEAlgorithm>aws:kms</SSEAlgorithm></ApplyServerSideEncryptionByDefault><BucketKeyEnabled>true</BucketKeyEnabled></Rule></ServerSideEncryptionConfiguration>"
+        ));
+        assert_eq!(
+            BucketEncryption::from_xml(body.as_bytes()).unwrap(),
+            Some(kms)
+        );
+        assert_eq!(
+            BucketEncryption::from_xml(
+                b"<ServerSideEncryptionConfiguration><Rule><ApplyServerSideEncryptionByDefault><SSEAlgorithm>aws:kms</SSEAlgorithm></ApplyServerSideEncryptionByDefault></Rule></ServerSideEncryptionConfiguration>"
+            )
+            .unwrap(),
+            Some(BucketEncryption::KMS {
+                key_id: None,
+                bucket_key: Some(false),
+            })
+        );
+        assert_eq!(
+            BucketEncryption::from_xml(&BucketEncryption::S3.to_xml().unwrap()).unwrap(),
+            Some(BucketEncryption::S3)
+        );
+    }
+}
@@ -1,9 +1,16 @@
 pub mod bucket;
+pub mod encryption;
+pub mod lock;
+pub mod notification;
 pub mod query;
 pub mod read;
+pub mod replication;
+pub mod tagging;
 pub mod transact;
+pub mod website;
 
-use rusoto_core::Region;
+use query::SelectClient;
+use rusoto_core::{Region, RusotoError};
 use rusoto_s3::S3Client;
 
 pub fn region(name: String, endpoint: String) -> Region {
@@ -30,6 +37,44 @@ where
     S3Client::new_with(request_dispatcher, credentials_provider, region)
 }
 
+pub fn select_client(region: Region) -> SelectClient {
+    SelectClient::new(region)
+}
+
+/// `select_client_with` allows user to configure credentials and request dispatcher for S3 Select;
+#[cfg(feature = "auth")]
+pub fn select_client_with<P, D>(
+    request_dispatcher: D,
+    credentials_provider: P,
+    region: Region,
+) -> SelectClient
+where
+    P: rusoto_credential::ProvideAwsCredentials + Send + Sync + 'static,
+    D: rusoto_core::DispatchSignedRequest + Send + Sync + 'static,
+{
+    SelectClient::new_with(request_dispatcher, credentials_provider, region)
+}
+
+// namespace of the request bodies signed by hand, see `SelectClient`
+pub(crate) const S3_XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+// Most `rusoto_s3` operation errors have no modeled variants, so operations built from
+// several requests only need to carry the transport errors over.
+pub(crate) fn convert_error<E, F>(
+    error: RusotoError<E>,
+    service: impl FnOnce(E) -> RusotoError<F>,
+) -> RusotoError<F> {
+    match error {
+        RusotoError::Service(e) => service(e),
+        RusotoError::HttpDispatch(e) => RusotoError::HttpDispatch(e),
+        RusotoError::Credentials(e) => RusotoError::Credentials(e),
+        RusotoError::Validation(e) => RusotoError::Validation(e),
+        RusotoError::ParseError(e) => RusotoError::ParseError(e),
+        RusotoError::Unknown(e) => RusotoError::Unknown(e),
+        RusotoError::Blocking => RusotoError::Blocking,
+    }
+}
+
 #[test]
 fn region_test() {
     let actual = region("name".to_string(), "endpoint".to_string());
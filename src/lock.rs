@@ -0,0 +1,279 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusoto_core::proto::xml::error::XmlErrorDeserializer;
+use rusoto_core::proto::xml::util::{find_start_element, XmlResponse};
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    HeadObjectRequest, ObjectLockConfiguration, ObjectLockRetention, ObjectLockRule, S3Client, S3,
+};
+use serde::Serialize;
+use std::fmt;
+use xml::EventReader;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum RetentionMode {
+    Governance, // can be bypassed with `s3:BypassGovernanceRetention`
+    Compliance, // can't be shortened or removed by anyone, root included
+}
+
+impl RetentionMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Governance => "GOVERNANCE",
+            Self::Compliance => "COMPLIANCE",
+        }
+    }
+
+    fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "GOVERNANCE" => Some(Self::Governance),
+            "COMPLIANCE" => Some(Self::Compliance),
+            _ => None,
+        }
+    }
+}
+
+/// Retention of a single object version.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Retention {
+    pub mode: RetentionMode,
+    pub retain_until: DateTime<Utc>,
+}
+
+impl Retention {
+    pub fn new(mode: RetentionMode, retain_until: DateTime<Utc>) -> Self {
+        Retention { mode, retain_until }
+    }
+
+    pub(crate) fn retain_until_date(&self) -> String {
+        self.retain_until
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+
+    pub(crate) fn to_object_lock(&self) -> ObjectLockRetention {
+        ObjectLockRetention {
+            mode: Some(self.mode.as_str().to_string()),
+            retain_until_date: Some(self.retain_until_date()),
+        }
+    }
+
+    pub(crate) fn from_object_lock(retention: ObjectLockRetention) -> Option<Self> {
+        let mode = RetentionMode::parse(retention.mode.as_deref()?)?;
+        let retain_until = DateTime::parse_from_rfc3339(retention.retain_until_date.as_deref()?)
+            .ok()?
+            .with_timezone(&Utc);
+
+        Some(Retention { mode, retain_until })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum RetentionPeriod {
+    Days(i64),
+    Years(i64),
+}
+
+/// Retention applied to every new object version in a bucket.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DefaultRetention {
+    pub mode: RetentionMode,
+    pub period: RetentionPeriod,
+}
+
+impl DefaultRetention {
+    pub fn new(mode: RetentionMode, period: RetentionPeriod) -> Self {
+        DefaultRetention { mode, period }
+    }
+
+    pub(crate) fn to_configuration(&self) -> ObjectLockConfiguration {
+        let (days, years) = match self.period {
+            RetentionPeriod::Days(days) => (Some(days), None),
+            RetentionPeriod::Years(years) => (None, Some(years)),
+        };
+
+        ObjectLockConfiguration {
+            object_lock_enabled: Some("Enabled".to_string()),
+            rule: Some(ObjectLockRule {
+                default_retention: Some(rusoto_s3::DefaultRetention {
+                    mode: Some(self.mode.as_str().to_string()),
+                    days,
+                    years,
+                }),
+            }),
+        }
+    }
+
+    pub(crate) fn from_configuration(config: ObjectLockConfiguration) -> Option<Self> {
+        let retention = config.rule?.default_retention?;
+        let mode = RetentionMode::parse(retention.mode.as_deref()?)?;
+        let period = match (retention.days, retention.years) {
+            (Some(days), _) => RetentionPeriod::Days(days),
+            (None, Some(years)) => RetentionPeriod::Years(years),
+            (None, None) => return None,
+        };
+
+        Some(DefaultRetention { mode, period })
+    }
+}
+
+pub(crate) fn legal_hold_status(on: bool) -> String {
+    if on {
+        "ON".to_string()
+    } else {
+        "OFF".to_string()
+    }
+}
+
+/// Error for writes and deletes that object lock can refuse.
+#[derive(Debug)]
+pub enum ObjectLockError<E> {
+    /// The object version is under retention or legal hold, holds the message from S3.
+    Locked(String),
+    S3(RusotoError<E>),
+}
+
+impl<E> From<RusotoError<E>> for ObjectLockError<E> {
+    fn from(error: RusotoError<E>) -> Self {
+        ObjectLockError::S3(error)
+    }
+}
+
+impl<E> ObjectLockError<E> {
+    /// `Locked` when S3 denied a request on `key` and that version is under retention or legal hold.
+    pub(crate) async fn check(
+        s3: &S3Client,
+        error: RusotoError<E>,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+    ) -> Self {
+        let message = match access_denied(&error) {
+            Some(message) => message,
+            None => return ObjectLockError::S3(error),
+        };
+
+        let head_object = HeadObjectRequest {
+            bucket: bucket_name,
+            key,
+            version_id,
+            ..Default::default()
+        };
+        let locked = match s3.head_object(head_object).await {
+            Ok(head) => {
+                let retained = head
+                    .object_lock_retain_until_date
+                    .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+                    .is_some_and(|date| date > Utc::now());
+
+                retained || head.object_lock_legal_hold_status.as_deref() == Some("ON")
+            }
+            Err(_) => false,
+        };
+
+        if locked {
+            ObjectLockError::Locked(message)
+        } else {
+            ObjectLockError::S3(error)
+        }
+    }
+}
+
+/// Message of an `AccessDenied` error, object lock refusals carry no code of their own.
+fn access_denied<E>(error: &RusotoError<E>) -> Option<String> {
+    let response = match error {
+        RusotoError::Unknown(response) if response.status.as_u16() == 403 => response,
+        _ => return None,
+    };
+
+    let reader = EventReader::new(response.body.as_ref());
+    let mut stack = XmlResponse::new(reader.into_iter().peekable());
+    find_start_element(&mut stack);
+    XmlErrorDeserializer::deserialize("Error", &mut stack)
+        .ok()
+        .filter(|error| error.code == "AccessDenied")
+        .map(|error| error.message)
+}
+
+impl<E: std::error::Error + 'static> fmt::Display for ObjectLockError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Locked(message) => write!(f, "object is locked: {}", message),
+            Self::S3(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ObjectLockError<E> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use rusoto_core::request::BufferedHttpResponse;
+    use rusoto_s3::DeleteObjectError;
+    use std::convert::TryInto;
+
+    fn response(status: u16, body: &str) -> BufferedHttpResponse {
+        BufferedHttpResponse {
+            status: status.try_into().unwrap(),
+            body: body.to_string().into(),
+            headers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn retention_roundtrip() {
+        let retention = Retention::new(
+            RetentionMode::Compliance,
+            Utc.with_ymd_and_hms(2030, 1, 2, 3, 4, 5).unwrap(),
+        );
+        let object_lock = retention.to_object_lock();
+
+        assert_eq!(
+            object_lock.retain_until_date,
+            Some("2030-01-02T03:04:05.000Z".to_string())
+        );
+        assert_eq!(Retention::from_object_lock(object_lock), Some(retention));
+    }
+
+    #[test]
+    fn default_retention_roundtrip() {
+        let retention = DefaultRetention::new(RetentionMode::Governance, RetentionPeriod::Days(30));
+
+        assert_eq!(
+            DefaultRetention::from_configuration(retention.to_configuration()),
+            Some(retention)
+        );
+    }
+
+    #[test]
+    fn access_denied_errors() {
+        let denied = |status: u16, body: &str| {
+            access_denied(&RusotoError::<DeleteObjectError>::Unknown(response(
+                status, body,
+            )))
+        };
+
+        assert_eq!(
+            denied(
+                403,
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>AccessDenied</Code><Message>Access Denied because object protected by object lock.</Message><RequestId>1</RequestId></Error>",
+            ),
+            Some("Access Denied because object protected by object lock.".to_string())
+        );
+        assert_eq!(
+            denied(
+                403,
+                "<Error><Code>InvalidRequest</Code><Message>object lock is not enabled</Message></Error>",
+            ),
+            None
+        );
+        assert_eq!(
+            denied(
+                400,
+                "<Error><Code>AccessDenied</Code><Message>Access Denied</Message></Error>"
+            ),
+            None
+        );
+        assert_eq!(denied(403, "object lock"), None);
+    }
+}
@@ -0,0 +1,485 @@
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    FilterRule, LambdaFunctionConfiguration, LambdaFunctionConfigurationListSerializer,
+    NotificationConfiguration, NotificationConfigurationFilter,
+    PutBucketNotificationConfigurationError, QueueConfiguration, QueueConfigurationListSerializer,
+    S3KeyFilter, TopicConfiguration, TopicConfigurationListSerializer,
+};
+use serde::Serialize;
+use std::fmt;
+use xml::reader::XmlEvent as ReadEvent;
+use xml::writer::XmlEvent as WriteEvent;
+use xml::{EventReader, EventWriter};
+
+use crate::S3_XMLNS;
+
+/// Where S3 delivers the event, each variant holds the target ARN.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum NotificationTarget {
+    Sqs(String),
+    Sns(String),
+    Lambda(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum NotificationEvent {
+    ObjectCreated,
+    ObjectCreatedPut,
+    ObjectCreatedPost,
+    ObjectCreatedCopy,
+    ObjectCreatedCompleteMultipartUpload,
+    ObjectRemoved,
+    ObjectRemovedDelete,
+    ObjectRemovedDeleteMarkerCreated,
+    ObjectRestorePost,
+    ObjectRestoreCompleted,
+    ReducedRedundancyLostObject,
+    Other(String), // any event type not listed above, e.g. `s3:Replication:*`
+}
+
+impl NotificationEvent {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::ObjectCreated => "s3:ObjectCreated:*",
+            Self::ObjectCreatedPut => "s3:ObjectCreated:Put",
+            Self::ObjectCreatedPost => "s3:ObjectCreated:Post",
+            Self::ObjectCreatedCopy => "s3:ObjectCreated:Copy",
+            Self::ObjectCreatedCompleteMultipartUpload => {
+                "s3:ObjectCreated:CompleteMultipartUpload"
+            }
+            Self::ObjectRemoved => "s3:ObjectRemoved:*",
+            Self::ObjectRemovedDelete => "s3:ObjectRemoved:Delete",
+            Self::ObjectRemovedDeleteMarkerCreated => "s3:ObjectRemoved:DeleteMarkerCreated",
+            Self::ObjectRestorePost => "s3:ObjectRestore:Post",
+            Self::ObjectRestoreCompleted => "s3:ObjectRestore:Completed",
+            Self::ReducedRedundancyLostObject => "s3:ReducedRedundancyLostObject",
+            Self::Other(event) => event,
+        }
+    }
+
+    fn parse(event: &str) -> Self {
+        match event {
+            "s3:ObjectCreated:*" => Self::ObjectCreated,
+            "s3:ObjectCreated:Put" => Self::ObjectCreatedPut,
+            "s3:ObjectCreated:Post" => Self::ObjectCreatedPost,
+            "s3:ObjectCreated:Copy" => Self::ObjectCreatedCopy,
+            "s3:ObjectCreated:CompleteMultipartUpload" => {
+                Self::ObjectCreatedCompleteMultipartUpload
+            }
+            "s3:ObjectRemoved:*" => Self::ObjectRemoved,
+            "s3:ObjectRemoved:Delete" => Self::ObjectRemovedDelete,
+            "s3:ObjectRemoved:DeleteMarkerCreated" => Self::ObjectRemovedDeleteMarkerCreated,
+            "s3:ObjectRestore:Post" => Self::ObjectRestorePost,
+            "s3:ObjectRestore:Completed" => Self::ObjectRestoreCompleted,
+            "s3:ReducedRedundancyLostObject" => Self::ReducedRedundancyLostObject,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    // `s3:ObjectCreated:*` also covers `s3:ObjectCreated:Put`
+    fn overlaps(&self, other: &Self) -> bool {
+        let covers = |pattern: &str, event: &str| {
+            pattern
+                .strip_suffix('*')
+                .is_some_and(|pattern| event.starts_with(pattern))
+        };
+        let (a, b) = (self.as_str(), other.as_str());
+
+        a == b || covers(a, b) || covers(b, a)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NotificationRule {
+    pub id: Option<String>,
+    pub target: NotificationTarget,
+    pub events: Vec<NotificationEvent>,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+}
+
+impl NotificationRule {
+    pub fn new(target: NotificationTarget, events: Vec<NotificationEvent>) -> Self {
+        NotificationRule {
+            id: None,
+            target,
+            events,
+            prefix: None,
+            suffix: None,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn suffix(mut self, suffix: &str) -> Self {
+        self.suffix = Some(suffix.to_string());
+        self
+    }
+
+    /// S3 rejects two rules that share an event type when one key could match both filters.
+    fn overlaps(&self, other: &Self) -> bool {
+        let affix = |a: &Option<String>, b: &Option<String>, covers: fn(&str, &str) -> bool| match (
+            a.as_deref(),
+            b.as_deref(),
+        ) {
+            (Some(a), Some(b)) => covers(a, b) || covers(b, a),
+            _ => true,
+        };
+
+        self.events
+            .iter()
+            .any(|event| other.events.iter().any(|o| event.overlaps(o)))
+            && affix(&self.prefix, &other.prefix, |a, b| a.starts_with(b))
+            && affix(&self.suffix, &other.suffix, |a, b| a.ends_with(b))
+    }
+
+    fn filter(&self) -> Option<NotificationConfigurationFilter> {
+        let filter_rules = vec![("prefix", &self.prefix), ("suffix", &self.suffix)]
+            .into_iter()
+            .filter_map(|(name, value)| {
+                value.as_ref().map(|value| FilterRule {
+                    name: Some(name.to_string()),
+                    value: Some(value.clone()),
+                })
+            })
+            .collect::<Vec<FilterRule>>();
+
+        if filter_rules.is_empty() {
+            None
+        } else {
+            Some(NotificationConfigurationFilter {
+                key: Some(S3KeyFilter {
+                    filter_rules: Some(filter_rules),
+                }),
+            })
+        }
+    }
+
+    fn from_parts(
+        id: Option<String>,
+        target: NotificationTarget,
+        events: Vec<String>,
+        filter: Option<NotificationConfigurationFilter>,
+    ) -> Self {
+        let mut rule = NotificationRule::new(
+            target,
+            events.iter().map(|e| NotificationEvent::parse(e)).collect(),
+        );
+        rule.id = id;
+
+        let filter_rules = filter
+            .and_then(|filter| filter.key)
+            .and_then(|key| key.filter_rules)
+            .unwrap_or_default();
+        for filter_rule in filter_rules {
+            match filter_rule.name.map(|name| name.to_lowercase()).as_deref() {
+                Some("prefix") => rule.prefix = filter_rule.value,
+                Some("suffix") => rule.suffix = filter_rule.value,
+                _ => (),
+            }
+        }
+
+        rule
+    }
+}
+
+pub(crate) fn to_configuration(rules: Vec<NotificationRule>) -> NotificationConfiguration {
+    let mut config = NotificationConfiguration::default();
+
+    for rule in rules {
+        let events = rule
+            .events
+            .iter()
+            .map(|e| e.as_str().to_string())
+            .collect::<Vec<String>>();
+        let filter = rule.filter();
+
+        match rule.target {
+            NotificationTarget::Sqs(queue_arn) => config
+                .queue_configurations
+                .get_or_insert_with(Vec::new)
+                .push(QueueConfiguration {
+                    events,
+                    filter,
+                    id: rule.id,
+                    queue_arn,
+                }),
+            NotificationTarget::Sns(topic_arn) => config
+                .topic_configurations
+                .get_or_insert_with(Vec::new)
+                .push(TopicConfiguration {
+                    events,
+                    filter,
+                    id: rule.id,
+                    topic_arn,
+                }),
+            NotificationTarget::Lambda(lambda_function_arn) => config
+                .lambda_function_configurations
+                .get_or_insert_with(Vec::new)
+                .push(LambdaFunctionConfiguration {
+                    events,
+                    filter,
+                    id: rule.id,
+                    lambda_function_arn,
+                }),
+        }
+    }
+
+    config
+}
+
+pub(crate) fn from_configuration(config: NotificationConfiguration) -> Vec<NotificationRule> {
+    let queues = config.queue_configurations.unwrap_or_default();
+    let topics = config.topic_configurations.unwrap_or_default();
+    let lambdas = config.lambda_function_configurations.unwrap_or_default();
+
+    queues
+        .into_iter()
+        .map(|q| {
+            NotificationRule::from_parts(
+                q.id,
+                NotificationTarget::Sqs(q.queue_arn),
+                q.events,
+                q.filter,
+            )
+        })
+        .chain(topics.into_iter().map(|t| {
+            NotificationRule::from_parts(
+                t.id,
+                NotificationTarget::Sns(t.topic_arn),
+                t.events,
+                t.filter,
+            )
+        }))
+        .chain(lambdas.into_iter().map(|l| {
+            NotificationRule::from_parts(
+                l.id,
+                NotificationTarget::Lambda(l.lambda_function_arn),
+                l.events,
+                l.filter,
+            )
+        }))
+        .collect()
+}
+
+/// Adds `rule` to `rules`, replacing only the rule with the same id or an identical rule.
+/// Fails when `rule` overlaps any other rule, as S3 would reject the whole configuration.
+// `NotificationConfiguration` body of `rules`, with the `EventBridgeConfiguration` rusoto can't write
+pub(crate) fn to_xml(
+    rules: Vec<NotificationRule>,
+    event_bridge: bool,
+) -> Result<Vec<u8>, xml::writer::Error> {
+    let config = to_configuration(rules);
+    let mut writer = EventWriter::new(Vec::new());
+
+    writer.write(WriteEvent::start_element("NotificationConfiguration").default_ns(S3_XMLNS))?;
+    if let Some(topics) = &config.topic_configurations {
+        TopicConfigurationListSerializer::serialize(&mut writer, "TopicConfiguration", topics)?;
+    }
+    if let Some(queues) = &config.queue_configurations {
+        QueueConfigurationListSerializer::serialize(&mut writer, "QueueConfiguration", queues)?;
+    }
+    if let Some(lambdas) = &config.lambda_function_configurations {
+        LambdaFunctionConfigurationListSerializer::serialize(
+            &mut writer,
+            "CloudFunctionConfiguration",
+            lambdas,
+        )?;
+    }
+    if event_bridge {
+        writer.write(WriteEvent::start_element("EventBridgeConfiguration"))?;
+        writer.write(WriteEvent::end_element())?;
+    }
+    writer.write(WriteEvent::end_element())?;
+
+    Ok(writer.into_inner())
+}
+
+// whether a `NotificationConfiguration` body sends events to EventBridge
+pub(crate) fn has_event_bridge(body: &[u8]) -> Result<bool, xml::reader::Error> {
+    for event in EventReader::new(body) {
+        if let ReadEvent::StartElement { name, .. } = event? {
+            if name.local_name == "EventBridgeConfiguration" {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+pub(crate) fn merge(
+    mut rules: Vec<NotificationRule>,
+    rule: NotificationRule,
+) -> Result<Vec<NotificationRule>, NotificationError> {
+    let replaced = rules
+        .iter()
+        .position(|existing| (rule.id.is_some() && existing.id == rule.id) || *existing == rule);
+    let overlapping = rules
+        .iter()
+        .enumerate()
+        .find(|(i, existing)| Some(*i) != replaced && existing.overlaps(&rule));
+    if let Some((_, existing)) = overlapping {
+        return Err(NotificationError::Overlap(existing.id.clone()));
+    }
+
+    match replaced {
+        Some(i) => rules[i] = rule,
+        None => rules.push(rule),
+    }
+    Ok(rules)
+}
+
+/// Error for `add_s3_bucket_notification`.
+#[derive(Debug)]
+pub enum NotificationError {
+    /// The new rule overlaps an existing one on event type, prefix and suffix, holds its id.
+    Overlap(Option<String>),
+    S3(Box<RusotoError<PutBucketNotificationConfigurationError>>),
+}
+
+impl From<RusotoError<PutBucketNotificationConfigurationError>> for NotificationError {
+    fn from(error: RusotoError<PutBucketNotificationConfigurationError>) -> Self {
+        NotificationError::S3(Box::new(error))
+    }
+}
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Overlap(Some(id)) => write!(f, "notification overlaps rule `{}`", id),
+            Self::Overlap(None) => write!(f, "notification overlaps an existing rule"),
+            Self::S3(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NotificationError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn queue_rule() -> NotificationRule {
+        NotificationRule::new(
+            NotificationTarget::Sqs("arn:aws:sqs:us-east-1:123456789012:ingest".to_string()),
+            vec![NotificationEvent::ObjectCreated],
+        )
+        .id("ingest")
+        .prefix("raw/")
+        .suffix(".json")
+    }
+
+    #[test]
+    fn configuration_roundtrip() {
+        let rules = vec![
+            queue_rule(),
+            NotificationRule::new(
+                NotificationTarget::Lambda(
+                    "arn:aws:lambda:us-east-1:123456789012:function:cleanup".to_string(),
+                ),
+                vec![
+                    NotificationEvent::ObjectRemovedDelete,
+                    NotificationEvent::Other("s3:Replication:*".to_string()),
+                ],
+            ),
+        ];
+
+        let config = to_configuration(rules.clone());
+
+        assert_eq!(config.queue_configurations.as_ref().unwrap().len(), 1);
+        assert_eq!(
+            config
+                .lambda_function_configurations
+                .as_ref()
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(config.topic_configurations.is_none());
+        assert_eq!(from_configuration(config), rules);
+    }
+
+    #[test]
+    fn merge_keeps_other_rules() {
+        let topic = NotificationRule::new(
+            NotificationTarget::Sns("arn:aws:sns:us-east-1:123456789012:alerts".to_string()),
+            vec![NotificationEvent::ObjectRemoved],
+        );
+
+        let merged = merge(
+            vec![queue_rule(), topic.clone()],
+            queue_rule().suffix(".csv"),
+        )
+        .unwrap();
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].suffix, Some(".csv".to_string()));
+        assert_eq!(merged[1], topic);
+
+        let merged = merge(merged, topic).unwrap();
+        assert_eq!(merged.len(), 2);
+
+        let merged = merge(merged, queue_rule().id("archive")).unwrap();
+        assert_eq!(merged.len(), 3);
+    }
+
+    #[test]
+    fn event_bridge_xml() {
+        let body = to_xml(vec![queue_rule()], true).unwrap();
+        let text = String::from_utf8(body.clone()).unwrap();
+
+        assert!(text.contains("<Queue>arn:aws:sqs:us-east-1:123456789012:ingest</Queue>"));
+        assert!(text.ends_with("<EventBridgeConfiguration /></NotificationConfiguration>"));
+        assert!(has_event_bridge(&body).unwrap());
+        assert!(!has_event_bridge(&to_xml(vec![queue_rule()], false).unwrap()).unwrap());
+        assert!(has_event_bridge(
+            b"<NotificationConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><EventBridgeConfiguration></EventBridgeConfiguration></NotificationConfiguration>"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn merge_rejects_overlaps() {
+        let lambda = |events: Vec<NotificationEvent>| {
+            NotificationRule::new(
+                NotificationTarget::Lambda(
+                    "arn:aws:lambda:us-east-1:123456789012:function:resize".to_string(),
+                ),
+                events,
+            )
+            .id("resize")
+        };
+        let overlap = |rule: NotificationRule| {
+            matches!(
+                merge(vec![queue_rule()], rule),
+                Err(NotificationError::Overlap(Some(id))) if id == "ingest"
+            )
+        };
+
+        assert!(overlap(lambda(vec![NotificationEvent::ObjectCreatedPut])));
+        assert!(overlap(
+            lambda(vec![NotificationEvent::ObjectCreated]).prefix("raw/2020/")
+        ));
+        assert!(overlap(
+            lambda(vec![NotificationEvent::ObjectCreated]).suffix("data.json")
+        ));
+        assert!(!overlap(
+            lambda(vec![NotificationEvent::ObjectRemoved]).prefix("raw/")
+        ));
+        assert!(!overlap(
+            lambda(vec![NotificationEvent::ObjectCreated]).prefix("clean/")
+        ));
+        assert!(!overlap(
+            lambda(vec![NotificationEvent::ObjectCreated]).suffix(".csv")
+        ));
+    }
+}
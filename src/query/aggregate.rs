@@ -0,0 +1,343 @@
+use chrono::DateTime;
+use serde_json::{Number, Value as Json};
+use std::cmp::Ordering;
+
+use super::stream::Record;
+use super::{QueryError, Select};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg, // merged from a sum and a count
+}
+
+/// Output field of an aggregate query and the fields its per object query returns for it.
+#[derive(Clone, Debug, PartialEq)]
+struct Column {
+    function: Function,
+    name: String,
+    parts: Vec<String>,
+}
+
+/// How an aggregate query over several objects is split and merged back.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct AggregatePlan {
+    columns: Vec<Column>,
+}
+
+impl AggregatePlan {
+    /// Plans `select` if it only has aggregates, `None` if it has plain columns.
+    ///
+    /// Unnamed fields keep the positional names S3 gives them, `_1`, `_2`, ...
+    pub(crate) fn new(select: &[Select]) -> Option<(Self, Vec<Select>)> {
+        let mut columns = Vec::new();
+        let mut rewritten = Vec::new();
+
+        for (i, element) in select.iter().enumerate() {
+            let (aggregate, name) = match element {
+                Select::As(aggregate, name) => (aggregate.as_ref(), name.clone()),
+                aggregate => (aggregate, format!("_{}", i + 1)),
+            };
+            let part = |suffix: &str| format!("part{}_{}", i, suffix);
+
+            let (function, parts) = match aggregate {
+                Select::Count(e) => (Function::Count, vec![Select::Count(e.clone())]),
+                Select::Sum(e) => (Function::Sum, vec![Select::Sum(e.clone())]),
+                Select::Min(e) => (Function::Min, vec![Select::Min(e.clone())]),
+                Select::Max(e) => (Function::Max, vec![Select::Max(e.clone())]),
+                // `Count(e)` skips nulls like `Avg(e)` does
+                Select::Avg(e) => (
+                    Function::Avg,
+                    vec![Select::Sum(e.clone()), Select::Count(e.clone())],
+                ),
+                Select::Elements(_) | Select::As(..) => return None,
+            };
+            let suffixes = match function {
+                Function::Avg => vec!["sum", "count"],
+                _ => vec!["value"],
+            };
+
+            columns.push(Column {
+                function,
+                name,
+                parts: suffixes.iter().map(|suffix| part(suffix)).collect(),
+            });
+            rewritten.extend(
+                parts
+                    .into_iter()
+                    .zip(suffixes)
+                    .map(|(select, suffix)| select.alias(&part(suffix))),
+            );
+        }
+
+        if columns.is_empty() {
+            None
+        } else {
+            Some((AggregatePlan { columns }, rewritten))
+        }
+    }
+
+    /// Partial result of a single record, `arguments` holds the value each aggregate reads.
+    pub(crate) fn row_partial(&self, arguments: &[Json]) -> Record {
+        let mut partial = Record::new();
+
+        for (column, argument) in self.columns.iter().zip(arguments) {
+            let counted = Json::from(!argument.is_null() as i64);
+            match column.function {
+                Function::Count => partial.insert(column.parts[0].clone(), counted),
+                Function::Avg => {
+                    partial.insert(column.parts[1].clone(), counted);
+                    partial.insert(column.parts[0].clone(), argument.clone())
+                }
+                _ => partial.insert(column.parts[0].clone(), argument.clone()),
+            };
+        }
+
+        partial
+    }
+
+    pub(crate) fn merger(&self) -> AggregateMerge {
+        AggregateMerge {
+            plan: self.clone(),
+            values: vec![Json::Null; self.columns.len()],
+            counts: vec![0; self.columns.len()],
+        }
+    }
+}
+
+/// Partial results of each object folded into a single record.
+pub(crate) struct AggregateMerge {
+    plan: AggregatePlan,
+    values: Vec<Json>, // count, sum, min or max so far
+    counts: Vec<i64>,  // counts for `Avg`
+}
+
+impl AggregateMerge {
+    pub(crate) fn add(&mut self, partial: &Record) -> Result<(), QueryError> {
+        for (i, column) in self.plan.columns.iter().enumerate() {
+            let value = partial.get(&column.parts[0]).unwrap_or(&Json::Null);
+            let merged = match column.function {
+                Function::Count | Function::Sum | Function::Avg => add(&self.values[i], value),
+                Function::Min => pick(&self.values[i], value, Ordering::Less),
+                Function::Max => pick(&self.values[i], value, Ordering::Greater),
+            }
+            .ok_or_else(|| {
+                QueryError::Decode(format!(
+                    "can't merge `{}` partial results {} and {}",
+                    column.name, self.values[i], value
+                ))
+            })?;
+            self.values[i] = merged;
+
+            if column.function == Function::Avg {
+                let count = partial.get(&column.parts[1]).unwrap_or(&Json::Null);
+                self.counts[i] += count.as_i64().unwrap_or_default();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Record {
+        let counts = self.counts;
+
+        self.plan
+            .columns
+            .into_iter()
+            .zip(self.values)
+            .enumerate()
+            .map(|(i, (column, value))| {
+                let value = match column.function {
+                    Function::Count if value.is_null() => Json::from(0),
+                    Function::Avg => match (value.as_f64(), counts[i]) {
+                        (Some(sum), count) if count > 0 => {
+                            Number::from_f64(sum / count as f64).map_or(Json::Null, Json::Number)
+                        }
+                        _ => Json::Null,
+                    },
+                    _ => value,
+                };
+                (column.name, value)
+            })
+            .collect()
+    }
+}
+
+// Sums and counts of objects without matching records are null
+fn add(total: &Json, value: &Json) -> Option<Json> {
+    match (total, value) {
+        (total, Json::Null) => Some(total.clone()),
+        (Json::Null, value) if value.is_number() => Some(value.clone()),
+        (Json::Number(a), Json::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.checked_add(b).map(Json::from),
+            _ => Number::from_f64(a.as_f64()? + b.as_f64()?).map(Json::Number),
+        },
+        _ => None,
+    }
+}
+
+fn pick(current: &Json, value: &Json, wanted: Ordering) -> Option<Json> {
+    let ordering = match (current, value) {
+        (current, Json::Null) => return Some(current.clone()),
+        (Json::Null, value) => return Some(value.clone()),
+        (Json::Number(a), Json::Number(b)) => b.as_f64()?.partial_cmp(&a.as_f64()?)?,
+        // timestamps come back as RFC 3339 strings whose precision and offset can differ between
+        // objects, so they are compared as instants, other strings in string order
+        (Json::String(a), Json::String(b)) => {
+            match (
+                DateTime::parse_from_rfc3339(a),
+                DateTime::parse_from_rfc3339(b),
+            ) {
+                (Ok(a), Ok(b)) => b.cmp(&a),
+                _ => b.cmp(a),
+            }
+        }
+        (Json::Bool(a), Json::Bool(b)) => b.cmp(a),
+        _ => return None,
+    };
+
+    if ordering == wanted {
+        Some(value.clone())
+    } else {
+        Some(current.clone())
+    }
+}
+
+#[cfg(test)]
+mod aggregate_test {
+    use super::*;
+    use crate::query::Expr;
+    use serde_json::json;
+
+    fn record(value: Json) -> Record {
+        value.as_object().unwrap().clone()
+    }
+
+    fn select() -> Vec<Select> {
+        vec![
+            Select::Count(Expr::Wildcard),
+            Select::Sum(Expr::column("price")).alias("total"),
+            Select::Min(Expr::column("price")),
+            Select::Max(Expr::column("logged_at")),
+            Select::Avg(Expr::column("price")).alias("average"),
+        ]
+    }
+
+    #[test]
+    fn rewrites_per_object() {
+        let (_, rewritten) = AggregatePlan::new(&select()).unwrap();
+        let sql = rewritten
+            .iter()
+            .map(Select::to_sql)
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        assert_eq!(
+            sql,
+            "Count(*) AS part0_value, Sum(s.price) AS part1_value, Min(s.price) AS part2_value, \
+             Max(s.logged_at) AS part3_value, Sum(s.price) AS part4_sum, Count(s.price) AS part4_count"
+        );
+    }
+
+    #[test]
+    fn only_aggregates() {
+        assert!(AggregatePlan::new(&[]).is_none());
+        assert!(AggregatePlan::new(&[
+            Select::Count(Expr::Wildcard),
+            Select::Elements(vec![Expr::column("id")]),
+        ])
+        .is_none());
+    }
+
+    #[test]
+    fn merges_partial_results() {
+        let (plan, _) = AggregatePlan::new(&select()).unwrap();
+        let mut merge = plan.merger();
+
+        for partial in [
+            json!({"part0_value": 2, "part1_value": 30, "part2_value": 10, "part3_value": "2020-01-02T00:00:00Z", "part4_sum": 30, "part4_count": 2}),
+            json!({"part0_value": 0, "part1_value": null, "part2_value": null, "part3_value": null, "part4_sum": null, "part4_count": 0}),
+            json!({"part0_value": 1, "part1_value": 1.5, "part2_value": 1.5, "part3_value": "2021-03-04T00:00:00Z", "part4_sum": 1.5, "part4_count": 1}),
+        ] {
+            merge.add(&record(partial)).unwrap();
+        }
+
+        assert_eq!(
+            Json::Object(merge.finish()),
+            json!({
+                "_1": 3,
+                "total": 31.5,
+                "_3": 1.5,
+                "_4": "2021-03-04T00:00:00Z",
+                "average": 10.5,
+            })
+        );
+    }
+
+    #[test]
+    fn timestamps_compare_as_instants() {
+        let select = [
+            Select::Min(Expr::column("logged_at")),
+            Select::Max(Expr::column("logged_at")),
+            Select::Max(Expr::column("name")),
+        ];
+        let (plan, _) = AggregatePlan::new(&select).unwrap();
+        let mut merge = plan.merger();
+
+        for partial in [
+            json!({"part0_value": "2020-01-01T00:00:00.5Z", "part1_value": "2020-01-01T00:00:00.5Z", "part2_value": "b"}),
+            json!({"part0_value": "2020-01-01T00:00:00Z", "part1_value": "2020-01-01T00:00:00Z", "part2_value": "a"}),
+            // 23:00 UTC the day before, sorts after every other value as a string
+            json!({"part0_value": "2020-01-01T01:00:00+02:00", "part1_value": "2020-01-01T01:00:00+02:00", "part2_value": "2020"}),
+            json!({"part0_value": "2020-01-01T00:00:01-01:00", "part1_value": "2020-01-01T00:00:01-01:00", "part2_value": null}),
+        ] {
+            merge.add(&record(partial)).unwrap();
+        }
+
+        assert_eq!(
+            Json::Object(merge.finish()),
+            json!({
+                "_1": "2020-01-01T01:00:00+02:00",
+                "_2": "2020-01-01T00:00:01-01:00",
+                "_3": "b",
+            })
+        );
+    }
+
+    #[test]
+    fn avg_is_not_an_average_of_averages() {
+        let (plan, _) = AggregatePlan::new(&[Select::Avg(Expr::column("age"))]).unwrap();
+        let mut merge = plan.merger();
+
+        merge
+            .add(&record(json!({"part0_sum": 10, "part0_count": 1})))
+            .unwrap();
+        merge
+            .add(&record(json!({"part0_sum": 30, "part0_count": 3})))
+            .unwrap();
+
+        assert_eq!(merge.finish()["_1"], json!(10.0));
+    }
+
+    #[test]
+    fn empty_results() {
+        let (plan, _) = AggregatePlan::new(&select()).unwrap();
+
+        assert_eq!(
+            Json::Object(plan.merger().finish()),
+            json!({"_1": 0, "total": null, "_3": null, "_4": null, "average": null})
+        );
+    }
+
+    #[test]
+    fn incompatible_partials() {
+        let (plan, _) = AggregatePlan::new(&[Select::Min(Expr::column("id"))]).unwrap();
+        let mut merge = plan.merger();
+
+        merge.add(&record(json!({"part0_value": 1}))).unwrap();
+        assert!(merge.add(&record(json!({"part0_value": "a"}))).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use arrow_array::RecordBatch;
+use arrow_json::reader::{infer_json_schema_from_iterator, ReaderBuilder};
+use arrow_schema::{ArrowError, SchemaRef};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde_json::Value as Json;
+use std::sync::Arc;
+
+use super::stream::Record;
+use super::QueryError;
+
+/// Arrow batches of query results, the stream ends after the first error.
+pub type RecordBatches = BoxStream<'static, Result<RecordBatch, QueryError>>;
+
+/// How `record_batches` builds its batches.
+#[derive(Clone, Debug)]
+pub struct ArrowOptions {
+    schema: Option<SchemaRef>,
+    infer_records: usize,
+    batch_size: usize,
+}
+
+impl Default for ArrowOptions {
+    fn default() -> Self {
+        ArrowOptions {
+            schema: None,
+            infer_records: 100,
+            batch_size: 1024,
+        }
+    }
+}
+
+impl ArrowOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schema of the batches, fields missing from a record are null and fields missing from
+    /// the schema are dropped. Without it the schema is inferred, see `infer_records`.
+    pub fn schema(mut self, schema: SchemaRef) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// How many of the first records the schema is inferred from, fields read from CSV input or output are strings.
+    pub fn infer_records(mut self, records: usize) -> Self {
+        self.infer_records = records.max(1);
+        self
+    }
+
+    /// Most rows in a batch, only the last one can be shorter.
+    pub fn batch_size(mut self, rows: usize) -> Self {
+        self.batch_size = rows.max(1);
+        self
+    }
+}
+
+struct Batcher {
+    records: BoxStream<'static, Result<Record, QueryError>>,
+    options: ArrowOptions,
+    pending: Vec<Record>, // read while inferring the schema, not in a batch yet
+    offset: usize,        // records already in a batch
+}
+
+/// Groups the records of a query, e.g. `RecordQueriable::select_s3_records`, into Arrow batches.
+pub fn record_batches<S>(records: S, options: ArrowOptions) -> RecordBatches
+where
+    S: Stream<Item = Result<Record, QueryError>> + Send + 'static,
+{
+    let batcher = Batcher {
+        records: records.boxed(),
+        options,
+        pending: Vec::new(),
+        offset: 0,
+    };
+
+    stream::try_unfold(batcher, |mut batcher| async move {
+        let schema = match &batcher.options.schema {
+            Some(schema) => schema.clone(),
+            None => {
+                while batcher.pending.len() < batcher.options.infer_records {
+                    match batcher.records.next().await {
+                        Some(record) => batcher.pending.push(record?),
+                        None => break,
+                    }
+                }
+                let values = batcher
+                    .pending
+                    .iter()
+                    .map(|record| Ok(Json::Object(record.clone())));
+                let schema =
+                    infer_json_schema_from_iterator(values).map_err(|e| QueryError::Convert {
+                        offset: batcher.offset,
+                        message: e.to_string(),
+                    })?;
+                let schema = Arc::new(schema);
+                batcher.options.schema = Some(schema.clone());
+                schema
+            }
+        };
+
+        let size = batcher.options.batch_size;
+        let mut rows = batcher
+            .pending
+            .drain(..size.min(batcher.pending.len()))
+            .collect::<Vec<Record>>();
+        while rows.len() < size {
+            match batcher.records.next().await {
+                Some(record) => rows.push(record?),
+                None => break,
+            }
+        }
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let batch = to_batch(&schema, &rows).map_err(|batch_error| {
+            // the decoder doesn't say which row failed, find it by converting them one by one
+            let (index, e) = rows
+                .iter()
+                .enumerate()
+                .find_map(|(i, row)| {
+                    to_batch(&schema, std::slice::from_ref(row))
+                        .err()
+                        .map(|e| (i, e))
+                })
+                .unwrap_or((0, batch_error));
+            QueryError::Convert {
+                offset: batcher.offset + index,
+                message: e.to_string(),
+            }
+        })?;
+        batcher.offset += rows.len();
+
+        Ok(Some((batch, batcher)))
+    })
+    .boxed()
+}
+
+fn to_batch(schema: &SchemaRef, rows: &[Record]) -> Result<RecordBatch, ArrowError> {
+    let mut decoder = ReaderBuilder::new(schema.clone())
+        .with_batch_size(rows.len())
+        .build_decoder()?;
+    decoder.serialize(rows)?;
+
+    Ok(decoder
+        .flush()?
+        .unwrap_or_else(|| RecordBatch::new_empty(schema.clone())))
+}
+
+#[cfg(test)]
+mod batches_test {
+    use super::*;
+    use crate::query::stream::RecordDecoder;
+    use crate::query::CsvOutput;
+    use arrow_array::{Array, Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::TryStreamExt;
+    use serde_json::json;
+
+    fn records(values: Vec<Json>) -> impl Stream<Item = Result<Record, QueryError>> {
+        stream::iter(values.into_iter().map(|value| match value {
+            Json::Object(record) => Ok(record),
+            _ => unreachable!(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn inferred_schema() {
+        let values = (0..5)
+            .map(|i| json!({"id": i, "name": format!("n{}", i)}))
+            .collect();
+        let batches = record_batches(
+            records(values),
+            ArrowOptions::new().infer_records(2).batch_size(2),
+        )
+        .try_collect::<Vec<RecordBatch>>()
+        .await
+        .unwrap();
+
+        assert_eq!(
+            batches
+                .iter()
+                .map(RecordBatch::num_rows)
+                .collect::<Vec<usize>>(),
+            vec![2, 2, 1]
+        );
+        let schema = batches[0].schema();
+        assert_eq!(
+            schema.field_with_name("id").unwrap().data_type(),
+            &DataType::Int64
+        );
+        let ids = batches[2]
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 4);
+    }
+
+    #[tokio::test]
+    async fn given_schema() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("_1", DataType::Int64, false),
+            Field::new("_2", DataType::Utf8, true),
+        ]));
+        // JSON output of CSV input without headers, every field is a string
+        let values = vec![json!({"_1": "1", "_2": "a"}), json!({"_1": "2", "_3": "x"})];
+        let batches = record_batches(records(values), ArrowOptions::new().schema(schema.clone()))
+            .try_collect::<Vec<RecordBatch>>()
+            .await
+            .unwrap();
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].schema(), schema);
+        let names = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "a");
+        assert!(names.is_null(1));
+    }
+
+    #[tokio::test]
+    async fn csv_output() {
+        let names = vec!["id".to_string(), "city".to_string()];
+        let mut decoder = RecordDecoder::csv(&CsvOutput::new(), names).unwrap();
+        let mut rows = decoder.push(b"1,porto\n,lisbon\n").unwrap();
+        rows.extend(decoder.finish().unwrap());
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("city", DataType::Utf8, false),
+        ]));
+
+        let batches = record_batches(
+            stream::iter(rows.clone().into_iter().map(Ok)),
+            ArrowOptions::new().schema(schema),
+        )
+        .try_collect::<Vec<RecordBatch>>()
+        .await
+        .unwrap();
+        let ids = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 1);
+        assert!(ids.is_null(1));
+
+        let batches = record_batches(stream::iter(rows.into_iter().map(Ok)), ArrowOptions::new())
+            .try_collect::<Vec<RecordBatch>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            batches[0]
+                .schema()
+                .field_with_name("id")
+                .unwrap()
+                .data_type(),
+            &DataType::Utf8
+        );
+    }
+
+    #[tokio::test]
+    async fn conversion_error_offset() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, true)]));
+        let values = vec![
+            json!({"id": 1}),
+            json!({"id": 2}),
+            json!({"id": 3}),
+            json!({"id": "three"}),
+        ];
+        let mut batches = record_batches(
+            records(values),
+            ArrowOptions::new().schema(schema).batch_size(2),
+        );
+
+        assert!(batches.next().await.unwrap().is_ok());
+        assert!(matches!(
+            batches.next().await.unwrap(),
+            Err(QueryError::Convert { offset: 3, .. })
+        ));
+        assert!(batches.next().await.is_none());
+    }
+}
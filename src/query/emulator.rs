@@ -0,0 +1,1091 @@
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use rusoto_core::request::BufferedHttpResponse;
+use rusoto_core::RusotoError;
+use rusoto_s3::ListObjectsV2Error;
+use serde_json::{Deserializer, Value as Json};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, PathBuf};
+use std::sync::Arc;
+
+use crate::encryption::CustomerKey;
+
+use super::aggregate::AggregatePlan;
+use super::eval::{eval, lookup, test, Datum, InputRecord};
+use super::local::{csv_field, csv_names, outputs_of, to_csv_record, Item};
+use super::objects::{QueryObject, RecordQueriable, Records};
+use super::path::Path;
+use super::stream::{csv_byte, csv_record, Record, RecordDecoder};
+use super::{
+    CompressionType, CsvInput, Expr, FileHeader, InputObjectFormat, JsonType, ObjectTarget,
+    OutputObjectFormat, QueryContent, QueryError, ScanRange, Select,
+};
+
+enum Store {
+    Memory(BTreeMap<String, BTreeMap<String, Vec<u8>>>), // bucket, key, body
+    Dir(PathBuf),                                        // `<dir>/<bucket>/<key>`
+}
+
+/// S3 Select run locally, on objects kept in memory or in a directory, so queries can be
+/// tested without AWS or localstack-pro.
+///
+/// Reads JSON Document, JSON Lines and CSV objects, GZIP or BZIP2 compressed, and returns
+/// records as S3 Select's JSON or CSV output has them. Errors carry the codes S3 sends, so mismatched
+/// objects are skipped by `query_s3_objects` as they are with `SelectClient`.
+pub struct SelectEmulator {
+    store: Store,
+}
+
+impl Default for SelectEmulator {
+    fn default() -> Self {
+        SelectEmulator {
+            store: Store::Memory(BTreeMap::new()),
+        }
+    }
+}
+
+impl SelectEmulator {
+    /// Emulator over objects kept in memory, added with `put_object`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emulator over the files in `root`, where each directory is a bucket.
+    pub fn from_dir(root: PathBuf) -> Self {
+        SelectEmulator {
+            store: Store::Dir(root),
+        }
+    }
+
+    /// Stores `body` as `key`, creating `bucket` if it doesn't exist.
+    pub fn put_object(
+        &mut self,
+        bucket: &str,
+        key: &str,
+        body: impl Into<Vec<u8>>,
+    ) -> Result<(), QueryError> {
+        match &mut self.store {
+            Store::Memory(buckets) => {
+                buckets
+                    .entry(bucket.to_string())
+                    .or_default()
+                    .insert(key.to_string(), body.into());
+                Ok(())
+            }
+            Store::Dir(root) => {
+                let path = object_path(root, bucket, key).ok_or_else(|| {
+                    QueryError::Invalid(format!("`{}` can't be stored as a file", key))
+                })?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(QueryError::Io)?;
+                }
+                fs::write(path, body.into()).map_err(QueryError::Io)
+            }
+        }
+    }
+
+    fn object(&self, bucket: &str, key: &str) -> Result<Vec<u8>, QueryError> {
+        let body = match &self.store {
+            Store::Memory(buckets) => match buckets.get(bucket) {
+                Some(objects) => objects.get(key).cloned(),
+                None => return Err(not_found("NoSuchBucket", bucket)),
+            },
+            Store::Dir(root) => {
+                if !root.join(bucket).is_dir() {
+                    return Err(not_found("NoSuchBucket", bucket));
+                }
+                object_path(root, bucket, key)
+                    .filter(|path| path.is_file())
+                    .map(fs::read)
+                    .transpose()
+                    .map_err(QueryError::Io)?
+            }
+        };
+
+        body.ok_or_else(|| not_found("NoSuchKey", key))
+    }
+}
+
+// keys with `.` or `..` segments would read outside of the bucket directory
+fn object_path(root: &std::path::Path, bucket: &str, key: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(key);
+    if key.is_empty()
+        || path
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    Some(root.join(bucket).join(path))
+}
+
+// same error response S3 sends, so `QueryError::code` reads it
+fn not_found(code: &str, name: &str) -> QueryError {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>`{}` does not exist</Message></Error>",
+        code, name
+    );
+    let response = BufferedHttpResponse {
+        status: 404u16.try_into().expect("404 is a status code"),
+        body: body.into(),
+        headers: Default::default(),
+    };
+
+    QueryError::Select(Box::new(RusotoError::Unknown(response)))
+}
+
+fn failed(code: &str, message: String) -> QueryError {
+    QueryError::Failed {
+        code: code.to_string(),
+        message,
+    }
+}
+
+#[async_trait]
+impl RecordQueriable for SelectEmulator {
+    async fn select_s3_records(
+        &self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+    ) -> Result<Records, QueryError> {
+        query.validate(&input_serialization, &body_compression)?;
+        query.build().map_err(QueryError::Invalid)?;
+        // fails on CSV output characters `SelectClient` can't read back
+        RecordDecoder::new(&output_serialization, &query.select)?;
+        let (key, bucket) = match query.from.clone() {
+            Some((ObjectTarget::Key(key), bucket)) => (key, bucket),
+            _ => {
+                return Err(QueryError::Invalid(
+                    "S3 Select reads a single object, use `query_s3_objects`".to_string(),
+                ))
+            }
+        };
+
+        let body = self.object(&bucket, &key)?;
+        let records = select(
+            &query,
+            &body,
+            &body_compression,
+            &input_serialization,
+            &output_serialization,
+        )?;
+
+        Ok(stream::iter(records).boxed())
+    }
+
+    async fn s3_object_range(
+        &self,
+        bucket: String,
+        key: String,
+        range: ScanRange,
+        _customer_key: Option<CustomerKey>,
+    ) -> Result<Vec<u8>, QueryError> {
+        let body = self.object(&bucket, &key)?;
+        if body.is_empty() {
+            return Ok(body);
+        }
+        let (first, last) = range.bounds(body.len() as u64);
+
+        Ok(body
+            .get(first as usize..=last as usize)
+            .unwrap_or_default()
+            .to_vec())
+    }
+
+    async fn list_s3_query_objects(
+        &self,
+        bucket: String,
+        prefix: String,
+    ) -> Result<Vec<QueryObject>, QueryError> {
+        let no_such_bucket = || {
+            QueryError::List(Box::new(RusotoError::Service(
+                ListObjectsV2Error::NoSuchBucket(bucket.clone()),
+            )))
+        };
+
+        let mut objects = match &self.store {
+            Store::Memory(buckets) => buckets
+                .get(&bucket)
+                .ok_or_else(no_such_bucket)?
+                .iter()
+                .map(|(key, body)| QueryObject {
+                    key: key.clone(),
+                    size: body.len() as i64,
+                })
+                .collect(),
+            Store::Dir(root) => {
+                let dir = root.join(&bucket);
+                if !dir.is_dir() {
+                    return Err(no_such_bucket());
+                }
+                let mut objects = Vec::new();
+                list_files(&dir, "", &mut objects)?;
+                objects
+            }
+        };
+
+        objects.retain(|object: &QueryObject| object.key.starts_with(&prefix));
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+fn list_files(
+    dir: &std::path::Path,
+    prefix: &str,
+    objects: &mut Vec<QueryObject>,
+) -> Result<(), QueryError> {
+    for entry in fs::read_dir(dir).map_err(QueryError::Io)? {
+        let entry = entry.map_err(QueryError::Io)?;
+        let key = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let metadata = entry.metadata().map_err(QueryError::Io)?;
+
+        if metadata.is_dir() {
+            list_files(&entry.path(), &format!("{}/", key), objects)?;
+        } else {
+            objects.push(QueryObject {
+                key,
+                size: metadata.len() as i64,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Results of `query` on an object, S3 ends the stream at the first error.
+fn select(
+    query: &QueryContent,
+    body: &[u8],
+    compression: &CompressionType,
+    input: &InputObjectFormat,
+    output: &OutputObjectFormat,
+) -> Result<Vec<Result<Record, QueryError>>, QueryError> {
+    let csv = matches!(output, OutputObjectFormat::CSV(_));
+    let range = match &query.scan_range {
+        Some(range) => {
+            let splittable = matches!(
+                input,
+                InputObjectFormat::JSON(JsonType::Lines) | InputObjectFormat::CSV(_)
+            );
+            if !splittable || *compression != CompressionType::NONE {
+                return Err(failed(
+                    "UnsupportedScanRangeInput",
+                    "scan ranges need uncompressed CSV or JSON Lines".to_string(),
+                ));
+            }
+            Some(range.bounds(body.len() as u64))
+        }
+        None => None,
+    };
+    let in_range = |start: u64| range.is_none_or(|(first, last)| start >= first && start <= last);
+
+    let body = decompress(body, compression)?;
+    let (records, error) = match input {
+        InputObjectFormat::JSON(json) => json_records(&body, json, query.path.as_deref(), in_range),
+        InputObjectFormat::CSV(csv) => csv_records(&body, csv, in_range)?,
+        InputObjectFormat::Parquet => {
+            return Err(QueryError::Invalid(
+                "the emulator reads JSON and CSV objects, not Parquet".to_string(),
+            ))
+        }
+    };
+
+    if query.select.iter().any(is_aggregate) {
+        if query.limit == Some(0) {
+            return Ok(Vec::new());
+        }
+        if let Some(error) = error {
+            return Ok(vec![Err(error)]);
+        }
+        let record = aggregate(query, &records);
+        return Ok(vec![match csv {
+            true => record.map(|record| to_csv_record(&query.select, &record)),
+            false => record,
+        }]);
+    }
+
+    let mut results = Vec::new();
+    for record in &records {
+        if query.limit.is_some_and(|limit| results.len() >= limit) {
+            return Ok(results);
+        }
+        match matches(query, record).and_then(|matched| match matched {
+            true if csv => project_csv(query, record).map(Some),
+            true => project(query, record).map(Some),
+            false => Ok(None),
+        }) {
+            Ok(Some(projected)) => results.push(Ok(projected)),
+            Ok(None) => (),
+            Err(e) => {
+                results.push(Err(e));
+                return Ok(results);
+            }
+        }
+    }
+    if let Some(error) = error {
+        if query.limit.is_none_or(|limit| results.len() < limit) {
+            results.push(Err(error));
+        }
+    }
+
+    Ok(results)
+}
+
+fn decompress(body: &[u8], compression: &CompressionType) -> Result<Vec<u8>, QueryError> {
+    let mut decompressed = Vec::new();
+    let read = match compression {
+        CompressionType::NONE => return Ok(body.to_vec()),
+        CompressionType::GZIP => {
+            flate2::read::MultiGzDecoder::new(body).read_to_end(&mut decompressed)
+        }
+        CompressionType::BZIP2 => bzip2_rs::DecoderReader::new(body).read_to_end(&mut decompressed),
+    };
+
+    read.map(|_| decompressed).map_err(|e| {
+        failed(
+            "InvalidCompressionFormat",
+            format!("object isn't {:?} compressed: {}", compression, e),
+        )
+    })
+}
+
+// records read before the first malformed one, and the error it caused
+type Input = (Vec<InputRecord>, Option<QueryError>);
+
+// `in_range` tells if a record starting at a byte offset is in the query's scan range
+fn json_records(
+    body: &[u8],
+    json: &JsonType,
+    path: Option<&[Path]>,
+    in_range: impl Fn(u64) -> bool,
+) -> Input {
+    let mut values = Vec::new();
+    let mut error = None;
+    let parse_error = |e: serde_json::Error| failed("JSONParsingError", e.to_string());
+
+    match json {
+        JsonType::Document => {
+            for value in Deserializer::from_slice(body).into_iter::<Json>() {
+                match value {
+                    Ok(value) => values.push(value),
+                    Err(e) => {
+                        error = Some(parse_error(e));
+                        break;
+                    }
+                }
+            }
+        }
+        JsonType::Lines => {
+            let mut offset = 0;
+            for line in body.split(|b| *b == b'\n') {
+                let start = line.iter().position(|b| !b.is_ascii_whitespace());
+                let line_offset = offset;
+                offset += line.len() as u64 + 1;
+                match start {
+                    Some(start) if in_range(line_offset + start as u64) => (),
+                    _ => continue,
+                }
+                match serde_json::from_slice(line) {
+                    Ok(value) => values.push(value),
+                    Err(e) => {
+                        error = Some(parse_error(e));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // `S3Object[*]` is every top level value, the rest of the path is read in each of them
+    let path = match path {
+        Some([Path::WildCardIndex, path @ ..]) => path,
+        Some(path) => path,
+        None => &[],
+    };
+    let records = values
+        .iter()
+        .flat_map(|value| lookup(value, path))
+        .map(|value| InputRecord::Json(value.clone()))
+        .collect();
+
+    (records, error)
+}
+
+fn csv_records(
+    body: &[u8],
+    csv: &CsvInput,
+    in_range: impl Fn(u64) -> bool,
+) -> Result<Input, QueryError> {
+    let quote = csv_byte(csv.quote_character, "quote character")?;
+    let escape = csv_byte(csv.quote_escape_character, "quote escape character")?;
+    let terminator = match csv.record_delimiter {
+        '\n' => csv::Terminator::CRLF,
+        delimiter => csv::Terminator::Any(csv_byte(delimiter, "record delimiter")?),
+    };
+    let comments = match csv.comments {
+        Some(comments) => Some(csv_byte(comments, "comment character")?),
+        None => None,
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(csv_byte(csv.field_delimiter, "field delimiter")?)
+        .quote(quote)
+        .double_quote(escape == quote)
+        .escape(Some(escape).filter(|escape| *escape != quote))
+        .terminator(terminator)
+        .comment(comments)
+        .from_reader(body);
+
+    let mut header = None;
+    let mut records = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let (start, fields) = match record {
+            Ok(record) => (
+                record.position().map_or(0, |position| position.byte()),
+                record.iter().map(String::from).collect::<Vec<String>>(),
+            ),
+            Err(e) => return Ok((records, Some(failed("CSVParsingError", e.to_string())))),
+        };
+        match csv.file_header {
+            FileHeader::Use if i == 0 => header = Some(Arc::new(fields)),
+            FileHeader::Ignore if i == 0 => (),
+            _ if !in_range(start) => (),
+            _ => records.push(InputRecord::Csv {
+                header: header.clone(),
+                fields,
+            }),
+        }
+    }
+
+    Ok((records, None))
+}
+
+fn is_aggregate(select: &Select) -> bool {
+    match select {
+        Select::Elements(_) => false,
+        Select::As(select, _) => is_aggregate(select),
+        _ => true,
+    }
+}
+
+fn matches(query: &QueryContent, record: &InputRecord) -> Result<bool, QueryError> {
+    match &query.clauses {
+        Some(clause) => Ok(test(clause, record)? == Some(true)),
+        None => Ok(true),
+    }
+}
+
+// output fields named like S3 names them, missing values are left out
+fn project(query: &QueryContent, record: &InputRecord) -> Result<Record, QueryError> {
+    let mut projected = Record::new();
+
+    for (name, item) in outputs_of(&query.select) {
+        match item {
+            Item::Value(Expr::Wildcard) => projected.extend(record.to_record()),
+            Item::Value(expr) => {
+                if let Some(value) = eval(&expr, record)?.into_json() {
+                    projected.insert(name, value);
+                }
+            }
+            Item::Aggregate(_) => (),
+        }
+    }
+
+    Ok(projected)
+}
+
+// fields in select order, keyed as `SelectClient` reads CSV output back
+fn project_csv(query: &QueryContent, record: &InputRecord) -> Result<Record, QueryError> {
+    let mut fields = Vec::new();
+
+    for (_, item) in outputs_of(&query.select) {
+        match item {
+            Item::Value(Expr::Wildcard) => fields.extend(record.csv_fields()),
+            Item::Value(expr) => fields.push(
+                eval(&expr, record)?
+                    .into_json()
+                    .map_or_else(String::new, |value| csv_field(&value)),
+            ),
+            Item::Aggregate(_) => (),
+        }
+    }
+
+    Ok(csv_record(&csv_names(&query.select), fields))
+}
+
+fn aggregate(query: &QueryContent, records: &[InputRecord]) -> Result<Record, QueryError> {
+    let (plan, _) = AggregatePlan::new(&query.select).ok_or_else(|| {
+        QueryError::Invalid("aggregates can't be mixed with plain columns".to_string())
+    })?;
+    let mut merge = plan.merger();
+
+    for record in records {
+        if !matches(query, record)? {
+            continue;
+        }
+        let mut arguments = Vec::new();
+        for select in &query.select {
+            arguments.push(aggregate_argument(select, record)?);
+        }
+        merge.add(&plan.row_partial(&arguments))?;
+    }
+
+    Ok(merge.finish())
+}
+
+fn aggregate_argument(select: &Select, record: &InputRecord) -> Result<Json, QueryError> {
+    let (name, e) = match select {
+        Select::As(select, _) => return aggregate_argument(select, record),
+        Select::Count(Expr::Wildcard) => return Ok(Json::Bool(true)),
+        Select::Count(e) => ("COUNT", e),
+        Select::Sum(e) => ("SUM", e),
+        Select::Avg(e) => ("AVG", e),
+        Select::Min(e) => ("MIN", e),
+        Select::Max(e) => ("MAX", e),
+        Select::Elements(_) => return Ok(Json::Null),
+    };
+
+    let value = eval(e, record)?;
+    let numeric = matches!(
+        value,
+        Datum::Int(_) | Datum::Float(_) | Datum::Null | Datum::Missing
+    );
+    if (name == "SUM" || name == "AVG") && !numeric {
+        return Err(failed(
+            "InvalidDataType",
+            format!(
+                "{} of `{}` needs numbers, got {:?}",
+                name,
+                e.to_sql(),
+                value
+            ),
+        ));
+    }
+
+    Ok(value.into_json().unwrap_or(Json::Null))
+}
+
+#[cfg(test)]
+mod emulator_test {
+    use super::*;
+    use crate::query::{
+        CastType, Clause, CsvOutput, Field, FieldType, ObjectRecord, QueryOptions, ScanRange,
+    };
+    use futures::TryStreamExt;
+    use serde_json::json;
+    use std::io::Write;
+
+    const LINES: &str = "{\"name\": \"world\"}
+{\"name\": \"india\", \"count\":1000}
+{\"name\": \"china\", \"count\":1300}
+{\"name\": \"ghana\"}
+{\"hello\": \"brasil\", \"count\":200}";
+
+    fn emulator() -> SelectEmulator {
+        let mut emulator = SelectEmulator::new();
+        emulator.put_object("bucket", "lines.json", LINES).unwrap();
+        emulator
+            .put_object(
+                "bucket",
+                "document.json",
+                "{\"rules\": [{\"id\": 1, \"expr\": \"y > x\"},\n {\"id\": 2}]}",
+            )
+            .unwrap();
+        emulator
+            .put_object(
+                "bucket",
+                "countries.csv",
+                "name,count\nindia,1000\n\"ghana, west africa\",10\n",
+            )
+            .unwrap();
+        emulator
+    }
+
+    async fn run(
+        emulator: &SelectEmulator,
+        query: QueryContent,
+        compression: CompressionType,
+        input: InputObjectFormat,
+    ) -> Result<Vec<Json>, QueryError> {
+        let records = emulator
+            .select_s3_records(query, compression, input, OutputObjectFormat::JSON(None))
+            .await?
+            .try_collect::<Vec<Record>>()
+            .await?;
+        Ok(records.into_iter().map(Json::Object).collect())
+    }
+
+    fn lines() -> InputObjectFormat {
+        InputObjectFormat::JSON(JsonType::Lines)
+    }
+
+    #[tokio::test]
+    async fn where_and_limit() {
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+            .from("bucket", "lines.json")
+            .limit(2)
+            .where_clause(Clause::IsNotNull(Expr::column("count")));
+
+        let records = run(&emulator(), query, CompressionType::NONE, lines())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            records,
+            vec![json!({"name": "india"}), json!({"name": "china"})]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_fields_and_names() {
+        let query = QueryContent::select(vec![
+            Select::Elements(vec![Expr::column("name"), Expr::column("count") * 2]),
+            Select::Elements(vec![Expr::column("name").upper()]).alias("upper"),
+        ])
+        .from("bucket", "lines.json");
+
+        let records = run(&emulator(), query, CompressionType::NONE, lines())
+            .await
+            .unwrap();
+
+        assert_eq!(records[0], json!({"name": "world", "upper": "WORLD"}));
+        assert_eq!(
+            records[1],
+            json!({"name": "india", "_2": 2000, "upper": "INDIA"})
+        );
+        assert_eq!(records[4], json!({"_2": 400}));
+    }
+
+    #[tokio::test]
+    async fn aggregates() {
+        let query = QueryContent::select(vec![
+            Select::Count(Expr::Wildcard),
+            Select::Count(Expr::column("count")),
+            Select::Sum(Expr::column("count")).alias("total"),
+            Select::Avg(Expr::column("count")),
+            Select::Max(Expr::column("name")),
+        ])
+        .from("bucket", "lines.json")
+        .where_clause(Clause::NotE(Expr::column("name"), "world".into()));
+
+        let records = run(&emulator(), query, CompressionType::NONE, lines())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            records,
+            vec![json!({"_1": 3, "_2": 2, "total": 2300, "_4": 1150.0, "_5": "india"})]
+        );
+    }
+
+    #[tokio::test]
+    async fn document_paths() {
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::Wildcard])])
+            .from("bucket", "document.json")
+            .from_path(vec![
+                Path::WildCardIndex,
+                Path::Name("rules".to_string()),
+                Path::WildCardIndex,
+            ]);
+
+        let records = run(
+            &emulator(),
+            query.clone(),
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Document),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![json!({"id": 1, "expr": "y > x"}), json!({"id": 2})]
+        );
+
+        let lines = run(&emulator(), query, CompressionType::NONE, lines()).await;
+        assert!(lines.unwrap_err().is_format_mismatch());
+    }
+
+    #[tokio::test]
+    async fn csv() {
+        let query = QueryContent::select(vec![Select::Elements(vec![
+            Expr::column("name"),
+            Expr::column("_2").cast(CastType::Int),
+        ])])
+        .from("bucket", "countries.csv")
+        .where_clause(Clause::L(
+            Expr::column("count").cast(CastType::Int),
+            100.into(),
+        ));
+
+        let records = run(
+            &emulator(),
+            query.clone(),
+            CompressionType::NONE,
+            InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Use)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![json!({"name": "ghana, west africa", "_2": 10})]
+        );
+
+        let ignored = QueryContent::select(vec![Select::Elements(vec![Expr::Wildcard])])
+            .from("bucket", "countries.csv");
+        let records = run(
+            &emulator(),
+            ignored,
+            CompressionType::NONE,
+            InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Ignore)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(records[0], json!({"_1": "india", "_2": "1000"}));
+    }
+
+    #[tokio::test]
+    async fn csv_output() {
+        let mut emulator = emulator();
+        emulator
+            .put_object(
+                "bucket",
+                "cities.csv",
+                "id,city\n1,\"porto, north\"\n,lisbon\n",
+            )
+            .unwrap();
+        let input = || InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Use));
+        let select = |query: QueryContent| {
+            emulator.select_s3_records(
+                query,
+                CompressionType::NONE,
+                input(),
+                OutputObjectFormat::CSV(CsvOutput::new()),
+            )
+        };
+
+        let query = QueryContent::select(vec![Select::Elements(vec![
+            Expr::column("city"),
+            Expr::column("id"),
+        ])])
+        .from("bucket", "cities.csv");
+        let records = select(query)
+            .await
+            .unwrap()
+            .try_collect::<Vec<Record>>()
+            .await;
+        assert_eq!(
+            records.unwrap(),
+            vec![
+                json!({"city": "porto, north", "id": "1"}),
+                json!({"city": "lisbon", "id": null}),
+            ]
+            .into_iter()
+            .map(|record| record.as_object().unwrap().clone())
+            .collect::<Vec<Record>>()
+        );
+
+        // `*` fields are only known by position
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::Wildcard])])
+            .from("bucket", "cities.csv");
+        let records = select(query)
+            .await
+            .unwrap()
+            .try_collect::<Vec<Record>>()
+            .await;
+        assert_eq!(
+            Json::Object(records.unwrap().remove(0)),
+            json!({"_1": "1", "_2": "porto, north"})
+        );
+
+        let count = QueryContent::select(vec![Select::Count(Expr::Wildcard)])
+            .from_prefix("bucket", "cities");
+        let records = emulator
+            .query_s3_records(
+                count,
+                CompressionType::NONE,
+                input(),
+                OutputObjectFormat::CSV(CsvOutput::new()),
+                QueryOptions::new(),
+            )
+            .await
+            .unwrap()
+            .try_collect::<Vec<Record>>()
+            .await;
+        assert_eq!(Json::Object(records.unwrap().remove(0)), json!({"_1": "2"}));
+
+        let invalid = QueryContent::select(vec![Select::Elements(vec![Expr::Wildcard])])
+            .from("bucket", "cities.csv");
+        let error = emulator
+            .select_s3_records(
+                invalid,
+                CompressionType::NONE,
+                input(),
+                OutputObjectFormat::CSV(CsvOutput::new().field_delimiter('§')),
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, QueryError::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn compressed() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(LINES.as_bytes()).unwrap();
+        let mut emulator = emulator();
+        emulator
+            .put_object("bucket", "lines.json.gz", gzip.finish().unwrap())
+            .unwrap();
+        let query = || {
+            QueryContent::select(vec![Select::Count(Expr::Wildcard)])
+                .from("bucket", "lines.json.gz")
+        };
+
+        let records = run(&emulator, query(), CompressionType::GZIP, lines())
+            .await
+            .unwrap();
+        assert_eq!(records, vec![json!({"_1": 5})]);
+
+        let error = run(&emulator, query(), CompressionType::BZIP2, lines())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some("InvalidCompressionFormat".to_string()));
+    }
+
+    #[tokio::test]
+    async fn missing_objects() {
+        let query = |bucket: &str| {
+            QueryContent::select(vec![Select::Elements(vec![Expr::Wildcard])])
+                .from(bucket, "absent.json")
+        };
+
+        let error = run(&emulator(), query("bucket"), CompressionType::NONE, lines())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some("NoSuchKey".to_string()));
+        let error = run(&emulator(), query("other"), CompressionType::NONE, lines())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some("NoSuchBucket".to_string()));
+    }
+
+    #[tokio::test]
+    async fn objects_in_a_directory() {
+        let root = std::env::temp_dir().join(format!("s3ql-emulator-{}", std::process::id()));
+        let mut emulator = SelectEmulator::from_dir(root.clone());
+        emulator
+            .put_object("bucket", "logs/a.json", "{\"id\": 1}")
+            .unwrap();
+        emulator
+            .put_object("bucket", "logs/b.csv", "id\n2")
+            .unwrap();
+        emulator
+            .put_object("bucket", "other.json", "{\"id\": 3}")
+            .unwrap();
+        assert!(emulator
+            .put_object("bucket", "../escape.json", "{}")
+            .is_err());
+
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("id")])])
+            .from_prefix("bucket", "logs/");
+        let records = emulator
+            .query_s3_objects(
+                query,
+                CompressionType::NONE,
+                lines(),
+                OutputObjectFormat::JSON(None),
+                QueryOptions::new(),
+            )
+            .await
+            .unwrap()
+            .try_collect::<Vec<ObjectRecord>>()
+            .await;
+        fs::remove_dir_all(root).unwrap();
+
+        let records = records.unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.contains(&ObjectRecord::Record {
+            key: "logs/a.json".to_string(),
+            record: json!({"id": 1}).as_object().unwrap().clone(),
+        }));
+        assert!(records.contains(&ObjectRecord::Skipped {
+            key: "logs/b.csv".to_string(),
+            reason: "object is CSV, not JSON".to_string(),
+        }));
+    }
+
+    #[tokio::test]
+    async fn scan_range() {
+        // records are read when their first byte is in the range
+        let query = |range: ScanRange| {
+            QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+                .from("bucket", "lines.json")
+                .scan_range(range)
+        };
+
+        let records = run(
+            &emulator(),
+            query(ScanRange::Between(1, 20)),
+            CompressionType::NONE,
+            lines(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(records, vec![json!({"name": "india"})]);
+
+        let records = run(
+            &emulator(),
+            query(ScanRange::Last(40)),
+            CompressionType::NONE,
+            lines(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(records, vec![json!({})]);
+
+        let error = run(
+            &emulator(),
+            query(ScanRange::From(0)),
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Document),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, QueryError::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn parallel_scan_ranges() {
+        let body = (0..100)
+            .map(|i| format!("{{\"id\": {}, \"padding\": \"{}\"}}", i, "x".repeat(i % 7)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let mut emulator = emulator();
+        emulator.put_object("bucket", "big.json", body).unwrap();
+        let query = || {
+            QueryContent::select(vec![Select::Elements(vec![Expr::column("id")])])
+                .from("bucket", "big.json")
+        };
+
+        let mut ids = emulator
+            .query_s3_records(
+                query(),
+                CompressionType::NONE,
+                lines(),
+                OutputObjectFormat::JSON(None),
+                QueryOptions::new().scan_ranges(7),
+            )
+            .await
+            .unwrap()
+            .map_ok(|record| record["id"].as_i64().unwrap())
+            .try_collect::<Vec<i64>>()
+            .await
+            .unwrap();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..100).collect::<Vec<i64>>());
+
+        let sum = emulator
+            .query_s3_records(
+                QueryContent::select(vec![
+                    Select::Sum(Expr::column("id")),
+                    Select::Count(Expr::Wildcard),
+                ])
+                .from("bucket", "big.json"),
+                CompressionType::NONE,
+                lines(),
+                OutputObjectFormat::JSON(None),
+                QueryOptions::new().scan_ranges(7),
+            )
+            .await
+            .unwrap()
+            .try_collect::<Vec<Record>>()
+            .await
+            .unwrap();
+        assert_eq!(Json::Object(sum[0].clone()), json!({"_1": 4950, "_2": 100}));
+    }
+
+    #[tokio::test]
+    async fn object_range_and_schema() {
+        let emulator = emulator();
+        let range = |range: ScanRange| {
+            emulator.s3_object_range("bucket".to_string(), "lines.json".to_string(), range, None)
+        };
+        assert_eq!(range(ScanRange::Between(2, 5)).await.unwrap(), b"name");
+        assert_eq!(range(ScanRange::Last(3)).await.unwrap(), b"00}");
+
+        let schema = emulator
+            .infer_s3_schema(
+                "bucket".to_string(),
+                "lines.json".to_string(),
+                CompressionType::NONE,
+                lines(),
+                3,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            schema.fields,
+            vec![
+                Field::new("name", FieldType::String, false),
+                Field::new("count", FieldType::Int, true),
+            ]
+        );
+
+        let error = emulator
+            .infer_s3_schema(
+                "bucket".to_string(),
+                "lines.json".to_string(),
+                CompressionType::NONE,
+                InputObjectFormat::Parquet,
+                3,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, QueryError::Decode(_)));
+    }
+
+    async fn matching(emulator: &SelectEmulator, clause: Clause) -> usize {
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+            .from("bucket", "lines.json")
+            .where_clause(clause);
+
+        run(emulator, query, CompressionType::NONE, lines())
+            .await
+            .unwrap()
+            .len()
+    }
+
+    #[tokio::test]
+    async fn non_finite_floats() {
+        let emulator = emulator();
+        let cases = vec![
+            (
+                f64::NEG_INFINITY,
+                "-inf",
+                Clause::G as fn(Expr, Expr) -> Clause,
+                3,
+            ),
+            (f64::INFINITY, "+inf", Clause::L, 3),
+            (f64::NAN, "nan", Clause::E, 0),
+        ];
+
+        for (value, text, compare, expected) in cases {
+            // the SQL sent for the literal is this cast, both must match the same records
+            let cast = Expr::from(text).cast(CastType::Float);
+            assert_eq!(Expr::from(value).to_sql(), cast.to_sql());
+
+            let literal = matching(&emulator, compare(Expr::column("count"), value.into())).await;
+            let cast = matching(&emulator, compare(Expr::column("count"), cast)).await;
+            assert_eq!((literal, cast), (expected, expected), "{}", text);
+        }
+    }
+}
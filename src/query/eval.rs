@@ -0,0 +1,1120 @@
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, NaiveTime, SecondsFormat,
+    Timelike, Utc,
+};
+use serde_json::{Number, Value as Json};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use super::local::csv_field;
+use super::path::{is_identifier, Path};
+use super::stream::Record;
+use super::{
+    CastType, Clause, DatePart, Expr, ExtractPart, QueryError, TrimSide, ValidationError, Value,
+};
+
+/// Value of an expression, `Missing` is a field the record doesn't have.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Datum {
+    Missing,
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Timestamp(DateTime<FixedOffset>),
+    Json(Json), // arrays and objects
+}
+
+impl Datum {
+    /// Output value, `None` for missing fields, which S3 leaves out of the record.
+    pub(crate) fn into_json(self) -> Option<Json> {
+        match self {
+            Self::Missing => None,
+            Self::Null => Some(Json::Null),
+            Self::Bool(b) => Some(Json::Bool(b)),
+            Self::Int(i) => Some(Json::from(i)),
+            Self::Float(f) => Some(Number::from_f64(f).map_or(Json::Null, Json::Number)),
+            Self::String(s) => Some(Json::String(s)),
+            Self::Timestamp(t) => Some(Json::String(timestamp_text(&t))),
+            Self::Json(value) => Some(value),
+        }
+    }
+
+    fn is_absent(&self) -> bool {
+        matches!(self, Self::Missing | Self::Null)
+    }
+}
+
+impl From<&Json> for Datum {
+    fn from(value: &Json) -> Self {
+        match value {
+            Json::Null => Datum::Null,
+            Json::Bool(b) => Datum::Bool(*b),
+            Json::Number(n) => match n.as_i64() {
+                Some(i) => Datum::Int(i),
+                None => Datum::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Json::String(s) => Datum::String(s.clone()),
+            value => Datum::Json(value.clone()),
+        }
+    }
+}
+
+/// Record a query runs on, JSON values or the fields of a CSV line.
+#[derive(Clone, Debug)]
+pub(crate) enum InputRecord {
+    Json(Json),
+    Csv {
+        header: Option<Arc<Vec<String>>>,
+        fields: Vec<String>,
+    },
+}
+
+impl InputRecord {
+    /// Record as `SELECT *` returns it, values that aren't objects are named `_1`.
+    pub(crate) fn to_record(&self) -> Record {
+        match self {
+            Self::Json(Json::Object(fields)) => fields.clone(),
+            Self::Json(value) => {
+                let mut record = Record::new();
+                record.insert("_1".to_string(), value.clone());
+                record
+            }
+            Self::Csv { header, fields } => fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let name = header
+                        .as_ref()
+                        .and_then(|header| header.get(i).cloned())
+                        .unwrap_or_else(|| format!("_{}", i + 1));
+                    (name, Json::String(field.clone()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Fields of `SELECT *` as CSV output writes them, JSON objects in name order.
+    pub(crate) fn csv_fields(&self) -> Vec<String> {
+        match self {
+            Self::Json(Json::Object(fields)) => fields.values().map(csv_field).collect(),
+            Self::Json(value) => vec![csv_field(value)],
+            Self::Csv { fields, .. } => fields.clone(),
+        }
+    }
+
+    fn column(&self, path: &[Path]) -> Datum {
+        match self {
+            Self::Json(value) => {
+                let found = lookup(value, path);
+                if path
+                    .iter()
+                    .any(|p| matches!(p, Path::WildCardIndex | Path::WildCardName))
+                {
+                    Datum::Json(Json::Array(found.into_iter().cloned().collect()))
+                } else {
+                    found
+                        .first()
+                        .map_or(Datum::Missing, |value| Datum::from(*value))
+                }
+            }
+            Self::Csv { header, fields } => match path {
+                [] => Datum::Json(Json::Object(self.to_record())),
+                [Path::Name(name)] => {
+                    let position = header
+                        .as_ref()
+                        .and_then(|header| find_name(header.iter().map(String::as_str), name))
+                        .or_else(|| {
+                            name.strip_prefix('_')
+                                .and_then(|n| n.parse::<usize>().ok())
+                                .filter(|n| *n > 0)
+                                .map(|n| n - 1)
+                        });
+                    position
+                        .and_then(|i| fields.get(i))
+                        .map_or(Datum::Missing, |field| Datum::String(field.clone()))
+                }
+                _ => Datum::Missing,
+            },
+        }
+    }
+}
+
+/// Values at `path` in `value`, wildcards match every element or field.
+///
+/// Names that don't need quoting match fields case insensitively when no field has the exact name.
+pub(crate) fn lookup<'a>(value: &'a Json, path: &[Path]) -> Vec<&'a Json> {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return vec![value],
+    };
+
+    let found: Vec<&Json> = match (segment, value) {
+        (Path::Name(name), Json::Object(fields)) => {
+            find_name(fields.keys().map(String::as_str), name)
+                .and_then(|i| fields.values().nth(i))
+                .into_iter()
+                .collect()
+        }
+        (Path::Index(i), Json::Array(items)) => items.get(*i).into_iter().collect(),
+        (Path::WildCardIndex, Json::Array(items)) => items.iter().collect(),
+        (Path::WildCardName, Json::Object(fields)) => fields.values().collect(),
+        _ => Vec::new(),
+    };
+
+    found
+        .into_iter()
+        .flat_map(|value| lookup(value, rest))
+        .collect()
+}
+
+fn find_name<'a>(names: impl Iterator<Item = &'a str> + Clone, name: &str) -> Option<usize> {
+    names.clone().position(|n| n == name).or_else(|| {
+        if is_identifier(name) {
+            names.into_iter().position(|n| n.eq_ignore_ascii_case(name))
+        } else {
+            None
+        }
+    })
+}
+
+fn failed(code: &str, message: String) -> QueryError {
+    QueryError::Failed {
+        code: code.to_string(),
+        message,
+    }
+}
+
+/// Evaluates `expr` on `record`.
+///
+/// NULL and MISSING operands make the result NULL or MISSING, operands of the wrong type make
+/// it NULL. Casts that can't convert, integer overflows and divisions by zero fail the query.
+pub(crate) fn eval(expr: &Expr, record: &InputRecord) -> Result<Datum, QueryError> {
+    let value = match expr {
+        Expr::Column(path) => record.column(path),
+        Expr::Wildcard => {
+            return Err(QueryError::Invalid(
+                "`*` is only valid as a projection or in `Count`".to_string(),
+            ))
+        }
+        Expr::Literal(value) => literal(value)?,
+        Expr::Param(name) => return Err(ValidationError::UnboundParam(name.clone()).into()),
+        Expr::CharLength(e) => {
+            string_function(eval(e, record)?, |s| Datum::Int(s.chars().count() as i64))
+        }
+        Expr::Lower(e) => string_function(eval(e, record)?, |s| Datum::String(s.to_lowercase())),
+        Expr::Upper(e) => string_function(eval(e, record)?, |s| Datum::String(s.to_uppercase())),
+        Expr::Substring(e, start, length) => {
+            let length = match length {
+                Some(length) => Some(eval(length, record)?),
+                None => None,
+            };
+            substring(eval(e, record)?, eval(start, record)?, length)?
+        }
+        Expr::Trim(side, characters, e) => {
+            let characters = match characters {
+                Some(characters) => eval(characters, record)?,
+                None => Datum::String(" ".to_string()),
+            };
+            match (eval(e, record)?, characters) {
+                (Datum::String(s), Datum::String(characters)) => {
+                    let trimmed = |c: char| characters.contains(c);
+                    Datum::String(
+                        match side {
+                            TrimSide::Leading => s.trim_start_matches(trimmed),
+                            TrimSide::Trailing => s.trim_end_matches(trimmed),
+                            TrimSide::Both => s.trim_matches(trimmed),
+                        }
+                        .to_string(),
+                    )
+                }
+                (a, b) => absent(&[a, b]),
+            }
+        }
+        Expr::Concat(a, b) => match (eval(a, record)?, eval(b, record)?) {
+            (Datum::String(a), Datum::String(b)) => Datum::String(a + &b),
+            (a, b) => absent(&[a, b]),
+        },
+        Expr::Cast(e, to) => cast(eval(e, record)?, to)?,
+        Expr::Case(whens, otherwise) => {
+            for (when, then) in whens {
+                if test(when, record)? == Some(true) {
+                    return eval(then, record);
+                }
+            }
+            match otherwise {
+                Some(otherwise) => eval(otherwise, record)?,
+                None => Datum::Null,
+            }
+        }
+        Expr::CaseOf(e, whens, otherwise) => {
+            let value = eval(e, record)?;
+            for (when, then) in whens {
+                if equals(&value, &eval(when, record)?) == Some(true) {
+                    return eval(then, record);
+                }
+            }
+            match otherwise {
+                Some(otherwise) => eval(otherwise, record)?,
+                None => Datum::Null,
+            }
+        }
+        Expr::Coalesce(exprs) => {
+            for e in exprs {
+                let value = eval(e, record)?;
+                if !value.is_absent() {
+                    return Ok(value);
+                }
+            }
+            Datum::Null
+        }
+        Expr::NullIf(a, b) => {
+            let a = eval(a, record)?;
+            if equals(&a, &eval(b, record)?) == Some(true) {
+                Datum::Null
+            } else {
+                a
+            }
+        }
+        Expr::DateAdd(part, quantity, e) => match (eval(quantity, record)?, eval(e, record)?) {
+            (Datum::Int(quantity), Datum::Timestamp(t)) => {
+                Datum::Timestamp(date_add(part, quantity, t).ok_or_else(|| {
+                    failed(
+                        "EvaluatorInvalidArguments",
+                        format!("DATE_ADD of {} {} overflows", quantity, part.as_str()),
+                    )
+                })?)
+            }
+            (a, b) => absent(&[a, b]),
+        },
+        Expr::DateDiff(part, from, to) => match (eval(from, record)?, eval(to, record)?) {
+            (Datum::Timestamp(from), Datum::Timestamp(to)) => Datum::Int(date_diff(part, from, to)),
+            (a, b) => absent(&[a, b]),
+        },
+        Expr::Extract(part, e) => match eval(e, record)? {
+            Datum::Timestamp(t) => Datum::Int(extract(part, &t)),
+            value => absent(&[value]),
+        },
+        Expr::ToString(e, pattern) => match eval(e, record)? {
+            Datum::Timestamp(t) => Datum::String(format_timestamp(&t, pattern)?),
+            value => absent(&[value]),
+        },
+        Expr::ToTimestamp(e) => match eval(e, record)? {
+            Datum::String(s) => Datum::Timestamp(parse_timestamp(&s).ok_or_else(|| {
+                failed("CastFailed", format!("`{}` isn't an ISO 8601 timestamp", s))
+            })?),
+            value => absent(&[value]),
+        },
+        Expr::UtcNow => Datum::Timestamp(Utc::now().fixed_offset()),
+        Expr::Add(a, b) => arithmetic(eval(a, record)?, eval(b, record)?, Operator::Add)?,
+        Expr::Sub(a, b) => arithmetic(eval(a, record)?, eval(b, record)?, Operator::Sub)?,
+        Expr::Mul(a, b) => arithmetic(eval(a, record)?, eval(b, record)?, Operator::Mul)?,
+        Expr::Div(a, b) => arithmetic(eval(a, record)?, eval(b, record)?, Operator::Div)?,
+        Expr::Mod(a, b) => arithmetic(eval(a, record)?, eval(b, record)?, Operator::Mod)?,
+        Expr::Neg(e) => match eval(e, record)? {
+            Datum::Int(i) => Datum::Int(i.checked_neg().ok_or_else(|| overflow("-", i))?),
+            Datum::Float(f) => Datum::Float(-f),
+            value => absent(&[value]),
+        },
+    };
+
+    Ok(value)
+}
+
+/// Whether `record` matches `clause`, `None` when the result is unknown, e.g. comparing NULL.
+pub(crate) fn test(clause: &Clause, record: &InputRecord) -> Result<Option<bool>, QueryError> {
+    let compare_with = |a: &Expr, b: &Expr, wanted: fn(Ordering) -> bool| {
+        Ok::<_, QueryError>(compare(&eval(a, record)?, &eval(b, record)?).map(wanted))
+    };
+
+    let result = match clause {
+        Clause::G(a, b) => compare_with(a, b, Ordering::is_gt)?,
+        Clause::GE(a, b) => compare_with(a, b, Ordering::is_ge)?,
+        Clause::L(a, b) => compare_with(a, b, Ordering::is_lt)?,
+        Clause::LE(a, b) => compare_with(a, b, Ordering::is_le)?,
+        // built as `IS NULL` and `IS NOT NULL`, which are true for missing fields too
+        Clause::E(a, Expr::Literal(Value::Null)) => Some(eval(a, record)?.is_absent()),
+        Clause::NotE(a, Expr::Literal(Value::Null)) => Some(!eval(a, record)?.is_absent()),
+        Clause::E(a, b) => equals(&eval(a, record)?, &eval(b, record)?),
+        Clause::NotE(a, b) => equals(&eval(a, record)?, &eval(b, record)?).map(|e| !e),
+        Clause::IsNotNull(a) => Some(eval(a, record)? != Datum::Missing),
+        Clause::IsNull(a) => Some(eval(a, record)? == Datum::Missing),
+        Clause::Between(a, start, end) => between(
+            &eval(a, record)?,
+            &eval(start, record)?,
+            &eval(end, record)?,
+        ),
+        Clause::NotBetween(a, start, end) => between(
+            &eval(a, record)?,
+            &eval(start, record)?,
+            &eval(end, record)?,
+        )
+        .map(|b| !b),
+        Clause::In(a, values) => is_in(&eval(a, record)?, values, record)?,
+        Clause::NotIn(a, values) => is_in(&eval(a, record)?, values, record)?.map(|b| !b),
+        Clause::Like(a, pattern, escape) => match (eval(a, record)?, eval(pattern, record)?) {
+            (Datum::String(text), Datum::String(pattern)) => Some(like(&text, &pattern, *escape)?),
+            _ => None,
+        },
+        Clause::And(a, b) => match test(a, record)? {
+            Some(false) => Some(false),
+            a => match (a, test(b, record)?) {
+                (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+        },
+        Clause::Or(a, b) => match test(a, record)? {
+            Some(true) => Some(true),
+            a => match (a, test(b, record)?) {
+                (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+        },
+        Clause::Not(clause) => test(clause, record)?.map(|b| !b),
+    };
+
+    Ok(result)
+}
+
+fn literal(value: &Value) -> Result<Datum, QueryError> {
+    Ok(match value {
+        Value::Int(i) => Datum::Int(*i),
+        Value::Float(f) => Datum::Float(*f),
+        Value::Decimal(d) => Datum::Float(
+            d.trim()
+                .parse()
+                .map_err(|_| failed("CastFailed", format!("`{}` isn't a decimal", d)))?,
+        ),
+        Value::Bool(b) => Datum::Bool(*b),
+        Value::String(s) => Datum::String(s.clone()),
+        Value::Timestamp(t) => Datum::Timestamp(t.fixed_offset()),
+        Value::Null => Datum::Null,
+    })
+}
+
+// MISSING if any operand is missing, otherwise NULL
+fn absent(operands: &[Datum]) -> Datum {
+    if operands.contains(&Datum::Missing) {
+        Datum::Missing
+    } else {
+        Datum::Null
+    }
+}
+
+fn string_function(value: Datum, f: impl FnOnce(&str) -> Datum) -> Datum {
+    match value {
+        Datum::String(s) => f(&s),
+        value => absent(&[value]),
+    }
+}
+
+// SQL `SUBSTRING`, characters from the 1-based `start` up to `start + length`
+fn substring(value: Datum, start: Datum, length: Option<Datum>) -> Result<Datum, QueryError> {
+    let (s, start, length) = match (value, start, length) {
+        (Datum::String(s), Datum::Int(start), None) => (s, start, None),
+        (Datum::String(s), Datum::Int(start), Some(Datum::Int(length))) => {
+            if length < 0 {
+                return Err(failed(
+                    "EvaluatorInvalidArguments",
+                    format!("SUBSTRING length {} is negative", length),
+                ));
+            }
+            (s, start, Some(length))
+        }
+        (value, start, length) => {
+            return Ok(absent(&[value, start, length.unwrap_or(Datum::Null)]))
+        }
+    };
+
+    let end = length.map_or(i64::MAX, |length| start.saturating_add(length));
+    Ok(Datum::String(
+        s.chars()
+            .enumerate()
+            .filter(|(i, _)| {
+                let position = *i as i64 + 1;
+                position >= start && position < end
+            })
+            .map(|(_, c)| c)
+            .collect(),
+    ))
+}
+
+fn cast(value: Datum, to: &CastType) -> Result<Datum, QueryError> {
+    let cast_failed = |value: &Datum| {
+        failed(
+            "CastFailed",
+            format!("can't cast {:?} to {}", value, to.as_str()),
+        )
+    };
+
+    let cast = match (to, &value) {
+        (_, Datum::Missing) | (_, Datum::Null) => Some(value.clone()),
+        (CastType::Int, Datum::Int(_)) => Some(value.clone()),
+        (CastType::Int, Datum::Float(f)) => float_to_int(*f),
+        (CastType::Int, Datum::Bool(b)) => Some(Datum::Int(*b as i64)),
+        (CastType::Int, Datum::String(s)) => {
+            let s = s.trim();
+            s.parse()
+                .ok()
+                .map(Datum::Int)
+                .or_else(|| s.parse().ok().and_then(float_to_int))
+        }
+        (CastType::Float, Datum::Int(i)) | (CastType::Decimal, Datum::Int(i)) => {
+            Some(Datum::Float(*i as f64))
+        }
+        (CastType::Float, Datum::Float(_)) | (CastType::Decimal, Datum::Float(_)) => {
+            Some(value.clone())
+        }
+        (CastType::Float, Datum::Bool(b)) | (CastType::Decimal, Datum::Bool(b)) => {
+            Some(Datum::Float(*b as i64 as f64))
+        }
+        (CastType::Float, Datum::String(s)) | (CastType::Decimal, Datum::String(s)) => {
+            s.trim().parse().ok().map(Datum::Float)
+        }
+        (CastType::Timestamp, Datum::Timestamp(_)) => Some(value.clone()),
+        (CastType::Timestamp, Datum::String(s)) => parse_timestamp(s).map(Datum::Timestamp),
+        (CastType::String, Datum::String(_)) => Some(value.clone()),
+        (CastType::String, Datum::Int(i)) => Some(Datum::String(i.to_string())),
+        (CastType::String, Datum::Float(f)) => Some(Datum::String(f.to_string())),
+        (CastType::String, Datum::Bool(b)) => Some(Datum::String(b.to_string())),
+        (CastType::String, Datum::Timestamp(t)) => Some(Datum::String(timestamp_text(t))),
+        (CastType::String, Datum::Json(json)) => Some(Datum::String(json.to_string())),
+        (CastType::Bool, Datum::Bool(_)) => Some(value.clone()),
+        (CastType::Bool, Datum::Int(i)) => Some(Datum::Bool(*i != 0)),
+        (CastType::Bool, Datum::Float(f)) => Some(Datum::Bool(*f != 0.0)),
+        (CastType::Bool, Datum::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Some(Datum::Bool(true)),
+            "false" => Some(Datum::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    cast.ok_or_else(|| cast_failed(&value))
+}
+
+fn float_to_int(f: f64) -> Option<Datum> {
+    if f.is_finite() && f.trunc() >= i64::MIN as f64 && f.trunc() < i64::MAX as f64 {
+        Some(Datum::Int(f.trunc() as i64))
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl Operator {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Mod => "%",
+        }
+    }
+}
+
+fn overflow(operator: &str, operand: i64) -> QueryError {
+    failed(
+        "IntegerOverflow",
+        format!("`{}` on {} overflows", operator, operand),
+    )
+}
+
+fn arithmetic(a: Datum, b: Datum, operator: Operator) -> Result<Datum, QueryError> {
+    match (&a, &b) {
+        (Datum::Int(x), Datum::Int(y)) => {
+            if *y == 0 && matches!(operator, Operator::Div | Operator::Mod) {
+                return Err(failed(
+                    "EvaluatorInvalidArguments",
+                    format!("{} {} 0 divides by zero", x, operator.as_str()),
+                ));
+            }
+            let result = match operator {
+                Operator::Add => x.checked_add(*y),
+                Operator::Sub => x.checked_sub(*y),
+                Operator::Mul => x.checked_mul(*y),
+                // integer division truncates, like S3 Select's
+                Operator::Div => x.checked_div(*y),
+                Operator::Mod => x.checked_rem(*y),
+            };
+            result
+                .map(Datum::Int)
+                .ok_or_else(|| overflow(operator.as_str(), *x))
+        }
+        (Datum::Int(_), Datum::Float(_))
+        | (Datum::Float(_), Datum::Int(_))
+        | (Datum::Float(_), Datum::Float(_)) => {
+            let (x, y) = (
+                number(&a).unwrap_or_default(),
+                number(&b).unwrap_or_default(),
+            );
+            Ok(Datum::Float(match operator {
+                Operator::Add => x + y,
+                Operator::Sub => x - y,
+                Operator::Mul => x * y,
+                Operator::Div => x / y,
+                Operator::Mod => x % y,
+            }))
+        }
+        _ => Ok(absent(&[a, b])),
+    }
+}
+
+fn number(value: &Datum) -> Option<f64> {
+    match value {
+        Datum::Int(i) => Some(*i as f64),
+        Datum::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// `=` of two values, `None` if either is NULL or MISSING, values of different types aren't equal.
+pub(crate) fn equals(a: &Datum, b: &Datum) -> Option<bool> {
+    if a.is_absent() || b.is_absent() {
+        return None;
+    }
+
+    Some(match (a, b) {
+        (Datum::Int(x), Datum::Int(y)) => x == y,
+        _ => match compare(a, b) {
+            Some(ordering) => ordering == Ordering::Equal,
+            None => a == b,
+        },
+    })
+}
+
+/// Order of two values of comparable types, numbers compare with numbers, strings with strings, ...
+pub(crate) fn compare(a: &Datum, b: &Datum) -> Option<Ordering> {
+    match (a, b) {
+        (Datum::Int(x), Datum::Int(y)) => Some(x.cmp(y)),
+        (Datum::String(x), Datum::String(y)) => Some(x.cmp(y)),
+        (Datum::Bool(x), Datum::Bool(y)) => Some(x.cmp(y)),
+        (Datum::Timestamp(x), Datum::Timestamp(y)) => Some(x.cmp(y)),
+        _ => number(a)?.partial_cmp(&number(b)?),
+    }
+}
+
+fn between(value: &Datum, start: &Datum, end: &Datum) -> Option<bool> {
+    match (compare(value, start), compare(value, end)) {
+        (Some(Ordering::Less), _) | (_, Some(Ordering::Greater)) => Some(false),
+        (Some(_), Some(_)) => Some(true),
+        _ => None,
+    }
+}
+
+fn is_in(value: &Datum, values: &[Expr], record: &InputRecord) -> Result<Option<bool>, QueryError> {
+    let mut result = Some(false);
+    for e in values {
+        match equals(value, &eval(e, record)?) {
+            Some(true) => return Ok(Some(true)),
+            Some(false) => (),
+            None => result = None,
+        }
+    }
+
+    Ok(result)
+}
+
+enum LikeToken {
+    Char(char),
+    One,  // `_`
+    Many, // `%`
+}
+
+/// `LIKE` matching, `%` matches any characters and `_` a single one.
+pub(crate) fn like(text: &str, pattern: &str, escape: Option<char>) -> Result<bool, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(escaped) if escaped == '%' || escaped == '_' || Some(escaped) == escape => {
+                    LikeToken::Char(escaped)
+                }
+                _ => {
+                    return Err(failed(
+                        "EvaluatorLikePatternInvalidEscapeSequence",
+                        format!("`{}` has an invalid escape sequence", pattern),
+                    ))
+                }
+            },
+            '%' => LikeToken::Many,
+            '_' => LikeToken::One,
+            c => LikeToken::Char(c),
+        });
+    }
+
+    // matched[j]: the text read so far matches the first `j` tokens
+    let mut matched = vec![false; tokens.len() + 1];
+    matched[0] = true;
+    for (j, token) in tokens.iter().enumerate() {
+        if let LikeToken::Many = token {
+            matched[j + 1] = matched[j];
+        }
+    }
+    for c in text.chars() {
+        let mut next = vec![false; tokens.len() + 1];
+        for (j, token) in tokens.iter().enumerate() {
+            next[j + 1] = match token {
+                LikeToken::Char(t) => matched[j] && *t == c,
+                LikeToken::One => matched[j],
+                LikeToken::Many => matched[j + 1] || next[j] || matched[j],
+            };
+        }
+        matched = next;
+    }
+
+    Ok(matched[tokens.len()])
+}
+
+/// ISO 8601 timestamps as S3 Select reads them, from `2021T` to
+/// `2021-02-03T04:05:06.789+01:00`, without an offset the time is UTC.
+pub(crate) fn parse_timestamp(text: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(text) {
+        return Some(t);
+    }
+
+    let (date, time) = text.split_once('T').unwrap_or((text, ""));
+    let mut parts = date.split('-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next().map_or(Some(1), |m| m.parse().ok())?;
+    let day = parts.next().map_or(Some(1), |d| d.parse().ok())?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(year, month, day)?;
+
+    let (time, offset) = if time.is_empty() {
+        (NaiveTime::MIN, FixedOffset::east_opt(0)?)
+    } else {
+        let (time, offset) = match time.find(['Z', '+', '-']) {
+            Some(i) => (&time[..i], parse_offset(&time[i..])?),
+            None => (time, FixedOffset::east_opt(0)?),
+        };
+        let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()?;
+        (time, offset)
+    };
+
+    date.and_time(time).and_local_timezone(offset).single()
+}
+
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+    FixedOffset::east_opt(sign * seconds)
+}
+
+fn timestamp_text(t: &DateTime<FixedOffset>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn date_add(
+    part: &DatePart,
+    quantity: i64,
+    t: DateTime<FixedOffset>,
+) -> Option<DateTime<FixedOffset>> {
+    let months = |months: i64| {
+        let shift = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+        if months < 0 {
+            t.checked_sub_months(shift)
+        } else {
+            t.checked_add_months(shift)
+        }
+    };
+
+    match part {
+        DatePart::Year => months(quantity.checked_mul(12)?),
+        DatePart::Month => months(quantity),
+        DatePart::Day => t.checked_add_signed(Duration::try_days(quantity)?),
+        DatePart::Hour => t.checked_add_signed(Duration::try_hours(quantity)?),
+        DatePart::Minute => t.checked_add_signed(Duration::try_minutes(quantity)?),
+        DatePart::Second => t.checked_add_signed(Duration::try_seconds(quantity)?),
+    }
+}
+
+// whole `part`s from `from` to `to`, negative when `to` is earlier
+fn date_diff(part: &DatePart, from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) -> i64 {
+    let months = || {
+        let (from, to) = (from.naive_utc(), to.naive_utc());
+        let mut months =
+            (to.year() as i64 - from.year() as i64) * 12 + to.month() as i64 - from.month() as i64;
+        let rest = |t: &chrono::NaiveDateTime| (t.day(), t.time());
+        if months > 0 && rest(&to) < rest(&from) {
+            months -= 1;
+        } else if months < 0 && rest(&to) > rest(&from) {
+            months += 1;
+        }
+        months
+    };
+    let elapsed = to.signed_duration_since(from);
+
+    match part {
+        DatePart::Year => months() / 12,
+        DatePart::Month => months(),
+        DatePart::Day => elapsed.num_days(),
+        DatePart::Hour => elapsed.num_hours(),
+        DatePart::Minute => elapsed.num_minutes(),
+        DatePart::Second => elapsed.num_seconds(),
+    }
+}
+
+fn extract(part: &ExtractPart, t: &DateTime<FixedOffset>) -> i64 {
+    let offset = t.offset().local_minus_utc() as i64;
+
+    match part {
+        ExtractPart::Year => t.year() as i64,
+        ExtractPart::Month => t.month() as i64,
+        ExtractPart::Day => t.day() as i64,
+        ExtractPart::Hour => t.hour() as i64,
+        ExtractPart::Minute => t.minute() as i64,
+        ExtractPart::Second => t.second() as i64,
+        ExtractPart::TimezoneHour => offset / 3600,
+        ExtractPart::TimezoneMinute => offset % 3600 / 60,
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// `TO_STRING`, letters repeat to pad or to pick longer forms, e.g. `yyyy-MM-dd'T'HH:mm:ssXXX`.
+fn format_timestamp(t: &DateTime<FixedOffset>, pattern: &str) -> Result<String, QueryError> {
+    let mut text = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // quoted text, `''` is a quote
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                text.push('\'');
+                continue;
+            }
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        text.push('\'');
+                    }
+                    Some('\'') => break,
+                    Some(c) => text.push(c),
+                    None => {
+                        return Err(failed(
+                            "EvaluatorUnterminatedTimestampFormatPatternToken",
+                            format!("`{}` has an unterminated quote", pattern),
+                        ))
+                    }
+                }
+            }
+            continue;
+        }
+        if !c.is_ascii_alphabetic() {
+            text.push(c);
+            continue;
+        }
+
+        let mut width = 1;
+        while chars.peek() == Some(&c) {
+            chars.next();
+            width += 1;
+        }
+        let padded = |value: u32| format!("{:0width$}", value, width = width);
+        let offset = t.offset().local_minus_utc();
+        let zone = |separator: &str, utc_z: bool| {
+            if offset == 0 && utc_z {
+                return "Z".to_string();
+            }
+            let sign = if offset < 0 { '-' } else { '+' };
+            let (hours, minutes) = (offset.abs() / 3600, offset.abs() % 3600 / 60);
+            match width {
+                1 if minutes == 0 => format!("{}{:02}", sign, hours),
+                1 | 2 => format!("{}{:02}{:02}", sign, hours, minutes),
+                _ => format!("{}{:02}{}{:02}", sign, hours, separator, minutes),
+            }
+        };
+
+        text += &match c {
+            'y' if width == 2 => format!("{:02}", t.year().rem_euclid(100)),
+            'y' => format!("{:0width$}", t.year(), width = width),
+            'M' if width == 3 => MONTHS[t.month0() as usize][..3].to_string(),
+            'M' if width == 4 => MONTHS[t.month0() as usize].to_string(),
+            'M' if width == 5 => MONTHS[t.month0() as usize][..1].to_string(),
+            'M' => padded(t.month()),
+            'd' => padded(t.day()),
+            'a' => (if t.hour() < 12 { "AM" } else { "PM" }).to_string(),
+            'h' => padded(t.hour12().1),
+            'H' => padded(t.hour()),
+            'm' => padded(t.minute()),
+            's' => padded(t.second()),
+            'S' => format!("{:09}", t.nanosecond() % 1_000_000_000)
+                .chars()
+                .chain(std::iter::repeat('0'))
+                .take(width)
+                .collect(),
+            'n' => (t.nanosecond() % 1_000_000_000).to_string(),
+            'X' => zone(":", true),
+            'x' => zone(":", false),
+            _ => {
+                return Err(failed(
+                    "EvaluatorInvalidTimestampFormatPatternSymbol",
+                    format!("`{}` isn't a timestamp pattern letter", c),
+                ))
+            }
+        };
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod eval_test {
+    use super::*;
+    use serde_json::json;
+
+    fn record() -> InputRecord {
+        InputRecord::Json(json!({
+            "name": "Ghana",
+            "count": 10,
+            "price": 2.5,
+            "tags": ["a", "b"],
+            "address": {"city": "Accra"},
+            "none": null,
+            "logged_at": "2021-03-04T05:06:07Z",
+        }))
+    }
+
+    fn value(expr: Expr) -> Datum {
+        eval(&expr, &record()).unwrap()
+    }
+
+    #[test]
+    fn columns() {
+        assert_eq!(
+            value(Expr::column("name")),
+            Datum::String("Ghana".to_string())
+        );
+        assert_eq!(
+            value(Expr::column("NAME")),
+            Datum::String("Ghana".to_string())
+        );
+        assert_eq!(
+            value(Expr::column("address.city")),
+            Datum::String("Accra".to_string())
+        );
+        assert_eq!(
+            value(Expr::column("tags[1]")),
+            Datum::String("b".to_string())
+        );
+        assert_eq!(value(Expr::column("none")), Datum::Null);
+        assert_eq!(value(Expr::column("absent")), Datum::Missing);
+        assert_eq!(value(Expr::column("absent").upper()), Datum::Missing);
+        assert_eq!(value(Expr::column("none").upper()), Datum::Null);
+    }
+
+    #[test]
+    fn csv_columns() {
+        let header = Some(Arc::new(vec!["name".to_string(), "count".to_string()]));
+        let record = InputRecord::Csv {
+            header,
+            fields: vec!["Ghana".to_string(), "10".to_string()],
+        };
+
+        for (column, expected) in [
+            ("name", Datum::String("Ghana".to_string())),
+            ("_2", Datum::String("10".to_string())),
+            ("_3", Datum::Missing),
+            ("other", Datum::Missing),
+        ] {
+            assert_eq!(eval(&Expr::column(column), &record).unwrap(), expected);
+        }
+        assert_eq!(
+            eval(&Expr::column("count").cast(CastType::Int), &record).unwrap(),
+            Datum::Int(10)
+        );
+    }
+
+    #[test]
+    fn arithmetic_and_strings() {
+        assert_eq!(value(Expr::column("count") / 4), Datum::Int(2));
+        assert_eq!(
+            value(Expr::column("count") * Expr::column("price")),
+            Datum::Float(25.0)
+        );
+        assert_eq!(value(Expr::column("name") + 1), Datum::Null);
+        assert_eq!(
+            value(Expr::column("name").concat("!").lower()),
+            Datum::String("ghana!".to_string())
+        );
+        assert_eq!(
+            value(Expr::column("name").substring(2, Some(3.into()))),
+            Datum::String("han".to_string())
+        );
+        assert_eq!(
+            value(Expr::from("xxhixx").trim_with(TrimSide::Leading, Some("x".into()))),
+            Datum::String("hixx".to_string())
+        );
+        assert!(matches!(
+            eval(&(Expr::column("count") / 0), &record()),
+            Err(QueryError::Failed { code, .. }) if code == "EvaluatorInvalidArguments"
+        ));
+        assert!(matches!(
+            eval(&(Expr::from(i64::MAX) + 1), &record()),
+            Err(QueryError::Failed { code, .. }) if code == "IntegerOverflow"
+        ));
+    }
+
+    #[test]
+    fn casts() {
+        assert_eq!(value(Expr::from("12").cast(CastType::Int)), Datum::Int(12));
+        assert_eq!(value(Expr::from(2.9).cast(CastType::Int)), Datum::Int(2));
+        assert_eq!(
+            value(Expr::from(" TRUE").cast(CastType::Bool)),
+            Datum::Bool(true)
+        );
+        assert_eq!(
+            value(Expr::from(3).cast(CastType::String)),
+            Datum::String("3".to_string())
+        );
+        assert!(matches!(
+            eval(&Expr::column("name").cast(CastType::Int), &record()),
+            Err(QueryError::Failed { code, .. }) if code == "CastFailed"
+        ));
+    }
+
+    #[test]
+    fn timestamps() {
+        let logged_at = || Expr::column("logged_at").cast(CastType::Timestamp);
+
+        assert_eq!(
+            value(logged_at().extract(ExtractPart::Month)),
+            Datum::Int(3)
+        );
+        assert_eq!(
+            value(
+                logged_at()
+                    .date_add(DatePart::Month, -3)
+                    .format_timestamp("yyyy-MM-dd")
+            ),
+            Datum::String("2020-12-04".to_string())
+        );
+        assert_eq!(
+            value(logged_at().format_timestamp("MMM d, h:mm a 'at' XXX")),
+            Datum::String("Mar 4, 5:06 AM at Z".to_string())
+        );
+        assert_eq!(
+            value(Expr::date_diff(
+                DatePart::Year,
+                Expr::from("2019-03-05T").parse_timestamp(),
+                logged_at()
+            )),
+            Datum::Int(1)
+        );
+        assert_eq!(
+            parse_timestamp("2021-02-03T04:05-08:00").map(|t| timestamp_text(&t)),
+            Some("2021-02-03T04:05:00-08:00".to_string())
+        );
+        assert_eq!(
+            parse_timestamp("2021T").map(|t| timestamp_text(&t)),
+            Some("2021-01-01T00:00:00Z".to_string())
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn clauses() {
+        let check = |clause: Clause| test(&clause, &record()).unwrap();
+
+        assert_eq!(
+            check(Clause::G(Expr::column("count"), 5.into())),
+            Some(true)
+        );
+        assert_eq!(check(Clause::G(Expr::column("name"), 5.into())), None);
+        assert_eq!(
+            check(Clause::E(Expr::column("name"), 5.into())),
+            Some(false)
+        );
+        assert_eq!(
+            check(Clause::E(Expr::column("count"), 10.0.into())),
+            Some(true)
+        );
+        assert_eq!(
+            check(Clause::E(Expr::column("none"), Value::Null.into())),
+            Some(true)
+        );
+        assert_eq!(
+            check(Clause::E(Expr::column("absent"), Value::Null.into())),
+            Some(true)
+        );
+        assert_eq!(check(Clause::IsNull(Expr::column("none"))), Some(false));
+        assert_eq!(check(Clause::IsNull(Expr::column("absent"))), Some(true));
+        assert_eq!(
+            check(Clause::In(Expr::column("count"), vec![1.into(), 10.into()])),
+            Some(true)
+        );
+        assert_eq!(
+            check(Clause::NotBetween(
+                Expr::column("price"),
+                1.into(),
+                2.into()
+            )),
+            Some(true)
+        );
+        assert_eq!(
+            check(
+                !Clause::G(Expr::column("absent"), 1.into())
+                    .and(Clause::IsNull(Expr::column("absent")))
+            ),
+            None
+        );
+        assert_eq!(
+            check(
+                Clause::G(Expr::column("absent"), 1.into())
+                    .or(Clause::IsNull(Expr::column("absent")))
+            ),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn like_patterns() {
+        assert!(like("Ghana", "G%a", None).unwrap());
+        assert!(like("Ghana", "_h_n_", None).unwrap());
+        assert!(!like("Ghana", "G_a", None).unwrap());
+        assert!(like("50%", "50\\%", Some('\\')).unwrap());
+        assert!(!like("500", "50\\%", Some('\\')).unwrap());
+        assert!(like("", "%", None).unwrap());
+        assert!(like("a%b_c", &format!("%{}%", "%b\\_"), Some('\\')).unwrap());
+        assert!(like("x", "x\\", Some('\\')).is_err());
+    }
+}
@@ -0,0 +1,810 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::path::{is_identifier, Path};
+use super::Clause;
+
+/// Literal used in an `Expr`, `Value::from` works for the common Rust types.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Decimal(String), // kept as text so no precision is lost, e.g. "19.99"
+    Bool(bool),
+    String(String),
+    Timestamp(DateTime<Utc>),
+    Null,
+}
+
+impl Value {
+    pub(crate) fn to_sql(&self) -> String {
+        match self {
+            Self::Int(i) => i.to_string(),
+            // without an exponent S3 Select reads the literal as a decimal
+            Self::Float(f) if f.is_finite() => format!("{:e}", f),
+            Self::Float(f) if f.is_nan() => String::from("CAST('nan' AS FLOAT)"),
+            Self::Float(f) if *f > 0.0 => String::from("CAST('+inf' AS FLOAT)"),
+            Self::Float(_) => String::from("CAST('-inf' AS FLOAT)"),
+            Self::Decimal(d) if is_decimal(d) => d.clone(),
+            Self::Decimal(d) => format!("CAST({} AS DECIMAL)", string_literal(d)),
+            Self::Bool(b) => b.to_string(),
+            Self::String(s) => string_literal(s),
+            Self::Timestamp(t) => format!("`{}`", t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            Self::Null => String::from("NULL"),
+        }
+    }
+}
+
+/// Single quoted SQL string, quotes inside are doubled.
+pub(crate) fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// A single identifier, e.g. an alias, double quoted unless it's a plain identifier.
+pub(crate) fn quoted_identifier(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+pub(crate) fn is_decimal(decimal: &str) -> bool {
+    let digits = decimal.strip_prefix('-').unwrap_or(decimal);
+    let mut parts = digits.splitn(2, '.');
+    let integer = parts.next().unwrap_or_default();
+    let fraction = parts.next();
+
+    !integer.is_empty()
+        && integer.chars().all(|c| c.is_ascii_digit())
+        && fraction.is_none_or(|f| !f.is_empty() && f.chars().all(|c| c.is_ascii_digit()))
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Value {
+    fn from(value: DateTime<Utc>) -> Self {
+        Value::Timestamp(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Side of the string `TRIM` removes characters from.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum TrimSide {
+    Leading,
+    Trailing,
+    Both,
+}
+
+impl TrimSide {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::Leading => "LEADING",
+            Self::Trailing => "TRAILING",
+            Self::Both => "BOTH",
+        }
+    }
+}
+
+/// Target type of `CAST`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum CastType {
+    Int,
+    Float,
+    Decimal,
+    Timestamp,
+    String,
+    Bool,
+}
+
+impl CastType {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::Int => "INT",
+            Self::Float => "FLOAT",
+            Self::Decimal => "DECIMAL",
+            Self::Timestamp => "TIMESTAMP",
+            Self::String => "STRING",
+            Self::Bool => "BOOL",
+        }
+    }
+}
+
+/// Unit for `DATE_ADD` and `DATE_DIFF`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DatePart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl DatePart {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Day => "day",
+            Self::Hour => "hour",
+            Self::Minute => "minute",
+            Self::Second => "second",
+        }
+    }
+}
+
+/// Field read by `EXTRACT`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ExtractPart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    TimezoneHour,
+    TimezoneMinute,
+}
+
+impl ExtractPart {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Day => "day",
+            Self::Hour => "hour",
+            Self::Minute => "minute",
+            Self::Second => "second",
+            Self::TimezoneHour => "timezone_hour",
+            Self::TimezoneMinute => "timezone_minute",
+        }
+    }
+}
+
+/// Scalar expression usable in `Select` and `Clause`, literals convert with `Expr::from`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Expr {
+    Column(Vec<Path>), // `s.<path>`
+    Wildcard,          // `*`, only valid as a projection or in `Count`
+    Literal(Value),
+    Param(String), // named placeholder, replaced by a value with `QueryContent::bind`
+    CharLength(Box<Expr>),
+    Lower(Box<Expr>),
+    Upper(Box<Expr>),
+    Substring(Box<Expr>, Box<Expr>, Option<Box<Expr>>), // string, 1-based start, length
+    Trim(TrimSide, Option<Box<Expr>>, Box<Expr>),       // side, characters to remove, string
+    Concat(Box<Expr>, Box<Expr>),
+    Cast(Box<Expr>, CastType),
+    Case(Vec<(Clause, Expr)>, Option<Box<Expr>>), // `CASE WHEN <clause> THEN <expr> ... ELSE <expr> END`
+    CaseOf(Box<Expr>, Vec<(Expr, Expr)>, Option<Box<Expr>>), // `CASE <expr> WHEN <expr> THEN <expr> ...`
+    Coalesce(Vec<Expr>),
+    NullIf(Box<Expr>, Box<Expr>),
+    DateAdd(DatePart, Box<Expr>, Box<Expr>), // part, quantity, timestamp
+    DateDiff(DatePart, Box<Expr>, Box<Expr>), // part, from, to
+    Extract(ExtractPart, Box<Expr>),
+    ToString(Box<Expr>, String), // timestamp, format pattern e.g. `yyyy-MM-dd`
+    ToTimestamp(Box<Expr>),
+    UtcNow,
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Mod(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+}
+
+impl Expr {
+    pub fn column(name: &str) -> Self {
+        Expr::Column(Path::parse(name))
+    }
+
+    /// Column for names `Expr::column` can't parse, e.g. containing a `.`.
+    pub fn path(path: Vec<Path>) -> Self {
+        Expr::Column(path)
+    }
+
+    /// Placeholder for a value given later with `QueryContent::bind`.
+    pub fn param(name: &str) -> Self {
+        Expr::Param(name.to_string())
+    }
+
+    pub fn char_length(self) -> Self {
+        Expr::CharLength(Box::new(self))
+    }
+
+    pub fn lower(self) -> Self {
+        Expr::Lower(Box::new(self))
+    }
+
+    pub fn upper(self) -> Self {
+        Expr::Upper(Box::new(self))
+    }
+
+    pub fn substring(self, start: impl Into<Expr>, length: Option<Expr>) -> Self {
+        Expr::Substring(Box::new(self), Box::new(start.into()), length.map(Box::new))
+    }
+
+    /// Removes spaces from both sides, see `trim_with` to pick the side and characters.
+    pub fn trim(self) -> Self {
+        Expr::Trim(TrimSide::Both, None, Box::new(self))
+    }
+
+    pub fn trim_with(self, side: TrimSide, characters: Option<Expr>) -> Self {
+        Expr::Trim(side, characters.map(Box::new), Box::new(self))
+    }
+
+    /// `self || other`
+    pub fn concat(self, other: impl Into<Expr>) -> Self {
+        Expr::Concat(Box::new(self), Box::new(other.into()))
+    }
+
+    /// Needed for CSV input, where every field is a string.
+    pub fn cast(self, to: CastType) -> Self {
+        Expr::Cast(Box::new(self), to)
+    }
+
+    /// Searched `CASE`, the first matching clause wins, `otherwise` defaults to NULL.
+    pub fn case(whens: Vec<(Clause, Expr)>, otherwise: Option<Expr>) -> Self {
+        Expr::Case(whens, otherwise.map(Box::new))
+    }
+
+    /// Simple `CASE`, compares `self` against each `when` value.
+    pub fn case_of(self, whens: Vec<(Expr, Expr)>, otherwise: Option<Expr>) -> Self {
+        Expr::CaseOf(Box::new(self), whens, otherwise.map(Box::new))
+    }
+
+    /// First expression that isn't null or missing.
+    pub fn coalesce(exprs: Vec<Expr>) -> Self {
+        Expr::Coalesce(exprs)
+    }
+
+    /// NULL when `self` equals `other`, otherwise `self`.
+    pub fn null_if(self, other: impl Into<Expr>) -> Self {
+        Expr::NullIf(Box::new(self), Box::new(other.into()))
+    }
+
+    pub fn utc_now() -> Self {
+        Expr::UtcNow
+    }
+
+    /// Shifts a timestamp, a negative `quantity` goes back in time.
+    pub fn date_add(self, part: DatePart, quantity: impl Into<Expr>) -> Self {
+        Expr::DateAdd(part, Box::new(quantity.into()), Box::new(self))
+    }
+
+    /// Whole `part`s elapsed from `from` to `to`.
+    pub fn date_diff(part: DatePart, from: Expr, to: Expr) -> Self {
+        Expr::DateDiff(part, Box::new(from), Box::new(to))
+    }
+
+    pub fn extract(self, part: ExtractPart) -> Self {
+        Expr::Extract(part, Box::new(self))
+    }
+
+    /// `TO_STRING`, see the S3 Select date functions reference for the pattern letters.
+    pub fn format_timestamp(self, pattern: &str) -> Self {
+        Expr::ToString(Box::new(self), pattern.to_string())
+    }
+
+    /// `TO_TIMESTAMP`, parses an ISO 8601 string.
+    pub fn parse_timestamp(self) -> Self {
+        Expr::ToTimestamp(Box::new(self))
+    }
+
+    /// Calls `f` on this expression and on every expression in it, `CASE` clauses included.
+    pub(crate) fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        f(self);
+        match self {
+            Self::Column(_) | Self::Wildcard | Self::UtcNow => (),
+            Self::Literal(_) | Self::Param(_) => (),
+            Self::CharLength(e)
+            | Self::Lower(e)
+            | Self::Upper(e)
+            | Self::Cast(e, _)
+            | Self::Extract(_, e)
+            | Self::ToString(e, _)
+            | Self::ToTimestamp(e)
+            | Self::Neg(e) => e.visit(f),
+            Self::Substring(e, start, length) => {
+                e.visit(f);
+                start.visit(f);
+                if let Some(length) = length {
+                    length.visit(f);
+                }
+            }
+            Self::Trim(_, characters, e) => {
+                if let Some(characters) = characters {
+                    characters.visit(f);
+                }
+                e.visit(f);
+            }
+            Self::Concat(a, b)
+            | Self::NullIf(a, b)
+            | Self::DateAdd(_, a, b)
+            | Self::DateDiff(_, a, b)
+            | Self::Add(a, b)
+            | Self::Sub(a, b)
+            | Self::Mul(a, b)
+            | Self::Div(a, b)
+            | Self::Mod(a, b) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Self::Case(whens, otherwise) => {
+                for (when, then) in whens {
+                    when.visit(f);
+                    then.visit(f);
+                }
+                if let Some(otherwise) = otherwise {
+                    otherwise.visit(f);
+                }
+            }
+            Self::CaseOf(e, whens, otherwise) => {
+                e.visit(f);
+                for (when, then) in whens {
+                    when.visit(f);
+                    then.visit(f);
+                }
+                if let Some(otherwise) = otherwise {
+                    otherwise.visit(f);
+                }
+            }
+            Self::Coalesce(exprs) => exprs.iter().for_each(|e| e.visit(f)),
+        }
+    }
+
+    /// `visit` that can change the expressions.
+    pub(crate) fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        f(self);
+        match self {
+            Self::Column(_) | Self::Wildcard | Self::UtcNow => (),
+            Self::Literal(_) | Self::Param(_) => (),
+            Self::CharLength(e)
+            | Self::Lower(e)
+            | Self::Upper(e)
+            | Self::Cast(e, _)
+            | Self::Extract(_, e)
+            | Self::ToString(e, _)
+            | Self::ToTimestamp(e)
+            | Self::Neg(e) => e.visit_mut(f),
+            Self::Substring(e, start, length) => {
+                e.visit_mut(f);
+                start.visit_mut(f);
+                if let Some(length) = length {
+                    length.visit_mut(f);
+                }
+            }
+            Self::Trim(_, characters, e) => {
+                if let Some(characters) = characters {
+                    characters.visit_mut(f);
+                }
+                e.visit_mut(f);
+            }
+            Self::Concat(a, b)
+            | Self::NullIf(a, b)
+            | Self::DateAdd(_, a, b)
+            | Self::DateDiff(_, a, b)
+            | Self::Add(a, b)
+            | Self::Sub(a, b)
+            | Self::Mul(a, b)
+            | Self::Div(a, b)
+            | Self::Mod(a, b) => {
+                a.visit_mut(f);
+                b.visit_mut(f);
+            }
+            Self::Case(whens, otherwise) => {
+                for (when, then) in whens {
+                    when.visit_mut(f);
+                    then.visit_mut(f);
+                }
+                if let Some(otherwise) = otherwise {
+                    otherwise.visit_mut(f);
+                }
+            }
+            Self::CaseOf(e, whens, otherwise) => {
+                e.visit_mut(f);
+                for (when, then) in whens {
+                    when.visit_mut(f);
+                    then.visit_mut(f);
+                }
+                if let Some(otherwise) = otherwise {
+                    otherwise.visit_mut(f);
+                }
+            }
+            Self::Coalesce(exprs) => exprs.iter_mut().for_each(|e| e.visit_mut(f)),
+        }
+    }
+
+    // binding strength, operands binding weaker than their operator get parentheses
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Concat(..) => 1,
+            Self::Add(..) | Self::Sub(..) => 2,
+            Self::Mul(..) | Self::Div(..) | Self::Mod(..) => 3,
+            Self::Neg(..) => 4,
+            _ => 5,
+        }
+    }
+
+    fn to_operand_sql(&self, parent: &Expr, left: bool) -> String {
+        let sql = self.to_sql();
+
+        if self.precedence() < parent.precedence()
+            || (!left && self.precedence() == parent.precedence())
+        {
+            format!("({})", sql)
+        } else {
+            sql
+        }
+    }
+
+    fn to_binary_sql(&self, a: &Expr, operator: &str, b: &Expr) -> String {
+        format!(
+            "{} {} {}",
+            a.to_operand_sql(self, true),
+            operator,
+            b.to_operand_sql(self, false)
+        )
+    }
+
+    pub(crate) fn to_sql(&self) -> String {
+        match self {
+            Self::Column(path) => format!("s{}", Path::to_sql(path)),
+            Self::Wildcard => String::from("*"),
+            Self::Literal(value) => value.to_sql(),
+            Self::Param(name) => format!(":{}", name),
+            Self::CharLength(e) => format!("CHAR_LENGTH({})", e.to_sql()),
+            Self::Lower(e) => format!("LOWER({})", e.to_sql()),
+            Self::Upper(e) => format!("UPPER({})", e.to_sql()),
+            Self::Substring(e, start, None) => {
+                format!("SUBSTRING({} FROM {})", e.to_sql(), start.to_sql())
+            }
+            Self::Substring(e, start, Some(length)) => format!(
+                "SUBSTRING({} FROM {} FOR {})",
+                e.to_sql(),
+                start.to_sql(),
+                length.to_sql()
+            ),
+            Self::Trim(TrimSide::Both, None, e) => format!("TRIM({})", e.to_sql()),
+            Self::Trim(side, None, e) => format!("TRIM({} FROM {})", side.as_str(), e.to_sql()),
+            Self::Trim(side, Some(characters), e) => format!(
+                "TRIM({} {} FROM {})",
+                side.as_str(),
+                characters.to_sql(),
+                e.to_sql()
+            ),
+            Self::Concat(a, b) => self.to_binary_sql(a, "||", b),
+            Self::Cast(e, to) => format!("CAST({} AS {})", e.to_sql(), to.as_str()),
+            Self::Case(whens, otherwise) => build_case(
+                "CASE",
+                whens
+                    .iter()
+                    .map(|(when, then)| (when.clone().to_where(), then.to_sql())),
+                otherwise,
+            ),
+            Self::CaseOf(e, whens, otherwise) => build_case(
+                &format!("CASE {}", e.to_sql()),
+                whens
+                    .iter()
+                    .map(|(when, then)| (when.to_sql(), then.to_sql())),
+                otherwise,
+            ),
+            Self::Coalesce(exprs) => format!(
+                "COALESCE({})",
+                exprs
+                    .iter()
+                    .map(Expr::to_sql)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Self::NullIf(a, b) => format!("NULLIF({}, {})", a.to_sql(), b.to_sql()),
+            Self::DateAdd(part, quantity, e) => format!(
+                "DATE_ADD({}, {}, {})",
+                part.as_str(),
+                quantity.to_sql(),
+                e.to_sql()
+            ),
+            Self::DateDiff(part, from, to) => format!(
+                "DATE_DIFF({}, {}, {})",
+                part.as_str(),
+                from.to_sql(),
+                to.to_sql()
+            ),
+            Self::Extract(part, e) => format!("EXTRACT({} FROM {})", part.as_str(), e.to_sql()),
+            Self::ToString(e, pattern) => {
+                format!("TO_STRING({}, {})", e.to_sql(), string_literal(pattern))
+            }
+            Self::ToTimestamp(e) => format!("TO_TIMESTAMP({})", e.to_sql()),
+            Self::UtcNow => String::from("UTCNOW()"),
+            Self::Add(a, b) => self.to_binary_sql(a, "+", b),
+            Self::Sub(a, b) => self.to_binary_sql(a, "-", b),
+            Self::Mul(a, b) => self.to_binary_sql(a, "*", b),
+            Self::Div(a, b) => self.to_binary_sql(a, "/", b),
+            Self::Mod(a, b) => self.to_binary_sql(a, "%", b),
+            Self::Neg(e) => {
+                let sql = e.to_sql();
+                // `--` would start a comment
+                if e.precedence() < self.precedence() || sql.starts_with('-') {
+                    format!("-({})", sql)
+                } else {
+                    format!("-{}", sql)
+                }
+            }
+        }
+    }
+}
+
+fn build_case(
+    case: &str,
+    whens: impl Iterator<Item = (String, String)>,
+    otherwise: &Option<Box<Expr>>,
+) -> String {
+    let whens = whens
+        .map(|(when, then)| format!(" WHEN {} THEN {}", when, then))
+        .collect::<String>();
+    let otherwise = otherwise
+        .as_ref()
+        .map(|e| format!(" ELSE {}", e.to_sql()))
+        .unwrap_or_default();
+
+    format!("{}{}{} END", case, whens, otherwise)
+}
+
+macro_rules! arithmetic {
+    ($trait:ident, $method:ident, $variant:ident) => {
+        impl<T: Into<Expr>> std::ops::$trait<T> for Expr {
+            type Output = Expr;
+
+            fn $method(self, other: T) -> Self::Output {
+                Expr::$variant(Box::new(self), Box::new(other.into()))
+            }
+        }
+    };
+}
+
+arithmetic!(Add, add, Add);
+arithmetic!(Sub, sub, Sub);
+arithmetic!(Mul, mul, Mul);
+arithmetic!(Div, div, Div);
+arithmetic!(Rem, rem, Mod);
+
+impl std::ops::Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Self::Output {
+        Expr::Neg(Box::new(self))
+    }
+}
+
+impl<T: Into<Value>> From<T> for Expr {
+    fn from(value: T) -> Self {
+        Expr::Literal(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn string_functions() {
+        let name = || Expr::column("name");
+
+        assert_eq!(name().char_length().to_sql(), "CHAR_LENGTH(s.name)");
+        assert_eq!(name().lower().to_sql(), "LOWER(s.name)");
+        assert_eq!(name().trim().upper().to_sql(), "UPPER(TRIM(s.name))");
+        assert_eq!(
+            name().substring(2, Some(3.into())).to_sql(),
+            "SUBSTRING(s.name FROM 2 FOR 3)"
+        );
+        assert_eq!(
+            name().substring(Expr::column("start"), None).to_sql(),
+            "SUBSTRING(s.name FROM s.start)"
+        );
+    }
+
+    #[test]
+    fn trims() {
+        let name = || Expr::column("name");
+
+        assert_eq!(
+            name().trim_with(TrimSide::Leading, None).to_sql(),
+            "TRIM(LEADING FROM s.name)"
+        );
+        assert_eq!(
+            name().trim_with(TrimSide::Both, Some("0".into())).to_sql(),
+            "TRIM(BOTH '0' FROM s.name)"
+        );
+        assert_eq!(
+            name()
+                .trim_with(TrimSide::Trailing, Some("/".into()))
+                .to_sql(),
+            "TRIM(TRAILING '/' FROM s.name)"
+        );
+    }
+
+    #[test]
+    fn casts() {
+        assert_eq!(
+            Expr::column("age").cast(CastType::Int).to_sql(),
+            "CAST(s.age AS INT)"
+        );
+        assert_eq!(
+            Expr::from("2021-01-01T").cast(CastType::Timestamp).to_sql(),
+            "CAST('2021-01-01T' AS TIMESTAMP)"
+        );
+        assert_eq!(
+            Expr::column("flag").cast(CastType::Bool).to_sql(),
+            "CAST(s.flag AS BOOL)"
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        let age = || Expr::column("age").cast(CastType::Int);
+        let case = Expr::case(
+            vec![
+                (Clause::L(age(), 18.into()), "minor".into()),
+                (Clause::Between(age(), 18.into(), 65.into()), "adult".into()),
+            ],
+            Some("senior".into()),
+        );
+
+        assert_eq!(
+            case.to_sql(),
+            "CASE WHEN CAST(s.age AS INT) < 18 THEN 'minor' WHEN CAST(s.age AS INT) BETWEEN 18 AND 65 THEN 'adult' ELSE 'senior' END"
+        );
+        assert_eq!(
+            Expr::column("status")
+                .case_of(vec![(1.into(), "active".into())], None)
+                .to_sql(),
+            "CASE s.status WHEN 1 THEN 'active' END"
+        );
+        assert_eq!(
+            Expr::coalesce(vec![Expr::column("nick"), Expr::column("name"), "?".into()]).to_sql(),
+            "COALESCE(s.nick, s.name, '?')"
+        );
+        assert_eq!(
+            Expr::column("score").null_if(-1).to_sql(),
+            "NULLIF(s.score, -1)"
+        );
+    }
+
+    #[test]
+    fn date_functions() {
+        let created = || Expr::column("created").parse_timestamp();
+        let since = Utc.with_ymd_and_hms(2021, 6, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            Expr::utc_now().date_add(DatePart::Hour, -24).to_sql(),
+            "DATE_ADD(hour, -24, UTCNOW())"
+        );
+        assert_eq!(
+            Expr::date_diff(DatePart::Day, since.into(), created()).to_sql(),
+            "DATE_DIFF(day, `2021-06-01T00:00:00Z`, TO_TIMESTAMP(s.created))"
+        );
+        assert_eq!(
+            created().extract(ExtractPart::TimezoneHour).to_sql(),
+            "EXTRACT(timezone_hour FROM TO_TIMESTAMP(s.created))"
+        );
+        assert_eq!(
+            created().format_timestamp("yyyy-MM-dd'T'HH").to_sql(),
+            "TO_STRING(TO_TIMESTAMP(s.created), 'yyyy-MM-dd''T''HH')"
+        );
+    }
+
+    #[test]
+    fn last_24_hours() {
+        let clause = Clause::GE(
+            Expr::column("logged_at").parse_timestamp(),
+            Expr::utc_now().date_add(DatePart::Hour, -24),
+        );
+
+        assert_eq!(
+            clause.to_where(),
+            "TO_TIMESTAMP(s.logged_at) >= DATE_ADD(hour, -24, UTCNOW())"
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let price = || Expr::column("price");
+        let qty = || Expr::column("qty");
+
+        assert_eq!((price() * qty()).to_sql(), "s.price * s.qty");
+        assert_eq!((price() * qty() + 1).to_sql(), "s.price * s.qty + 1");
+        assert_eq!(((price() + 1) * qty()).to_sql(), "(s.price + 1) * s.qty");
+        assert_eq!((price() - (qty() - 1)).to_sql(), "s.price - (s.qty - 1)");
+        assert_eq!((price() - qty() - 1).to_sql(), "s.price - s.qty - 1");
+        assert_eq!((price() / qty() % 7).to_sql(), "s.price / s.qty % 7");
+        assert_eq!((price() % (qty() * 2)).to_sql(), "s.price % (s.qty * 2)");
+        assert_eq!((price() - Expr::from(-5)).to_sql(), "s.price - -5");
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!((-Expr::column("a")).to_sql(), "-s.a");
+        assert_eq!((-(Expr::column("a") + 2)).to_sql(), "-(s.a + 2)");
+        assert_eq!((-Expr::from(-2)).to_sql(), "-(-2)");
+        assert_eq!((-(-Expr::column("a"))).to_sql(), "-(-s.a)");
+        assert_eq!(
+            (-Expr::column("a") * Expr::column("b")).to_sql(),
+            "-s.a * s.b"
+        );
+    }
+
+    #[test]
+    fn arithmetic_and_concatenation() {
+        let total = Expr::column("price") * Expr::column("qty");
+
+        assert_eq!(
+            Expr::from("total: ").concat(total.clone()).to_sql(),
+            "'total: ' || s.price * s.qty"
+        );
+        assert_eq!(
+            (Expr::column("a").concat("1").cast(CastType::Int) + 1).to_sql(),
+            "CAST(s.a || '1' AS INT) + 1"
+        );
+        assert_eq!(
+            (Expr::column("a").concat("1") + 1).to_sql(),
+            "(s.a || '1') + 1"
+        );
+    }
+
+    #[test]
+    fn concatenation() {
+        let full_name = Expr::column("first_name")
+            .concat(" ")
+            .concat(Expr::column("last_name"));
+
+        assert_eq!(full_name.to_sql(), "s.first_name || ' ' || s.last_name");
+    }
+}
@@ -0,0 +1,417 @@
+use super::schema::{Field, FieldType, Schema};
+use super::QueryError;
+
+// A Parquet file ends with its Thrift compact encoded metadata, the metadata length
+// as a little endian u32 and `PAR1`
+const MAGIC: &[u8] = b"PAR1";
+pub(crate) const FOOTER_LENGTH: usize = 8;
+// nested structs deeper than this are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// Length of the metadata before the last `FOOTER_LENGTH` bytes of `tail`.
+pub(crate) fn metadata_length(tail: &[u8]) -> Result<usize, QueryError> {
+    if tail.len() < FOOTER_LENGTH || &tail[tail.len() - MAGIC.len()..] != MAGIC {
+        return Err(malformed("the object doesn't end with `PAR1`"));
+    }
+    let length = &tail[tail.len() - FOOTER_LENGTH..tail.len() - MAGIC.len()];
+
+    Ok(u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize)
+}
+
+/// Schema of a Parquet file from its last bytes, `tail` holds at least the metadata and footer.
+pub(crate) fn footer_schema(tail: &[u8]) -> Result<Schema, QueryError> {
+    let length = metadata_length(tail)?;
+    let end = tail.len() - FOOTER_LENGTH;
+    if length > end {
+        return Err(malformed("the metadata is longer than the bytes read"));
+    }
+
+    parquet_schema(&tail[end - length..end])
+}
+
+/// Schema in the `FileMetaData` of a Parquet file.
+fn parquet_schema(metadata: &[u8]) -> Result<Schema, QueryError> {
+    let mut thrift = Thrift {
+        bytes: metadata,
+        offset: 0,
+    };
+    let mut elements = Vec::new();
+
+    // the schema is field 2, right after the version
+    let mut last = 0;
+    while let Some((id, kind)) = thrift.field(&mut last)? {
+        if id != 2 {
+            thrift.skip(kind, 0)?;
+            continue;
+        }
+        let (size, _) = thrift.list()?;
+        for _ in 0..size {
+            elements.push(thrift.element()?);
+        }
+        break;
+    }
+
+    let root = elements
+        .first()
+        .ok_or_else(|| malformed("the metadata has no schema"))?;
+    let mut next = 1;
+    let mut fields = Vec::new();
+    for _ in 0..root.children {
+        fields.push(field(&elements, &mut next, 0)?);
+    }
+
+    Ok(Schema::new(fields))
+}
+
+// `SchemaElement`, the schema is its tree flattened depth first
+#[derive(Default)]
+struct Element {
+    name: String,
+    physical: Option<i64>,
+    repetition: i64, // 0 required, 1 optional, 2 repeated
+    children: usize,
+    converted: Option<i64>,
+    logical: Option<i16>, // field of the `LogicalType` union
+}
+
+fn field(elements: &[Element], next: &mut usize, depth: usize) -> Result<Field, QueryError> {
+    let element = elements
+        .get(*next)
+        .ok_or_else(|| malformed("the schema has fewer elements than it says"))?;
+    if depth > MAX_DEPTH {
+        return Err(malformed("the schema is nested too deeply"));
+    }
+    *next += 1;
+
+    let mut children = Vec::new();
+    for _ in 0..element.children {
+        children.push(field(elements, next, depth + 1)?);
+    }
+
+    let is_list = element.converted == Some(3) || element.logical == Some(3);
+    let is_map = matches!(element.converted, Some(1) | Some(2)) || element.logical == Some(2);
+    let field_type = if is_list && children.len() == 1 {
+        // `<list> (LIST) { repeated group list { <element> } }`, or the repeated element itself
+        let element = match children.remove(0).field_type {
+            FieldType::List(element) => match *element {
+                FieldType::Struct(mut fields) if fields.len() == 1 => fields.remove(0).field_type,
+                element => element,
+            },
+            element => element,
+        };
+        FieldType::List(Box::new(element))
+    } else if is_map {
+        FieldType::Any
+    } else if element.children > 0 || element.physical.is_none() {
+        FieldType::Struct(children)
+    } else {
+        primitive(element)
+    };
+
+    Ok(match element.repetition {
+        2 => Field::new(&element.name, FieldType::List(Box::new(field_type)), false),
+        repetition => Field::new(&element.name, field_type, repetition == 1),
+    })
+}
+
+fn primitive(element: &Element) -> FieldType {
+    match (element.physical, element.converted, element.logical) {
+        (_, Some(5), _) | (_, _, Some(5)) => FieldType::Decimal,
+        (Some(3), _, _) | (_, Some(6), _) | (_, Some(9), _) | (_, Some(10), _) => {
+            FieldType::Timestamp
+        }
+        (_, _, Some(6)) | (_, _, Some(8)) => FieldType::Timestamp,
+        (Some(0), _, _) => FieldType::Bool,
+        (Some(1), _, _) | (Some(2), _, _) => FieldType::Int,
+        (Some(4), _, _) | (Some(5), _, _) => FieldType::Float,
+        _ => FieldType::String,
+    }
+}
+
+fn malformed(message: &str) -> QueryError {
+    QueryError::Decode(format!("malformed Parquet footer, {}", message))
+}
+
+// Thrift compact protocol, only what reading `FileMetaData.schema` needs
+struct Thrift<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Thrift<'a> {
+    fn byte(&mut self) -> Result<u8, QueryError> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or_else(|| malformed("the metadata ends early"))?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, QueryError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed("a varint is too long"))
+    }
+
+    fn zigzag(&mut self) -> Result<i64, QueryError> {
+        let n = self.varint()?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    fn binary(&mut self) -> Result<&'a [u8], QueryError> {
+        let length = self.varint()? as usize;
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| malformed("a string is longer than the metadata"))?;
+        let binary = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(binary)
+    }
+
+    // field id and type, `None` at the end of a struct
+    fn field(&mut self, last: &mut i16) -> Result<Option<(i16, u8)>, QueryError> {
+        let header = self.byte()?;
+        if header == 0 {
+            return Ok(None);
+        }
+        let delta = (header >> 4) as i16;
+        *last = if delta == 0 {
+            self.zigzag()? as i16
+        } else {
+            last.wrapping_add(delta)
+        };
+        Ok(Some((*last, header & 0x0f)))
+    }
+
+    // size and element type
+    fn list(&mut self) -> Result<(usize, u8), QueryError> {
+        let header = self.byte()?;
+        let size = match header >> 4 {
+            15 => self.varint()? as usize,
+            size => size as usize,
+        };
+        Ok((size, header & 0x0f))
+    }
+
+    fn element(&mut self) -> Result<Element, QueryError> {
+        let mut element = Element::default();
+        let mut last = 0;
+        while let Some((id, kind)) = self.field(&mut last)? {
+            match (id, kind) {
+                (1, 5) => element.physical = Some(self.zigzag()?),
+                (3, 5) => element.repetition = self.zigzag()?,
+                (4, 8) => element.name = String::from_utf8_lossy(self.binary()?).to_string(),
+                (5, 5) => element.children = self.zigzag()?.max(0) as usize,
+                (6, 5) => element.converted = Some(self.zigzag()?),
+                (10, 12) => {
+                    let mut last = 0;
+                    if let Some((id, kind)) = self.field(&mut last)? {
+                        element.logical = Some(id);
+                        self.skip(kind, 1)?;
+                        // a union has a single field
+                        self.skip_fields(&mut last, 1)?;
+                    }
+                }
+                (_, kind) => self.skip(kind, 0)?,
+            }
+        }
+        Ok(element)
+    }
+
+    fn skip(&mut self, kind: u8, depth: usize) -> Result<(), QueryError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("the metadata is nested too deeply"));
+        }
+        match kind {
+            1 | 2 => (), // booleans are in the field header
+            3 => {
+                self.byte()?;
+            }
+            4..=6 => {
+                self.varint()?;
+            }
+            7 => {
+                for _ in 0..8 {
+                    self.byte()?;
+                }
+            }
+            8 => {
+                self.binary()?;
+            }
+            9 | 10 => {
+                let (size, kind) = self.list()?;
+                for _ in 0..size {
+                    self.skip_element(kind, depth + 1)?;
+                }
+            }
+            11 => {
+                let size = self.varint()? as usize;
+                if size > 0 {
+                    let kinds = self.byte()?;
+                    for _ in 0..size {
+                        self.skip_element(kinds >> 4, depth + 1)?;
+                        self.skip_element(kinds & 0x0f, depth + 1)?;
+                    }
+                }
+            }
+            12 => {
+                let mut last = 0;
+                self.skip_fields(&mut last, depth + 1)?;
+            }
+            kind => return Err(malformed(&format!("unknown Thrift type {}", kind))),
+        }
+        Ok(())
+    }
+
+    // booleans in lists and maps take a byte
+    fn skip_element(&mut self, kind: u8, depth: usize) -> Result<(), QueryError> {
+        match kind {
+            1 | 2 => self.byte().map(|_| ()),
+            kind => self.skip(kind, depth),
+        }
+    }
+
+    fn skip_fields(&mut self, last: &mut i16, depth: usize) -> Result<(), QueryError> {
+        while let Some((_, kind)) = self.field(last)? {
+            self.skip(kind, depth)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod footer_test {
+    use super::*;
+
+    // Thrift compact encoding of the fields `parquet_schema` reads
+    #[derive(Default)]
+    struct Writer {
+        bytes: Vec<u8>,
+        last: Vec<i16>,
+    }
+
+    impl Writer {
+        fn varint(&mut self, mut n: u64) {
+            while n >= 0x80 {
+                self.bytes.push((n as u8) | 0x80);
+                n >>= 7;
+            }
+            self.bytes.push(n as u8);
+        }
+
+        fn field(&mut self, id: i16, kind: u8) {
+            let last = self.last.last_mut().unwrap();
+            self.bytes.push((((id - *last) as u8) << 4) | kind);
+            *last = id;
+        }
+
+        fn int(&mut self, id: i16, value: i64) {
+            self.field(id, 5);
+            self.varint(((value << 1) ^ (value >> 63)) as u64);
+        }
+
+        fn string(&mut self, id: i16, value: &str) {
+            self.field(id, 8);
+            self.varint(value.len() as u64);
+            self.bytes.extend_from_slice(value.as_bytes());
+        }
+
+        fn begin(&mut self) {
+            self.last.push(0);
+        }
+
+        fn end(&mut self) {
+            self.bytes.push(0);
+            self.last.pop();
+        }
+    }
+
+    // name, physical type, repetition, children, converted type
+    type Column<'a> = (&'a str, Option<i64>, i64, i64, Option<i64>);
+
+    fn footer(columns: &[Column]) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.begin();
+        w.int(1, 1);
+        w.field(2, 9);
+        w.bytes.push(0xfc); // 15 or more structs
+        w.varint(columns.len() as u64);
+        for (name, physical, repetition, children, converted) in columns {
+            w.begin();
+            if let Some(physical) = physical {
+                w.int(1, *physical);
+            }
+            w.int(3, *repetition);
+            w.string(4, name);
+            if *children > 0 {
+                w.int(5, *children);
+            }
+            if let Some(converted) = converted {
+                w.int(6, *converted);
+            }
+            w.end();
+        }
+        w.int(3, 42); // num_rows, after the schema
+        w.end();
+
+        let length = w.bytes.len() as u32;
+        w.bytes.extend_from_slice(&length.to_le_bytes());
+        w.bytes.extend_from_slice(MAGIC);
+        w.bytes
+    }
+
+    #[test]
+    fn schema() {
+        let file = footer(&[
+            ("schema", None, 0, 4, None),
+            ("id", Some(2), 0, 0, None),
+            ("name", Some(6), 1, 0, Some(0)),
+            ("tags", None, 1, 1, Some(3)),
+            ("list", None, 2, 1, None),
+            ("element", Some(6), 1, 0, Some(0)),
+            ("address", None, 1, 2, None),
+            ("city", Some(6), 0, 0, Some(0)),
+            ("since", Some(2), 1, 0, Some(9)),
+        ]);
+        assert_eq!(
+            footer_schema(&file).unwrap(),
+            Schema::new(vec![
+                Field::new("id", FieldType::Int, false),
+                Field::new("name", FieldType::String, true),
+                Field::new("tags", FieldType::List(Box::new(FieldType::String)), true),
+                Field::new(
+                    "address",
+                    FieldType::Struct(vec![
+                        Field::new("city", FieldType::String, false),
+                        Field::new("since", FieldType::Timestamp, true),
+                    ]),
+                    true
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn malformed_footers() {
+        assert!(metadata_length(b"not parquet").is_err());
+        let file = footer(&[("schema", None, 0, 0, None)]);
+        assert!(footer_schema(&file[file.len() - 20..]).is_err());
+
+        let file = footer(&[("schema", None, 0, 2, None), ("id", Some(2), 0, 0, None)]);
+        let metadata = &file[..file.len() - FOOTER_LENGTH];
+        assert!(matches!(
+            parquet_schema(metadata),
+            Err(QueryError::Decode(message)) if message.contains("fewer elements")
+        ));
+        assert!(parquet_schema(&metadata[..metadata.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,813 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use super::aggregate::{AggregateMerge, AggregatePlan};
+use super::path::Path;
+use super::stream::{csv_record, Record};
+use super::{Expr, Order, QueryContent, QueryError, Select};
+
+/// Output field of a query run with local operations.
+#[derive(Clone, Debug)]
+enum Output {
+    Value { name: String, column: String },
+    Aggregate { name: String },
+}
+
+#[derive(Clone, Debug)]
+enum OrderKey {
+    Field(String),  // output field, aliases included
+    Column(String), // pushed down expression
+}
+
+/// GROUP BY, ORDER BY and DISTINCT, run on the records S3 Select returns.
+///
+/// S3 Select only evaluates the projection and the where clause, every expression the local
+/// operations need is pushed down as an aliased column, `c0`, `c1`, ...
+#[derive(Clone)]
+pub(crate) struct LocalPlan {
+    pub(crate) pushed: QueryContent,
+    outputs: Vec<Output>,
+    group: Option<(Vec<String>, AggregatePlan, Vec<Option<String>>)>, // keys, aggregates, their arguments
+    order: Vec<(OrderKey, Order)>,
+    distinct: bool,
+    limit: Option<usize>,
+}
+
+impl LocalPlan {
+    /// Plans `query` if it has local operations, `None` if S3 Select can run it as is.
+    pub(crate) fn new(query: &QueryContent) -> Result<Option<Self>, QueryError> {
+        if query.group_by.is_empty() && query.order_by.is_empty() && !query.distinct {
+            return Ok(None);
+        }
+
+        let mut columns: Vec<Expr> = Vec::new();
+        let mut column = |expr: &Expr| -> String {
+            let i = columns.iter().position(|c| c == expr).unwrap_or_else(|| {
+                columns.push(expr.clone());
+                columns.len() - 1
+            });
+            format!("c{}", i)
+        };
+
+        let keys = query.group_by.iter().map(&mut column).collect::<Vec<_>>();
+        let mut outputs = Vec::new();
+        let mut aggregates = Vec::new();
+        let mut arguments = Vec::new();
+        for (name, item) in outputs_of(&query.select) {
+            match item {
+                Item::Value(expr) => {
+                    if !query.group_by.is_empty() && !query.group_by.contains(&expr) {
+                        return Err(QueryError::Invalid(format!(
+                            "`{}` is neither aggregated nor in GROUP BY",
+                            expr.to_sql()
+                        )));
+                    }
+                    outputs.push(Output::Value {
+                        name,
+                        column: column(&expr),
+                    });
+                }
+                Item::Aggregate(aggregate) => {
+                    let argument = match &aggregate {
+                        Select::Count(Expr::Wildcard) => None,
+                        Select::Count(e)
+                        | Select::Sum(e)
+                        | Select::Min(e)
+                        | Select::Max(e)
+                        | Select::Avg(e) => Some(column(e)),
+                        Select::Elements(_) | Select::As(..) => None,
+                    };
+                    arguments.push(argument);
+                    aggregates.push(aggregate.alias(&name));
+                    outputs.push(Output::Aggregate { name });
+                }
+            }
+        }
+
+        let group = if aggregates.is_empty() && query.group_by.is_empty() {
+            None
+        } else {
+            // without GROUP BY the aggregates fold every record into one group
+            let plan = AggregatePlan::new(&aggregates)
+                .map(|(plan, _)| plan)
+                .unwrap_or_default();
+            Some((keys.clone(), plan, arguments))
+        };
+
+        let mut order = Vec::new();
+        for (expr, direction) in &query.order_by {
+            let field = match expr {
+                Expr::Column(path) => match path.as_slice() {
+                    [Path::Name(name)] if outputs.iter().any(|o| o.name() == name) => {
+                        Some(name.clone())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            let key = match field {
+                Some(name) => OrderKey::Field(name),
+                // grouped records only keep their keys and aggregates
+                None if group.is_some() => match query.group_by.iter().position(|g| g == expr) {
+                    Some(k) => OrderKey::Column(keys[k].clone()),
+                    None => {
+                        return Err(QueryError::Invalid(format!(
+                            "ORDER BY `{}` is neither an output field nor in GROUP BY",
+                            expr.to_sql()
+                        )))
+                    }
+                },
+                None => OrderKey::Column(column(expr)),
+            };
+            order.push((key, direction.clone()));
+        }
+
+        let mut pushed = query.clone();
+        pushed.select = columns
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| Select::Elements(vec![expr.clone()]).alias(&format!("c{}", i)))
+            .collect();
+        pushed.group_by = Vec::new();
+        pushed.order_by = Vec::new();
+        pushed.distinct = false;
+        pushed.limit = None;
+        if pushed.select.is_empty() {
+            // `Count(*)` alone still needs a record per match
+            pushed.select = vec![Select::Elements(vec![Expr::Literal(true.into())]).alias("c0")];
+        }
+
+        Ok(Some(LocalPlan {
+            pushed,
+            outputs,
+            group,
+            order,
+            distinct: query.distinct,
+            limit: query.limit,
+        }))
+    }
+
+    pub(crate) fn runner(&self, spill: Option<Spill>) -> LocalRun {
+        LocalRun {
+            plan: self.clone(),
+            groups: HashMap::new(),
+            group_order: Vec::new(),
+            seen: HashSet::new(),
+            sorter: Sorter::new(self.order.clone(), spill),
+        }
+    }
+
+    fn row(&self, fields: Record, pushed: &Record) -> Row {
+        let order = self
+            .order
+            .iter()
+            .map(|(key, _)| match key {
+                OrderKey::Field(name) => fields.get(name).cloned().unwrap_or(Json::Null),
+                OrderKey::Column(column) => pushed.get(column).cloned().unwrap_or(Json::Null),
+            })
+            .collect();
+
+        Row { fields, order }
+    }
+}
+
+impl Output {
+    fn name(&self) -> &str {
+        match self {
+            Self::Value { name, .. } | Self::Aggregate { name } => name,
+        }
+    }
+}
+
+pub(crate) enum Item {
+    Value(Expr),
+    Aggregate(Select),
+}
+
+// Output fields in order, named like S3 names them: aliases, the last name of a column path or `_n`
+pub(crate) fn outputs_of(select: &[Select]) -> Vec<(String, Item)> {
+    let mut outputs = Vec::new();
+
+    for element in select {
+        let (element, alias) = match element {
+            Select::As(element, alias) => (element.as_ref(), Some(alias.clone())),
+            element => (element, None),
+        };
+        match element {
+            Select::Elements(exprs) => {
+                for expr in exprs {
+                    let name = alias
+                        .clone()
+                        .filter(|_| exprs.len() == 1)
+                        .unwrap_or_else(|| default_name(expr, outputs.len() + 1));
+                    outputs.push((name, Item::Value(expr.clone())));
+                }
+            }
+            aggregate => {
+                let name = alias.unwrap_or_else(|| format!("_{}", outputs.len() + 1));
+                outputs.push((name, Item::Aggregate(aggregate.clone())));
+            }
+        }
+    }
+
+    outputs
+}
+
+/// Names of CSV output fields by position, up to the first `*` which S3 expands to fields
+/// the query doesn't name.
+pub(crate) fn csv_names(select: &[Select]) -> Vec<String> {
+    outputs_of(select)
+        .into_iter()
+        .take_while(|(_, item)| !matches!(item, Item::Value(Expr::Wildcard)))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// A record of JSON output as it reads back from CSV output, for results merged locally.
+///
+/// Fields come in select order, or all in name order and keyed `_n` when the query has a `*`.
+pub(crate) fn to_csv_record(select: &[Select], record: &Record) -> Record {
+    let names = csv_names(select);
+    if names.len() < outputs_of(select).len() {
+        return csv_record(&[], record.values().map(csv_field).collect());
+    }
+
+    let fields = names
+        .iter()
+        .map(|name| record.get(name).map_or_else(String::new, csv_field))
+        .collect();
+    csv_record(&names, fields)
+}
+
+/// A value as S3 writes it in a CSV output field.
+pub(crate) fn csv_field(value: &Json) -> String {
+    match value {
+        Json::Null => String::new(),
+        Json::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn default_name(expr: &Expr, position: usize) -> String {
+    match expr {
+        Expr::Column(path) => match path.last() {
+            Some(Path::Name(name)) => name.clone(),
+            _ => format!("_{}", position),
+        },
+        _ => format!("_{}", position),
+    }
+}
+
+/// Record as it goes through the local operations, `order` holds its sort keys.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Row {
+    fields: Record,
+    order: Vec<Json>,
+}
+
+/// Records fed in the order S3 sends them, results come out once every record is in.
+pub(crate) struct LocalRun {
+    plan: LocalPlan,
+    groups: HashMap<String, (Vec<Json>, AggregateMerge)>,
+    group_order: Vec<String>, // first seen first, so unsorted results are stable
+    seen: HashSet<String>,
+    sorter: Sorter,
+}
+
+impl LocalRun {
+    pub(crate) fn push(&mut self, pushed: Record) -> Result<(), QueryError> {
+        let (keys, plan, arguments) = match &self.plan.group {
+            Some(group) => group,
+            None => {
+                let fields = self
+                    .plan
+                    .outputs
+                    .iter()
+                    .filter_map(|output| match output {
+                        Output::Value { name, column } => Some((
+                            name.clone(),
+                            pushed.get(column).cloned().unwrap_or(Json::Null),
+                        )),
+                        Output::Aggregate { .. } => None,
+                    })
+                    .collect();
+                let row = self.plan.row(fields, &pushed);
+                return self.emit(row);
+            }
+        };
+
+        let values = keys
+            .iter()
+            .map(|key| pushed.get(key).cloned().unwrap_or(Json::Null))
+            .collect::<Vec<Json>>();
+        let group = serde_json::to_string(&values).unwrap_or_default();
+        if !self.groups.contains_key(&group) {
+            self.group_order.push(group.clone());
+        }
+        let (_, merge) = self
+            .groups
+            .entry(group)
+            .or_insert_with(|| (values, plan.merger()));
+
+        let arguments = arguments
+            .iter()
+            .map(|argument| match argument {
+                Some(column) => pushed.get(column).cloned().unwrap_or(Json::Null),
+                None => Json::Bool(true),
+            })
+            .collect::<Vec<Json>>();
+        merge.add(&plan.row_partial(&arguments))
+    }
+
+    fn emit(&mut self, row: Row) -> Result<(), QueryError> {
+        if self.plan.distinct {
+            let fields = serde_json::to_string(&row.fields).unwrap_or_default();
+            if !self.seen.insert(fields) {
+                return Ok(());
+            }
+        }
+
+        self.sorter.push(row)
+    }
+
+    /// Results in order, limited to the query `limit`.
+    pub(crate) fn finish(mut self) -> Result<LocalRecords, QueryError> {
+        if let Some((keys, plan, _)) = self.plan.group.clone() {
+            // aggregates without GROUP BY return a row even when nothing matched
+            if keys.is_empty() && self.groups.is_empty() {
+                self.groups
+                    .insert(String::new(), (Vec::new(), plan.merger()));
+                self.group_order.push(String::new());
+            }
+
+            for group in std::mem::take(&mut self.group_order) {
+                let (values, merge) = self
+                    .groups
+                    .remove(&group)
+                    .unwrap_or_else(|| (Vec::new(), plan.merger()));
+                let mut aggregates = merge.finish();
+                let pushed = keys.iter().cloned().zip(values).collect::<Record>();
+                let fields = self
+                    .plan
+                    .outputs
+                    .iter()
+                    .map(|output| match output {
+                        Output::Value { name, column } => (
+                            name.clone(),
+                            pushed.get(column).cloned().unwrap_or(Json::Null),
+                        ),
+                        Output::Aggregate { name } => {
+                            (name.clone(), aggregates.remove(name).unwrap_or(Json::Null))
+                        }
+                    })
+                    .collect();
+                let row = self.plan.row(fields, &pushed);
+                self.emit(row)?;
+            }
+        }
+
+        Ok(LocalRecords {
+            rows: self.sorter.finish()?,
+            remaining: self.plan.limit,
+        })
+    }
+}
+
+/// Results of a `LocalRun`, read back from disk when the sort spilled.
+pub(crate) struct LocalRecords {
+    rows: SortedRows,
+    remaining: Option<usize>,
+}
+
+impl Iterator for LocalRecords {
+    type Item = Result<Record, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        self.remaining = self.remaining.map(|remaining| remaining - 1);
+
+        Some(self.rows.next()?.map(|row| row.fields))
+    }
+}
+
+/// Where sorts that don't fit in memory write their sorted runs.
+#[derive(Clone, Debug)]
+pub(crate) struct Spill {
+    pub(crate) dir: PathBuf,
+    pub(crate) records_in_memory: usize,
+}
+
+static SPILL_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+struct Sorter {
+    order: Vec<(OrderKey, Order)>,
+    spill: Option<Spill>,
+    rows: Vec<Row>,
+    runs: Vec<PathBuf>,
+}
+
+impl Sorter {
+    fn new(order: Vec<(OrderKey, Order)>, spill: Option<Spill>) -> Self {
+        Sorter {
+            order,
+            spill,
+            rows: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: Row) -> Result<(), QueryError> {
+        self.rows.push(row);
+
+        match &self.spill {
+            Some(spill)
+                if !self.order.is_empty() && self.rows.len() >= spill.records_in_memory.max(1) =>
+            {
+                self.write_run()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn compare(order: &[(OrderKey, Order)], a: &Row, b: &Row) -> Ordering {
+        order
+            .iter()
+            .zip(a.order.iter().zip(&b.order))
+            .map(|((_, direction), (a, b))| match direction {
+                Order::Asc => compare(a, b),
+                Order::Desc => compare(b, a),
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn sort(&mut self) {
+        let order = &self.order;
+        // stable, so rows with equal keys keep the order they came in
+        self.rows.sort_by(|a, b| Self::compare(order, a, b));
+    }
+
+    fn write_run(&mut self) -> Result<(), QueryError> {
+        let dir = match &self.spill {
+            Some(spill) => spill.dir.clone(),
+            None => return Ok(()),
+        };
+        self.sort();
+
+        let path = dir.join(format!(
+            "s3ql-sort-{}-{}.jsonl",
+            std::process::id(),
+            SPILL_RUNS.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        let mut writer = BufWriter::new(File::create(&path).map_err(QueryError::Io)?);
+        self.runs.push(path);
+        for row in self.rows.drain(..) {
+            serde_json::to_writer(&mut writer, &row)
+                .map_err(|e| QueryError::Decode(e.to_string()))?;
+            writer.write_all(b"\n").map_err(QueryError::Io)?;
+        }
+
+        writer.flush().map_err(QueryError::Io)
+    }
+
+    fn finish(mut self) -> Result<SortedRows, QueryError> {
+        if self.runs.is_empty() {
+            self.sort();
+            return Ok(SortedRows::Memory(
+                std::mem::take(&mut self.rows).into_iter(),
+            ));
+        }
+        if !self.rows.is_empty() {
+            self.write_run()?;
+        }
+
+        let mut readers = Vec::new();
+        for path in &self.runs {
+            let file = File::open(path).map_err(QueryError::Io)?;
+            readers.push(BufReader::new(file).lines());
+        }
+        let mut merge = RunMerge {
+            order: self.order.clone(),
+            heads: Vec::new(),
+            readers,
+            runs: std::mem::take(&mut self.runs),
+        };
+        for i in 0..merge.readers.len() {
+            let head = merge.read(i)?;
+            merge.heads.push(head);
+        }
+
+        Ok(SortedRows::Disk(merge))
+    }
+}
+
+enum SortedRows {
+    Memory(std::vec::IntoIter<Row>),
+    Disk(RunMerge),
+}
+
+impl Iterator for SortedRows {
+    type Item = Result<Row, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Memory(rows) => rows.next().map(Ok),
+            Self::Disk(merge) => merge.next(),
+        }
+    }
+}
+
+/// K-way merge of the sorted runs, the files are removed once the merge is dropped.
+struct RunMerge {
+    order: Vec<(OrderKey, Order)>,
+    heads: Vec<Option<Row>>,
+    readers: Vec<Lines<BufReader<File>>>,
+    runs: Vec<PathBuf>,
+}
+
+impl RunMerge {
+    fn read(&mut self, run: usize) -> Result<Option<Row>, QueryError> {
+        match self.readers[run].next() {
+            Some(line) => {
+                let line = line.map_err(QueryError::Io)?;
+                let row =
+                    serde_json::from_str(&line).map_err(|e| QueryError::Decode(e.to_string()))?;
+                Ok(Some(row))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next(&mut self) -> Option<Result<Row, QueryError>> {
+        // earlier runs win ties, which keeps the sort stable
+        let mut smallest: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            if let Some(row) = head {
+                let smaller = smallest.is_none_or(|s| {
+                    let current = self.heads[s].as_ref().expect("smallest run has a head");
+                    Sorter::compare(&self.order, row, current) == Ordering::Less
+                });
+                if smaller {
+                    smallest = Some(i);
+                }
+            }
+        }
+
+        let run = smallest?;
+        let row = self.heads[run].take();
+        match self.read(run) {
+            Ok(head) => self.heads[run] = head,
+            Err(e) => return Some(Err(e)),
+        }
+
+        row.map(Ok)
+    }
+}
+
+impl Drop for RunMerge {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+impl Drop for Sorter {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+/// Total order over JSON values, `null` < booleans < numbers < strings < arrays < objects.
+pub(crate) fn compare(a: &Json, b: &Json) -> Ordering {
+    fn rank(value: &Json) -> u8 {
+        match value {
+            Json::Null => 0,
+            Json::Bool(_) => 1,
+            Json::Number(_) => 2,
+            Json::String(_) => 3,
+            Json::Array(_) => 4,
+            Json::Object(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Json::Bool(a), Json::Bool(b)) => a.cmp(b),
+        (Json::Number(a), Json::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a
+                .as_f64()
+                .unwrap_or(f64::NAN)
+                .total_cmp(&b.as_f64().unwrap_or(f64::NAN)),
+        },
+        (Json::String(a), Json::String(b)) => a.cmp(b),
+        (Json::Array(a), Json::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Json::Object(a), Json::Object(b)) => serde_json::to_string(a)
+            .unwrap_or_default()
+            .cmp(&serde_json::to_string(b).unwrap_or_default()),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod local_test {
+    use super::*;
+    use crate::query::Clause;
+    use serde_json::json;
+
+    fn rows(values: Json) -> Vec<Record> {
+        values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_object().unwrap().clone())
+            .collect()
+    }
+
+    fn run(plan: &LocalPlan, spill: Option<Spill>, pushed: Json) -> Json {
+        let mut run = plan.runner(spill);
+        for record in rows(pushed) {
+            run.push(record).unwrap();
+        }
+
+        Json::Array(
+            run.finish()
+                .unwrap()
+                .map(|record| Json::Object(record.unwrap()))
+                .collect(),
+        )
+    }
+
+    fn grouped() -> QueryContent {
+        QueryContent::select(vec![
+            Select::Elements(vec![Expr::column("country")]),
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("age")).alias("average"),
+        ])
+        .from_prefix("bucket", "people/")
+        .where_clause(Clause::IsNotNull(Expr::column("age")))
+        .group_by(vec![Expr::column("country")])
+        .order_by(Expr::column("average"), Order::Desc)
+        .limit(2)
+    }
+
+    #[test]
+    fn pushes_projection_and_filters() {
+        let plan = LocalPlan::new(&grouped()).unwrap().unwrap();
+
+        assert_eq!(
+            plan.pushed.build().unwrap(),
+            "SELECT s.country AS c0, s.age AS c1 FROM S3Object s WHERE s.age IS NOT MISSING"
+        );
+        assert!(LocalPlan::new(&QueryContent::select(vec![]))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn group_by() {
+        let plan = LocalPlan::new(&grouped()).unwrap().unwrap();
+
+        assert_eq!(
+            run(
+                &plan,
+                None,
+                json!([
+                    {"c0": "br", "c1": 30},
+                    {"c0": "in", "c1": 20},
+                    {"c0": "br", "c1": 40},
+                    {"c0": "gh", "c1": 50},
+                    {"c0": "in", "c1": null},
+                ])
+            ),
+            json!([
+                {"country": "gh", "_2": 1, "average": 50.0},
+                {"country": "br", "_2": 2, "average": 35.0},
+            ])
+        );
+    }
+
+    #[test]
+    fn aggregates_without_group_by() {
+        let query = QueryContent::select(vec![Select::Count(Expr::Wildcard)])
+            .from("bucket", "key")
+            .distinct();
+        let plan = LocalPlan::new(&query).unwrap().unwrap();
+
+        assert_eq!(
+            plan.pushed.build().unwrap(),
+            "SELECT true AS c0 FROM S3Object s"
+        );
+        assert_eq!(run(&plan, None, json!([])), json!([{"_1": 0}]));
+        assert_eq!(
+            run(&plan, None, json!([{"c0": true}, {"c0": true}])),
+            json!([{"_1": 2}])
+        );
+    }
+
+    #[test]
+    fn distinct_order_by_expression() {
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+            .from("bucket", "key")
+            .distinct()
+            .order_by(Expr::column("age"), Order::Asc)
+            .order_by(Expr::column("name"), Order::Desc);
+        let plan = LocalPlan::new(&query).unwrap().unwrap();
+
+        assert_eq!(
+            plan.pushed.build().unwrap(),
+            "SELECT s.name AS c0, s.age AS c1 FROM S3Object s"
+        );
+        assert_eq!(
+            run(
+                &plan,
+                None,
+                json!([
+                    {"c0": "b", "c1": 3},
+                    {"c0": "a", "c1": 3},
+                    {"c0": "c"},
+                    {"c0": "b", "c1": 1},
+                ])
+            ),
+            json!([{"name": "c"}, {"name": "b"}, {"name": "a"}])
+        );
+    }
+
+    #[test]
+    fn invalid_plans() {
+        let ungrouped = QueryContent::select(vec![
+            Select::Elements(vec![Expr::column("name")]),
+            Select::Count(Expr::Wildcard),
+        ])
+        .group_by(vec![Expr::column("country")]);
+        let unknown_order = QueryContent::select(vec![Select::Count(Expr::Wildcard)])
+            .group_by(vec![Expr::column("country")])
+            .order_by(Expr::column("age"), Order::Asc);
+
+        assert!(matches!(
+            LocalPlan::new(&ungrouped),
+            Err(QueryError::Invalid(_))
+        ));
+        assert!(matches!(
+            LocalPlan::new(&unknown_order),
+            Err(QueryError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn spilled_sort() {
+        let dir = std::env::temp_dir().join(format!("s3ql-spill-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("id")])])
+            .from("bucket", "key")
+            .order_by(Expr::column("id"), Order::Asc)
+            .limit(5);
+        let plan = LocalPlan::new(&query).unwrap().unwrap();
+        let spill = Spill {
+            dir: dir.clone(),
+            records_in_memory: 2,
+        };
+
+        let pushed = Json::Array((0..9).rev().map(|id| json!({ "c0": id })).collect());
+        assert_eq!(
+            run(&plan, Some(spill), pushed),
+            json!([{"id": 0}, {"id": 1}, {"id": 2}, {"id": 3}, {"id": 4}])
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_order() {
+        let mut values = vec![
+            json!("b"),
+            json!(2.5),
+            json!(null),
+            json!("a"),
+            json!(2),
+            json!(true),
+            json!(-1),
+        ];
+        values.sort_by(compare);
+
+        assert_eq!(
+            Json::Array(values),
+            json!([null, true, -1, 2, 2.5, "a", "b"])
+        );
+    }
+}
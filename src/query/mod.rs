@@ -0,0 +1,1708 @@
+use async_trait::async_trait;
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    GetObjectError, HeadObjectError, ListObjectsV2Error, S3Client, SelectObjectContentError,
+    SelectObjectContentOutput, SelectObjectContentRequest, S3,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::encryption::CustomerKey;
+
+mod aggregate;
+#[cfg(feature = "arrow")]
+mod batches;
+mod emulator;
+mod eval;
+mod expr;
+mod footer;
+mod local;
+mod objects;
+mod params;
+mod path;
+mod schema;
+mod select;
+mod stream;
+#[cfg(feature = "datafusion")]
+mod table;
+mod validate;
+#[cfg(feature = "arrow")]
+pub use batches::{record_batches, ArrowOptions, RecordBatches};
+pub use emulator::SelectEmulator;
+#[cfg(test)]
+use expr::is_decimal;
+use expr::{quoted_identifier, string_literal};
+pub use expr::{CastType, DatePart, Expr, ExtractPart, TrimSide, Value};
+pub use objects::{ObjectRecord, QueryObject, QueryOptions, RecordQueriable, Records};
+pub use params::Params;
+pub use path::Path;
+pub use schema::{Field, FieldType, Schema};
+pub use select::SelectClient;
+pub use stream::Record;
+#[cfg(feature = "datafusion")]
+pub use table::S3Table;
+pub use validate::{ValidationError, MAX_EXPRESSION_BYTES};
+
+/// S3 Select through `rusoto_s3`, which can't run it.
+///
+/// `rusoto_s3` 0.45 sends `SelectObjectContent` but panics reading the response, so
+/// `query_s3_object_content` only validates the query and returns `RusotoError::Validation`
+/// without sending it. `SelectClient` runs the same queries, see `RecordQueriable`.
+#[async_trait]
+pub trait Queriable: S3 {
+    async fn query_s3_object_content(
+        &self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+    ) -> Result<SelectObjectContentOutput, RusotoError<SelectObjectContentError>>;
+}
+
+#[async_trait]
+impl Queriable for S3Client {
+    async fn query_s3_object_content(
+        &self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        _output_serialization: OutputObjectFormat,
+    ) -> Result<SelectObjectContentOutput, RusotoError<SelectObjectContentError>> {
+        query
+            .validate(&input_serialization, &body_compression)
+            .map_err(|e| RusotoError::Validation(e.to_string()))?;
+        if let Some((ObjectTarget::Prefix(_), bucket)) | Some((ObjectTarget::Glob(_), bucket)) =
+            &query.from
+        {
+            return Err(RusotoError::Validation(format!(
+                "query on bucket `{}` targets several objects, use `RecordQueriable::query_s3_objects`",
+                bucket
+            )));
+        }
+
+        Err(RusotoError::Validation(
+            "rusoto_s3 can't read S3 Select responses, use `SelectClient::select_s3_records`"
+                .to_string(),
+        ))
+    }
+}
+
+pub(crate) fn select_request(
+    query: &QueryContent,
+    expression: String,
+    bucket: String,
+    key: String,
+    body_compression: CompressionType,
+    input_serialization: InputObjectFormat,
+    output_serialization: OutputObjectFormat,
+) -> SelectObjectContentRequest {
+    let mut select = SelectObjectContentRequest {
+        key,
+        bucket,
+        expression,
+        expression_type: String::from("SQL"),
+        sse_customer_algorithm: query.customer_key.as_ref().map(CustomerKey::algorithm),
+        sse_customer_key: query.customer_key.as_ref().map(CustomerKey::key),
+        sse_customer_key_md5: query.customer_key.as_ref().map(CustomerKey::key_md5),
+        scan_range: query.scan_range.as_ref().map(ScanRange::to_scan_range),
+        ..SelectObjectContentRequest::default()
+    };
+
+    match output_serialization {
+        OutputObjectFormat::JSON(delimiter) => {
+            select.output_serialization = rusoto_s3::OutputSerialization {
+                csv: None,
+                json: Some(rusoto_s3::JSONOutput {
+                    record_delimiter: delimiter,
+                }),
+            };
+        }
+        OutputObjectFormat::CSV(csv) => {
+            select.output_serialization = rusoto_s3::OutputSerialization {
+                csv: Some(csv.to_output()),
+                json: None,
+            };
+        }
+    };
+
+    match input_serialization {
+        InputObjectFormat::Parquet => {
+            select.input_serialization = rusoto_s3::InputSerialization {
+                csv: None,
+                json: None,
+                parquet: Some(rusoto_s3::ParquetInput {}),
+                compression_type: compression(body_compression),
+            }
+        }
+        InputObjectFormat::CSV(csv) => {
+            select.input_serialization = rusoto_s3::InputSerialization {
+                csv: Some(csv.to_input()),
+                json: None,
+                parquet: None,
+                compression_type: compression(body_compression),
+            }
+        }
+        InputObjectFormat::JSON(t) => {
+            select.input_serialization = rusoto_s3::InputSerialization {
+                csv: None,
+                parquet: None,
+                compression_type: compression(body_compression),
+                json: Some(rusoto_s3::JSONInput {
+                    type_: match t {
+                        JsonType::Document => Some(String::from("Document")),
+                        JsonType::Lines => Some(String::from("Lines")),
+                    },
+                }),
+            }
+        }
+    }
+
+    select
+}
+
+fn compression(compression: CompressionType) -> Option<String> {
+    match compression {
+        CompressionType::BZIP2 => Some("BZIP2".to_string()),
+        CompressionType::GZIP => Some("GZIP".to_string()),
+        CompressionType::NONE => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonType {
+    Document,
+    Lines,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputObjectFormat {
+    JSON(JsonType),
+    CSV(CsvInput),
+    Parquet,
+}
+
+/// How the first line of a CSV object is read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileHeader {
+    Use,    // column names, usable as `s.<name>`
+    Ignore, // skipped, columns are `s._1`, `s._2`, ...
+    None,   // a record like any other
+}
+
+impl FileHeader {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Use => "USE",
+            Self::Ignore => "IGNORE",
+            Self::None => "NONE",
+        }
+    }
+}
+
+/// CSV input, defaults to S3 Select's: no header, `,` between fields, `"` quotes and `\n` records.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvInput {
+    pub(crate) file_header: FileHeader,
+    pub(crate) field_delimiter: char,
+    pub(crate) quote_character: char,
+    pub(crate) quote_escape_character: char,
+    pub(crate) record_delimiter: char,
+    pub(crate) comments: Option<char>,
+}
+
+impl Default for CsvInput {
+    fn default() -> Self {
+        CsvInput {
+            file_header: FileHeader::None,
+            field_delimiter: ',',
+            quote_character: '"',
+            quote_escape_character: '"',
+            record_delimiter: '\n',
+            comments: None,
+        }
+    }
+}
+
+impl CsvInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file_header(mut self, header: FileHeader) -> Self {
+        self.file_header = header;
+        self
+    }
+
+    pub fn field_delimiter(mut self, delimiter: char) -> Self {
+        self.field_delimiter = delimiter;
+        self
+    }
+
+    pub fn quote_character(mut self, quote: char) -> Self {
+        self.quote_character = quote;
+        self
+    }
+
+    /// Escapes a quote inside a quoted field, the quote character itself by default (`""`).
+    pub fn quote_escape_character(mut self, escape: char) -> Self {
+        self.quote_escape_character = escape;
+        self
+    }
+
+    pub fn record_delimiter(mut self, delimiter: char) -> Self {
+        self.record_delimiter = delimiter;
+        self
+    }
+
+    /// Lines starting with `comments` are skipped.
+    pub fn comments(mut self, comments: char) -> Self {
+        self.comments = Some(comments);
+        self
+    }
+
+    fn to_input(&self) -> rusoto_s3::CSVInput {
+        rusoto_s3::CSVInput {
+            allow_quoted_record_delimiter: None,
+            comments: self.comments.map(String::from),
+            field_delimiter: Some(self.field_delimiter.to_string()),
+            file_header_info: Some(self.file_header.as_str().to_string()),
+            quote_character: Some(self.quote_character.to_string()),
+            quote_escape_character: Some(self.quote_escape_character.to_string()),
+            record_delimiter: Some(self.record_delimiter.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompressionType {
+    NONE,
+    GZIP,
+    BZIP2,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputObjectFormat {
+    JSON(Option<String>),
+    CSV(CsvOutput),
+}
+
+/// Which CSV output fields are quoted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuoteFields {
+    Always,
+    AsNeeded,
+}
+
+impl QuoteFields {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Always => "ALWAYS",
+            Self::AsNeeded => "ASNEEDED",
+        }
+    }
+}
+
+/// CSV output, defaults to S3 Select's: `,` between fields, `"` quotes when needed and `\n` records.
+///
+/// Rows don't name their fields, records read from them are keyed by the names JSON output would
+/// use, or `_1`, `_2`, ... from the first `*` on. Every field is a string and empty ones are null.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvOutput {
+    pub(crate) quote_fields: QuoteFields,
+    pub(crate) field_delimiter: char,
+    pub(crate) quote_character: char,
+    pub(crate) quote_escape_character: char,
+    pub(crate) record_delimiter: char,
+}
+
+impl Default for CsvOutput {
+    fn default() -> Self {
+        CsvOutput {
+            quote_fields: QuoteFields::AsNeeded,
+            field_delimiter: ',',
+            quote_character: '"',
+            quote_escape_character: '"',
+            record_delimiter: '\n',
+        }
+    }
+}
+
+impl CsvOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn quote_fields(mut self, quote_fields: QuoteFields) -> Self {
+        self.quote_fields = quote_fields;
+        self
+    }
+
+    pub fn field_delimiter(mut self, delimiter: char) -> Self {
+        self.field_delimiter = delimiter;
+        self
+    }
+
+    pub fn quote_character(mut self, quote: char) -> Self {
+        self.quote_character = quote;
+        self
+    }
+
+    /// Escapes a quote inside a quoted field, the quote character itself by default (`""`).
+    pub fn quote_escape_character(mut self, escape: char) -> Self {
+        self.quote_escape_character = escape;
+        self
+    }
+
+    pub fn record_delimiter(mut self, delimiter: char) -> Self {
+        self.record_delimiter = delimiter;
+        self
+    }
+
+    fn to_output(&self) -> rusoto_s3::CSVOutput {
+        rusoto_s3::CSVOutput {
+            field_delimiter: Some(self.field_delimiter.to_string()),
+            quote_character: Some(self.quote_character.to_string()),
+            quote_escape_character: Some(self.quote_escape_character.to_string()),
+            quote_fields: Some(self.quote_fields.as_str().to_string()),
+            record_delimiter: Some(self.record_delimiter.to_string()),
+        }
+    }
+}
+
+/// Error for queries consumed record by record.
+#[derive(Debug)]
+pub enum QueryError {
+    /// The query can't be sent as built.
+    Invalid(String),
+    Select(Box<RusotoError<SelectObjectContentError>>),
+    List(Box<RusotoError<ListObjectsV2Error>>),
+    Head(Box<RusotoError<HeadObjectError>>),
+    Get(Box<RusotoError<GetObjectError>>),
+    /// Error event S3 sent in the middle of the results.
+    Failed {
+        code: String,
+        message: String,
+    },
+    /// The event stream or the records in it are malformed.
+    Decode(String),
+    /// A local sort couldn't write or read its spilled records.
+    Io(std::io::Error),
+    /// A record doesn't fit the Arrow schema, `offset` counts the records before it.
+    Convert {
+        offset: usize,
+        message: String,
+    },
+}
+
+// S3 Select error codes for objects that don't match the requested format or compression
+const MISMATCH_CODES: [&str; 9] = [
+    "CSVParsingError",
+    "InvalidCompressionFormat",
+    "InvalidJsonType",
+    "InvalidTextEncoding",
+    "JSONParsingError",
+    "ParquetParsingError",
+    "TruncatedInput",
+    "UnsupportedParquetType",
+    "ObjectSerializationConflict",
+];
+
+impl QueryError {
+    /// Error code sent by S3, either in the error response or as an error event.
+    pub fn code(&self) -> Option<String> {
+        match self {
+            Self::Failed { code, .. } => Some(code.clone()),
+            Self::Select(e) => {
+                let response = match e.as_ref() {
+                    RusotoError::Unknown(response) => response,
+                    _ => return None,
+                };
+                let body = String::from_utf8_lossy(&response.body);
+                let start = body.find("<Code>")? + "<Code>".len();
+                let end = start + body[start..].find("</Code>")?;
+                Some(body[start..end].to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// The object couldn't be read with the requested format or compression.
+    pub fn is_format_mismatch(&self) -> bool {
+        self.code()
+            .is_some_and(|code| MISMATCH_CODES.contains(&code.as_str()))
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => write!(f, "invalid query: {}", message),
+            Self::Select(e) => write!(f, "{}", e),
+            Self::List(e) => write!(f, "{}", e),
+            Self::Head(e) => write!(f, "{}", e),
+            Self::Get(e) => write!(f, "{}", e),
+            Self::Failed { code, message } => write!(f, "{}: {}", code, message),
+            Self::Decode(message) => write!(f, "malformed select output: {}", message),
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Convert { offset, message } => {
+                write!(f, "record {} can't be converted: {}", offset, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Objects a query reads from.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) enum ObjectTarget {
+    Key(String),
+    Prefix(String),
+    Glob(String), // e.g. `logs/2020-*/*.json`, `*` doesn't match `/`
+}
+
+/// Bytes of an uncompressed CSV or JSON Lines object a query scans, offsets start at 0.
+///
+/// Records are read when their first byte is in the range, even if they end after it.
+/// `RecordQueriable::s3_object_range` reads the bytes of any object with it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ScanRange {
+    Between(u64, u64), // first and last byte, both included
+    From(u64),         // to the end of the object
+    Last(u64),         // last `n` bytes
+}
+
+impl ScanRange {
+    fn to_scan_range(&self) -> rusoto_s3::ScanRange {
+        let (start, end) = match self {
+            Self::Between(start, end) => (Some(*start), Some(*end)),
+            Self::From(start) => (Some(*start), None),
+            Self::Last(n) => (None, Some(*n)),
+        };
+
+        rusoto_s3::ScanRange {
+            start: start.map(|start| start as i64),
+            end: end.map(|end| end as i64),
+        }
+    }
+
+    /// HTTP `Range` header of a GET reading the same bytes.
+    pub(crate) fn to_header(&self) -> String {
+        match self {
+            Self::Between(start, end) => format!("bytes={}-{}", start, end),
+            Self::From(start) => format!("bytes={}-", start),
+            Self::Last(n) => format!("bytes=-{}", n),
+        }
+    }
+
+    /// First and last byte scanned in an object of `size` bytes.
+    pub(crate) fn bounds(&self, size: u64) -> (u64, u64) {
+        let last = size.saturating_sub(1);
+        match self {
+            Self::Between(start, end) => (*start, (*end).min(last)),
+            Self::From(start) => (*start, last),
+            Self::Last(n) => (size.saturating_sub(*n), last),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub enum Select {
+    Elements(Vec<Expr>),
+    Count(Expr), // `Expr::Wildcard` counts every record
+    Avg(Expr),
+    Max(Expr),
+    Min(Expr),
+    Sum(Expr),
+    As(Box<Select>, String), // a single element or aggregate, named `String` in the output
+}
+
+impl Select {
+    // every expression of the element or aggregate
+    pub(crate) fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        match self {
+            Self::Elements(exprs) => exprs.iter().for_each(|e| e.visit(f)),
+            Self::Count(e) | Self::Avg(e) | Self::Max(e) | Self::Min(e) | Self::Sum(e) => {
+                e.visit(f)
+            }
+            Self::As(select, _) => select.visit(f),
+        }
+    }
+
+    pub(crate) fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        match self {
+            Self::Elements(exprs) => exprs.iter_mut().for_each(|e| e.visit_mut(f)),
+            Self::Count(e) | Self::Avg(e) | Self::Max(e) | Self::Min(e) | Self::Sum(e) => {
+                e.visit_mut(f)
+            }
+            Self::As(select, _) => select.visit_mut(f),
+        }
+    }
+
+    /// Names the output field, e.g. `Select::Sum(price * qty).alias("total")`.
+    pub fn alias(self, name: &str) -> Self {
+        Select::As(Box::new(self), name.to_string())
+    }
+
+    fn to_sql(&self) -> String {
+        match self {
+            Self::Count(el) => build_select_element("Count", el),
+            Self::Avg(el) => build_select_element("Avg", el),
+            Self::Max(el) => build_select_element("Max", el),
+            Self::Min(el) => build_select_element("Min", el),
+            Self::Sum(el) => build_select_element("Sum", el),
+            Self::Elements(els) => build_list(els),
+            Self::As(select, name) => format!("{} AS {}", select.to_sql(), quoted_identifier(name)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Clause {
+    G(Expr, Expr),
+    L(Expr, Expr),
+    E(Expr, Expr),
+    GE(Expr, Expr),
+    LE(Expr, Expr),
+    NotE(Expr, Expr),
+    IsNotNull(Expr),
+    IsNull(Expr),
+    Between(Expr, Expr, Expr),
+    In(Expr, Vec<Expr>),
+    NotBetween(Expr, Expr, Expr),
+    NotIn(Expr, Vec<Expr>),
+    Like(Expr, Expr, Option<char>), // expression, pattern with `%` and `_`, escape character
+    And(Box<Clause>, Box<Clause>),
+    Or(Box<Clause>, Box<Clause>),
+    Not(Box<Clause>),
+}
+
+// escape character used by `Clause::starts_with` and `Clause::contains`
+const LIKE_ESCAPE: char = '\\';
+
+impl Clause {
+    pub fn and(self, other: Clause) -> Self {
+        Clause::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Clause) -> Self {
+        Clause::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Joins every clause with AND, `None` when `clauses` is empty.
+    pub fn all(clauses: Vec<Clause>) -> Option<Self> {
+        clauses.into_iter().reduce(Clause::and)
+    }
+
+    /// Joins every clause with OR, `None` when `clauses` is empty.
+    pub fn any(clauses: Vec<Clause>) -> Option<Self> {
+        clauses.into_iter().reduce(Clause::or)
+    }
+
+    /// `LIKE` matching `prefix` literally, `%` and `_` in it are escaped.
+    pub fn starts_with(expr: Expr, prefix: &str) -> Self {
+        Clause::Like(
+            expr,
+            format!("{}%", escape_like(prefix)).into(),
+            Some(LIKE_ESCAPE),
+        )
+    }
+
+    /// `LIKE` matching `substring` literally anywhere, `%` and `_` in it are escaped.
+    pub fn contains(expr: Expr, substring: &str) -> Self {
+        Clause::Like(
+            expr,
+            format!("%{}%", escape_like(substring)).into(),
+            Some(LIKE_ESCAPE),
+        )
+    }
+
+    /// Calls `f` on every expression in the clause and in the expressions nested in them.
+    pub(crate) fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        match self {
+            Self::G(a, b)
+            | Self::L(a, b)
+            | Self::E(a, b)
+            | Self::GE(a, b)
+            | Self::LE(a, b)
+            | Self::NotE(a, b)
+            | Self::Like(a, b, _) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Self::IsNotNull(a) | Self::IsNull(a) => a.visit(f),
+            Self::Between(a, start, end) | Self::NotBetween(a, start, end) => {
+                a.visit(f);
+                start.visit(f);
+                end.visit(f);
+            }
+            Self::In(a, values) | Self::NotIn(a, values) => {
+                a.visit(f);
+                values.iter().for_each(|value| value.visit(f));
+            }
+            Self::And(a, b) | Self::Or(a, b) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Self::Not(clause) => clause.visit(f),
+        }
+    }
+
+    pub(crate) fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        match self {
+            Self::G(a, b)
+            | Self::L(a, b)
+            | Self::E(a, b)
+            | Self::GE(a, b)
+            | Self::LE(a, b)
+            | Self::NotE(a, b)
+            | Self::Like(a, b, _) => {
+                a.visit_mut(f);
+                b.visit_mut(f);
+            }
+            Self::IsNotNull(a) | Self::IsNull(a) => a.visit_mut(f),
+            Self::Between(a, start, end) | Self::NotBetween(a, start, end) => {
+                a.visit_mut(f);
+                start.visit_mut(f);
+                end.visit_mut(f);
+            }
+            Self::In(a, values) | Self::NotIn(a, values) => {
+                a.visit_mut(f);
+                values.iter_mut().for_each(|value| value.visit_mut(f));
+            }
+            Self::And(a, b) | Self::Or(a, b) => {
+                a.visit_mut(f);
+                b.visit_mut(f);
+            }
+            Self::Not(clause) => clause.visit_mut(f),
+        }
+    }
+
+    // nested AND/OR are grouped unless they use the same operator as their parent
+    fn to_grouped_where(&self, parent: &Clause) -> String {
+        match (self, parent) {
+            (Self::And(..), Self::And(..)) | (Self::Or(..), Self::Or(..)) => {
+                self.clone().to_where()
+            }
+            (Self::And(..), _) | (Self::Or(..), _) => format!("({})", self.clone().to_where()),
+            _ => self.clone().to_where(),
+        }
+    }
+
+    fn to_where(self) -> String {
+        match &self {
+            Self::G(a, b) => format!("{} > {}", a.to_sql(), b.to_sql()),
+            Self::GE(a, b) => format!("{} >= {}", a.to_sql(), b.to_sql()),
+            Self::L(a, b) => format!("{} < {}", a.to_sql(), b.to_sql()),
+            Self::LE(a, b) => format!("{} <= {}", a.to_sql(), b.to_sql()),
+            // `= NULL` is never true, SQL needs `IS NULL`
+            Self::E(a, Expr::Literal(Value::Null)) => format!("{} IS NULL", a.to_sql()),
+            Self::NotE(a, Expr::Literal(Value::Null)) => format!("{} IS NOT NULL", a.to_sql()),
+            Self::E(a, b) => format!("{} = {}", a.to_sql(), b.to_sql()),
+            Self::NotE(a, b) => format!("{} != {}", a.to_sql(), b.to_sql()),
+            Self::IsNotNull(a) => format!("{} IS NOT MISSING", a.to_sql()),
+            Self::IsNull(a) => format!("{} IS MISSING", a.to_sql()),
+            Self::In(a, v) => format!("{} IN ({})", a.to_sql(), build_list(v)),
+            Self::NotIn(a, v) => format!("{} NOT IN ({})", a.to_sql(), build_list(v)),
+            Self::Between(a, start, end) => format!(
+                "{} BETWEEN {} AND {}",
+                a.to_sql(),
+                start.to_sql(),
+                end.to_sql()
+            ),
+            Self::NotBetween(a, start, end) => format!(
+                "{} NOT BETWEEN {} AND {}",
+                a.to_sql(),
+                start.to_sql(),
+                end.to_sql()
+            ),
+            Self::Like(a, pattern, None) => format!("{} LIKE {}", a.to_sql(), pattern.to_sql()),
+            Self::Like(a, pattern, Some(escape)) => format!(
+                "{} LIKE {} ESCAPE {}",
+                a.to_sql(),
+                pattern.to_sql(),
+                string_literal(&escape.to_string())
+            ),
+            Self::And(clause1, clause2) => format!(
+                "{} AND {}",
+                clause1.to_grouped_where(&self),
+                clause2.to_grouped_where(&self)
+            ),
+            Self::Or(clause1, clause2) => format!(
+                "{} OR {}",
+                clause1.to_grouped_where(&self),
+                clause2.to_grouped_where(&self)
+            ),
+            Self::Not(clause) => format!("NOT {}", clause.to_grouped_where(&self)),
+        }
+    }
+}
+
+fn build_list(exprs: &[Expr]) -> String {
+    exprs
+        .iter()
+        .map(Expr::to_sql)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn escape_like(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        if c == '%' || c == '_' || c == LIKE_ESCAPE {
+            escaped.push(LIKE_ESCAPE);
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+impl std::ops::Not for Clause {
+    type Output = Clause;
+
+    fn not(self) -> Self::Output {
+        Clause::Not(Box::new(self))
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct QueryContent {
+    select: Vec<Select>,                  // elements, count, avg, max, min, sum
+    from: Option<(ObjectTarget, String)>, // objects, bucket
+    path: Option<Vec<Path>>,              // S3Object[*].path
+    // By name (in an object): .name or ['name']
+    // By index (in an array): [index]
+    // By wildcard (in an object): .*
+    // By wildcard (in an array): [*]
+    clauses: Option<Clause>, // >, <, =, "id IS NOT MISSING", between, in, !=, AND, OR, >=, <=
+    limit: Option<usize>,
+    #[serde(skip)]
+    customer_key: Option<CustomerKey>, // SSE-C key the object was written with, never serialized
+    scan_range: Option<ScanRange>,
+    // run locally, S3 Select has no GROUP BY, ORDER BY or DISTINCT
+    group_by: Vec<Expr>,
+    order_by: Vec<(Expr, Order)>,
+    distinct: bool,
+}
+
+impl QueryContent {
+    pub fn select(elements: Vec<Select>) -> QueryContent {
+        QueryContent {
+            select: elements,
+            from: None,
+            path: None,
+            clauses: None,
+            limit: None,
+            customer_key: None,
+            scan_range: None,
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            distinct: false,
+        }
+    }
+
+    pub fn from(mut self, bucket: &str, key: &str) -> Self {
+        self.from = Some((ObjectTarget::Key(key.to_string()), bucket.to_string()));
+        self
+    }
+
+    /// Queries every object whose key starts with `prefix`.
+    pub fn from_prefix(mut self, bucket: &str, prefix: &str) -> Self {
+        self.from = Some((ObjectTarget::Prefix(prefix.to_string()), bucket.to_string()));
+        self
+    }
+
+    /// Queries every object whose key matches the glob `pattern`.
+    pub fn from_glob(mut self, bucket: &str, pattern: &str) -> Self {
+        self.from = Some((ObjectTarget::Glob(pattern.to_string()), bucket.to_string()));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn from_path(mut self, path: Vec<Path>) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    pub fn where_clause(mut self, clauses: Clause) -> Self {
+        self.clauses = Some(clauses);
+        self
+    }
+
+    pub fn customer_key(mut self, key: CustomerKey) -> Self {
+        self.customer_key = Some(key);
+        self
+    }
+
+    /// Scans only `range` of the object, see `QueryOptions::scan_ranges` to split objects
+    /// into ranges queried concurrently.
+    pub fn scan_range(mut self, range: ScanRange) -> Self {
+        self.scan_range = Some(range);
+        self
+    }
+
+    /// Groups the records by `keys`, plain columns in the projection must be one of them.
+    pub fn group_by(mut self, keys: Vec<Expr>) -> Self {
+        self.group_by = keys;
+        self
+    }
+
+    /// Sorts by `expr`, an output field name or an expression, each call adds a sort key.
+    pub fn order_by(mut self, expr: Expr, order: Order) -> Self {
+        self.order_by.push((expr, order));
+        self
+    }
+
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    // every expression of the select, where clause, GROUP BY and ORDER BY
+    fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        self.select.iter().for_each(|select| select.visit(f));
+        if let Some(clause) = &self.clauses {
+            clause.visit(f);
+        }
+        self.group_by.iter().for_each(|e| e.visit(f));
+        self.order_by.iter().for_each(|(e, _)| e.visit(f));
+    }
+
+    fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        self.select
+            .iter_mut()
+            .for_each(|select| select.visit_mut(f));
+        if let Some(clause) = &mut self.clauses {
+            clause.visit_mut(f);
+        }
+        self.group_by.iter_mut().for_each(|e| e.visit_mut(f));
+        self.order_by.iter_mut().for_each(|(e, _)| e.visit_mut(f));
+    }
+
+    fn build(&self) -> Result<String, String> {
+        if self.from.is_none() {
+            return Err(ValidationError::MissingFrom.to_string());
+        }
+        if !self.group_by.is_empty() || !self.order_by.is_empty() || self.distinct {
+            return Err(
+                "GROUP BY, ORDER BY and DISTINCT run locally, use `RecordQueriable::query_s3_records`"
+                    .to_string(),
+            );
+        }
+
+        Ok(self.sql())
+    }
+
+    // SQL S3 Select runs, without the local operations
+    fn sql(&self) -> String {
+        let mut query = String::from("SELECT ");
+        query = query + &build_select(self.select.clone());
+        if self.path.is_none() {
+            query = query + " FROM S3Object s";
+        } else {
+            query = query + &build_path(self.path.clone().unwrap());
+        }
+
+        if self.clauses.is_some() {
+            query = query + &build_where(self.clauses.clone().unwrap());
+        }
+
+        if self.limit.is_some() {
+            query = query + " LIMIT " + &self.limit.unwrap().to_string();
+        }
+        query
+    }
+}
+
+fn build_select(elements: Vec<Select>) -> String {
+    elements
+        .iter()
+        .map(Select::to_sql)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn build_select_element(function: &str, el: &Expr) -> String {
+    format!("{}({})", function, el.to_sql())
+}
+
+fn build_path(path: Vec<Path>) -> String {
+    format!(" FROM S3Object{} s", Path::to_sql(&path))
+}
+
+fn build_where(clause: Clause) -> String {
+    String::from(" WHERE ") + &clause.to_where()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_select() {
+        let elements = vec![
+            Select::Elements(vec![
+                Expr::column("id"),
+                Expr::column("name"),
+                Expr::column("age"),
+            ]),
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("age")),
+        ];
+
+        let query = QueryContent::select(elements)
+            .from("bucket", "key")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s"
+        );
+    }
+
+    #[test]
+    fn build_limit() {
+        let elements = vec![
+            Select::Elements(vec![
+                Expr::column("id"),
+                Expr::column("name"),
+                Expr::column("age"),
+            ]),
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("age")),
+        ];
+
+        let query = QueryContent::select(elements)
+            .from("bucket", "key")
+            .limit(5)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s LIMIT 5"
+        );
+    }
+
+    #[test]
+    fn build_path() {
+        let elements = vec![
+            Select::Elements(vec![
+                Expr::column("id"),
+                Expr::column("name"),
+                Expr::column("age"),
+            ]),
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("age")),
+        ];
+
+        let query = QueryContent::select(elements)
+            .from("bucket", "key")
+            .from_path(vec![
+                Path::WildCardName,
+                Path::Index(5),
+                Path::Name("Rules".to_string()),
+                Path::WildCardIndex,
+            ])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object.*[5].Rules[*] s"
+        );
+    }
+}
+
+#[cfg(test)]
+mod path_test {
+    use super::*;
+
+    #[test]
+    fn nested_columns_and_paths() {
+        let query = QueryContent::select(vec![Select::Elements(vec![
+            Expr::column("address.city"),
+            Expr::column("tags[0]"),
+            Expr::path(vec![Path::Name("a.b".to_string()), Path::Index(1)]),
+        ])])
+        .from("bucket", "key")
+        .from_path(vec![
+            Path::Name("order items".to_string()),
+            Path::WildCardIndex,
+        ])
+        .where_clause(Clause::E(Expr::column("address.zip-code"), "90210".into()))
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s.address.city, s.tags[0], s['a.b'][1] FROM S3Object['order items'][*] s WHERE s.address['zip-code'] = '90210'"
+        );
+    }
+}
+
+#[cfg(test)]
+mod where_test {
+    use super::*;
+
+    fn select() -> QueryContent {
+        let elements = vec![
+            Select::Elements(vec![
+                Expr::column("id"),
+                Expr::column("name"),
+                Expr::column("age"),
+            ]),
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("age")),
+        ];
+
+        QueryContent::select(elements).from("bucket", "key")
+    }
+
+    #[test]
+    fn and_greater() {
+        let query = select();
+        let query_str = query
+            .where_clause(Clause::And(
+                Box::new(Clause::GE(Expr::column("id"), 300.into())),
+                Box::new(Clause::G(Expr::column("age"), 4.into())),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(query_str, "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s WHERE s.id >= 300 AND s.age > 4");
+    }
+
+    #[test]
+    fn or_lesser() {
+        let query = select();
+        let query_str = query
+            .where_clause(Clause::Or(
+                Box::new(Clause::LE(Expr::column("id"), 300.into())),
+                Box::new(Clause::L(Expr::column("age"), 4.into())),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(query_str, "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s WHERE s.id <= 300 OR s.age < 4");
+    }
+
+    #[test]
+    fn and_equal() {
+        let query = select();
+        let query_str = query
+            .where_clause(Clause::And(
+                Box::new(Clause::E(Expr::column("id"), "74927".into())),
+                Box::new(Clause::NotE(Expr::column("name"), "test".into())),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(query_str, "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s WHERE s.id = '74927' AND s.name != 'test'");
+    }
+
+    #[test]
+    fn or_missing() {
+        let query = select();
+        let query_str = query
+            .where_clause(Clause::Or(
+                Box::new(Clause::IsNotNull(Expr::column("id"))),
+                Box::new(Clause::IsNull(Expr::column("name"))),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(query_str, "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s WHERE s.id IS NOT MISSING OR s.name IS MISSING");
+    }
+
+    #[test]
+    fn and_in() {
+        let query = select();
+        let query_str = query
+            .where_clause(Clause::And(
+                Box::new(Clause::In(
+                    Expr::column("name"),
+                    vec!["julia".into(), "naomi".into()],
+                )),
+                Box::new(Clause::NotIn(
+                    Expr::column("id"),
+                    vec![432904.into(), "90jd243".into()],
+                )),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(query_str, "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s WHERE s.name IN ('julia', 'naomi') AND s.id NOT IN (432904, '90jd243')");
+    }
+
+    #[test]
+    fn betweens() {
+        let query = select();
+        let query_str = query
+            .where_clause(Clause::And(
+                Box::new(Clause::Between(Expr::column("age"), 25.into(), 35.into())),
+                Box::new(Clause::NotBetween(
+                    Expr::column("id"),
+                    300.into(),
+                    500.into(),
+                )),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(query_str, "SELECT s.id, s.name, s.age, Count(*), Avg(s.age) FROM S3Object s WHERE s.age BETWEEN 25 AND 35 AND s.id NOT BETWEEN 300 AND 500");
+
+        let negative = Clause::NotBetween(Expr::column("id"), (-300).into(), (-1).into());
+        assert_eq!(negative.to_where(), "s.id NOT BETWEEN -300 AND -1");
+    }
+}
+
+#[cfg(test)]
+mod like_test {
+    use super::*;
+
+    #[test]
+    fn like_with_escape() {
+        let clause = Clause::Like(Expr::column("path"), "logs/%!_%".into(), Some('!'));
+
+        assert_eq!(clause.to_where(), "s.path LIKE 'logs/%!_%' ESCAPE '!'");
+        assert_eq!(
+            Clause::Like(Expr::column("path"), "%.json".into(), None).to_where(),
+            "s.path LIKE '%.json'"
+        );
+    }
+
+    #[test]
+    fn prefix_and_substring() {
+        assert_eq!(
+            Clause::starts_with(Expr::column("severity"), "ERR").to_where(),
+            "s.severity LIKE 'ERR%' ESCAPE '\\'"
+        );
+        assert_eq!(
+            Clause::contains(Expr::column("message").lower(), "100%_done\\").to_where(),
+            "LOWER(s.message) LIKE '%100\\%\\_done\\\\%' ESCAPE '\\'"
+        );
+    }
+
+    #[test]
+    fn string_functions_in_select_and_where() {
+        let query = QueryContent::select(vec![Select::Elements(vec![
+            Expr::column("id"),
+            Expr::column("first_name")
+                .concat(" ")
+                .concat(Expr::column("last_name"))
+                .upper(),
+        ])])
+        .from("bucket", "key")
+        .where_clause(
+            Clause::G(Expr::column("name").trim().char_length(), 3.into()).and(
+                Clause::starts_with(Expr::column("code").substring(1, Some(2.into())), "BR"),
+            ),
+        )
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s.id, UPPER(s.first_name || ' ' || s.last_name) FROM S3Object s WHERE CHAR_LENGTH(TRIM(s.name)) > 3 AND SUBSTRING(s.code FROM 1 FOR 2) LIKE 'BR%' ESCAPE '\\'"
+        );
+    }
+}
+
+#[cfg(test)]
+mod alias_test {
+    use super::*;
+
+    #[test]
+    fn aliases_and_arithmetic() {
+        let price = || Expr::column("price");
+        let qty = || Expr::column("qty");
+
+        let query = QueryContent::select(vec![
+            Select::Elements(vec![Expr::column("id")]).alias("order id"),
+            Select::Elements(vec![-price()]).alias("refund"),
+            Select::Sum(price() * qty()).alias("total"),
+            Select::Count(Expr::Wildcard),
+        ])
+        .from("bucket", "key")
+        .where_clause(Clause::G(price() * qty() - 10, 0.into()))
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s.id AS \"order id\", -s.price AS refund, Sum(s.price * s.qty) AS total, Count(*) FROM S3Object s WHERE s.price * s.qty - 10 > 0"
+        );
+    }
+}
+
+#[cfg(test)]
+mod value_test {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn where_of(clause: Clause) -> String {
+        clause.to_where()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            where_of(Clause::E(Expr::column("n"), (-7).into())),
+            "s.n = -7"
+        );
+        assert_eq!(
+            where_of(Clause::G(Expr::column("n"), 1.5.into())),
+            "s.n > 1.5e0"
+        );
+        assert_eq!(
+            where_of(Clause::L(Expr::column("n"), 100.0.into())),
+            "s.n < 1e2"
+        );
+        assert_eq!(
+            where_of(Clause::GE(Expr::column("n"), f64::NEG_INFINITY.into())),
+            "s.n >= CAST('-inf' AS FLOAT)"
+        );
+        assert_eq!(
+            where_of(Clause::LE(
+                Expr::column("price"),
+                Value::Decimal("-19.99".to_string()).into()
+            )),
+            "s.price <= -19.99"
+        );
+        assert_eq!(
+            where_of(Clause::LE(
+                Expr::column("price"),
+                Value::Decimal("1e3".to_string()).into()
+            )),
+            "s.price <= CAST('1e3' AS DECIMAL)"
+        );
+    }
+
+    #[test]
+    fn bools_timestamps_and_nulls() {
+        let since = Utc.with_ymd_and_hms(2021, 3, 4, 5, 6, 7).unwrap();
+
+        assert_eq!(
+            where_of(Clause::E(Expr::column("active"), true.into())),
+            "s.active = true"
+        );
+        assert_eq!(
+            where_of(Clause::Between(
+                Expr::column("created"),
+                since.into(),
+                Value::Timestamp(since + chrono::Duration::milliseconds(1500)).into()
+            )),
+            "s.created BETWEEN `2021-03-04T05:06:07Z` AND `2021-03-04T05:06:08.500Z`"
+        );
+        assert_eq!(
+            where_of(Clause::E(Expr::column("deleted"), Value::Null.into())),
+            "s.deleted IS NULL"
+        );
+        assert_eq!(
+            where_of(Clause::NotE(Expr::column("deleted"), None::<bool>.into())),
+            "s.deleted IS NOT NULL"
+        );
+        assert_eq!(
+            where_of(Clause::In(
+                Expr::column("code"),
+                vec![1.into(), "one".into(), false.into(), Value::Null.into()]
+            )),
+            "s.code IN (1, 'one', false, NULL)"
+        );
+    }
+}
+
+#[cfg(test)]
+mod escape_test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Name(String), // bare word or double quoted identifier, unescaped
+        Str(String),  // single quoted literal, unescaped
+        Punct(char),
+    }
+
+    fn name(name: &str) -> Token {
+        Token::Name(name.to_string())
+    }
+
+    fn lex(sql: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut chars = sql.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' => (),
+                '\'' | '"' => {
+                    let mut text = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(q) if q == c && chars.peek() == Some(&c) => {
+                                chars.next();
+                                text.push(c);
+                            }
+                            Some(q) if q == c => break,
+                            Some(other) => text.push(other),
+                            None => panic!("unterminated {} in `{}`", c, sql),
+                        }
+                    }
+                    tokens.push(if c == '"' {
+                        Token::Name(text)
+                    } else {
+                        Token::Str(text)
+                    });
+                }
+                c if c.is_ascii_alphanumeric() || c == '_' => {
+                    let mut text = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if !(next.is_ascii_alphanumeric() || next == '_') {
+                            break;
+                        }
+                        text.push(next);
+                        chars.next();
+                    }
+                    tokens.push(Token::Name(text));
+                }
+                c => tokens.push(Token::Punct(c)),
+            }
+        }
+
+        tokens
+    }
+
+    // `s.name`, or `s['name']` when the name isn't a plain identifier
+    fn column_tokens(field: &str) -> Vec<Token> {
+        if path::is_identifier(field) {
+            vec![name("s"), Token::Punct('.'), name(field)]
+        } else {
+            vec![
+                name("s"),
+                Token::Punct('['),
+                Token::Str(field.to_string()),
+                Token::Punct(']'),
+            ]
+        }
+    }
+
+    #[test]
+    fn quotes_strings_and_identifiers() {
+        let query = QueryContent::select(vec![
+            Select::Elements(vec![Expr::column("first name"), Expr::column("user.id")]),
+            Select::Max(Expr::column("items[0].price")),
+        ])
+        .from("bucket", "key")
+        .where_clause(Clause::E(
+            Expr::column("last-name"),
+            "O'Brien' OR 1=1 --".into(),
+        ))
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT s['first name'], s['user'].id, Max(s.items[0].price) FROM S3Object s WHERE s['last-name'] = 'O''Brien'' OR 1=1 --'"
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(quoted_identifier("total"), "total");
+        assert_eq!(quoted_identifier("count"), "\"count\"");
+        assert_eq!(quoted_identifier("a[1]"), "\"a[1]\"");
+        assert_eq!(quoted_identifier("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    proptest! {
+        #[test]
+        fn string_literals_roundtrip(value in any::<String>()) {
+            prop_assert_eq!(lex(&string_literal(&value)), vec![Token::Str(value)]);
+        }
+
+        #[test]
+        fn equality_stays_one_comparison(field in any::<String>(), value in any::<String>()) {
+            let column = || Expr::path(vec![Path::Name(field.clone())]);
+            let query = QueryContent::select(vec![Select::Elements(vec![column()])])
+                .from("bucket", "key")
+                .where_clause(Clause::E(column(), value.clone().into()))
+                .build()
+                .unwrap();
+
+            let mut expected = vec![name("SELECT")];
+            expected.extend(column_tokens(&field));
+            expected.extend(vec![name("FROM"), name("S3Object"), name("s"), name("WHERE")]);
+            expected.extend(column_tokens(&field));
+            expected.extend(vec![Token::Punct('='), Token::Str(value)]);
+
+            prop_assert_eq!(lex(&query), expected);
+        }
+
+        #[test]
+        fn betweens_stay_one_comparison(
+            field in any::<String>(),
+            low in any::<String>(),
+            high in any::<String>(),
+            negated in any::<bool>(),
+        ) {
+            let column = Expr::path(vec![Path::Name(field.clone())]);
+            let clause = if negated {
+                Clause::NotBetween(column, low.clone().into(), high.clone().into())
+            } else {
+                Clause::Between(column, low.clone().into(), high.clone().into())
+            };
+
+            let mut expected = column_tokens(&field);
+            if negated {
+                expected.push(name("NOT"));
+            }
+            expected.extend(vec![name("BETWEEN"), Token::Str(low), name("AND"), Token::Str(high)]);
+
+            prop_assert_eq!(lex(&clause.to_where()), expected);
+        }
+
+        #[test]
+        fn in_lists_keep_every_value(values in prop::collection::vec(any::<String>(), 1..5)) {
+            let clause = Clause::In(
+                Expr::column("tag"),
+                values.iter().cloned().map(Expr::from).collect(),
+            );
+            let literals = lex(&clause.to_where())
+                .into_iter()
+                .filter(|token| matches!(token, Token::Str(_)))
+                .collect::<Vec<Token>>();
+
+            prop_assert_eq!(literals, values.into_iter().map(Token::Str).collect::<Vec<Token>>());
+        }
+
+        #[test]
+        fn decimals_are_numbers_or_casts(decimal in any::<String>()) {
+            let tokens = lex(&Value::Decimal(decimal.clone()).to_sql());
+
+            if is_decimal(&decimal) {
+                prop_assert!(tokens.iter().all(|token| !matches!(token, Token::Str(_))));
+            } else {
+                prop_assert_eq!(
+                    tokens,
+                    vec![
+                        name("CAST"),
+                        Token::Punct('('),
+                        Token::Str(decimal),
+                        name("AS"),
+                        name("DECIMAL"),
+                        Token::Punct(')'),
+                    ]
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod grouping_test {
+    use super::*;
+
+    // atoms are `s.<name> IS MISSING`, so a clause can be evaluated for any set of missing names
+    fn atom(name: &str) -> Clause {
+        Clause::IsNull(Expr::column(name))
+    }
+
+    fn eval(clause: &Clause, missing: &[&str]) -> bool {
+        match clause {
+            Clause::IsNull(Expr::Column(path)) => missing.contains(&&Path::to_sql(path)[1..]),
+            Clause::And(a, b) => eval(a, missing) && eval(b, missing),
+            Clause::Or(a, b) => eval(a, missing) || eval(b, missing),
+            Clause::Not(a) => !eval(a, missing),
+            _ => unreachable!(),
+        }
+    }
+
+    // evaluates the generated SQL with SQL precedence: NOT, then AND, then OR
+    struct Sql<'a> {
+        tokens: Vec<String>,
+        position: usize,
+        missing: &'a [&'a str],
+    }
+
+    impl<'a> Sql<'a> {
+        fn eval(sql: &str, missing: &'a [&'a str]) -> bool {
+            let tokens = sql
+                .replace('(', " ( ")
+                .replace(')', " ) ")
+                .split_whitespace()
+                .map(String::from)
+                .collect();
+            let mut sql = Sql {
+                tokens,
+                position: 0,
+                missing,
+            };
+            let result = sql.or();
+            assert_eq!(sql.position, sql.tokens.len());
+            result
+        }
+
+        fn next(&mut self) -> String {
+            self.position += 1;
+            self.tokens[self.position - 1].clone()
+        }
+
+        fn peek(&self) -> Option<&str> {
+            self.tokens.get(self.position).map(String::as_str)
+        }
+
+        fn or(&mut self) -> bool {
+            let mut result = self.and();
+            while self.peek() == Some("OR") {
+                self.next();
+                let rhs = self.and();
+                result = result || rhs;
+            }
+            result
+        }
+
+        fn and(&mut self) -> bool {
+            let mut result = self.not();
+            while self.peek() == Some("AND") {
+                self.next();
+                let rhs = self.not();
+                result = result && rhs;
+            }
+            result
+        }
+
+        fn not(&mut self) -> bool {
+            match self.next().as_str() {
+                "NOT" => !self.not(),
+                "(" => {
+                    let result = self.or();
+                    assert_eq!(self.next(), ")");
+                    result
+                }
+                name => {
+                    assert_eq!(self.next(), "IS");
+                    assert_eq!(self.next(), "MISSING");
+                    self.missing.contains(&name.trim_start_matches("s."))
+                }
+            }
+        }
+    }
+
+    fn assert_same_semantics(clause: Clause) {
+        let sql = clause.clone().to_where();
+        let names = ["a", "b", "c", "d"];
+
+        for mask in 0..(1 << names.len()) {
+            let missing = names
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, name)| *name)
+                .collect::<Vec<&str>>();
+
+            assert_eq!(
+                eval(&clause, &missing),
+                Sql::eval(&sql, &missing),
+                "`{}` with {:?} missing",
+                sql,
+                missing
+            );
+        }
+    }
+
+    #[test]
+    fn or_inside_and() {
+        let clause = atom("a").or(atom("b")).and(atom("c"));
+
+        assert_eq!(
+            clause.clone().to_where(),
+            "(s.a IS MISSING OR s.b IS MISSING) AND s.c IS MISSING"
+        );
+        assert_same_semantics(clause);
+    }
+
+    #[test]
+    fn and_inside_or() {
+        let clause = atom("a").or(atom("b").and(atom("c")));
+
+        assert_eq!(
+            clause.clone().to_where(),
+            "s.a IS MISSING OR (s.b IS MISSING AND s.c IS MISSING)"
+        );
+        assert_same_semantics(clause);
+    }
+
+    #[test]
+    fn same_operator_is_flat() {
+        let clause = Clause::all(vec![atom("a"), atom("b"), atom("c")]).unwrap();
+
+        assert_eq!(
+            clause.to_where(),
+            "s.a IS MISSING AND s.b IS MISSING AND s.c IS MISSING"
+        );
+        assert!(Clause::any(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn not() {
+        assert_eq!((!atom("a")).to_where(), "NOT s.a IS MISSING");
+        assert_eq!(
+            (!atom("a").and(atom("b"))).to_where(),
+            "NOT (s.a IS MISSING AND s.b IS MISSING)"
+        );
+        assert_eq!((!!atom("a")).to_where(), "NOT NOT s.a IS MISSING");
+    }
+
+    #[test]
+    fn nesting_keeps_semantics() {
+        let clauses = vec![
+            !atom("a").or(atom("b")),
+            !(atom("a").and(!atom("b"))).or(atom("c")),
+            atom("a").and(atom("b").or(atom("c").and(!atom("d")))),
+            Clause::any(vec![
+                atom("a").and(atom("b")),
+                !atom("c").or(atom("d")),
+                atom("b"),
+            ])
+            .unwrap()
+            .and(atom("d").or(atom("a"))),
+            !(!(atom("a").or(atom("b"))).and(atom("c").or(!atom("d")))),
+        ];
+
+        for clause in clauses {
+            assert_same_semantics(clause);
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_test {
+    use super::*;
+    use rusoto_core::request::BufferedHttpResponse;
+    use std::convert::TryInto;
+
+    #[test]
+    fn error_codes() {
+        let response = BufferedHttpResponse {
+            status: 400u16.try_into().unwrap(),
+            body: "<Error><Code>InvalidCompressionFormat</Code><Message>GZIP is not applicable to the queried object.</Message></Error>".into(),
+            headers: Default::default(),
+        };
+        let error = QueryError::Select(Box::new(RusotoError::Unknown(response)));
+
+        assert_eq!(error.code(), Some("InvalidCompressionFormat".to_string()));
+        assert!(error.is_format_mismatch());
+        assert!(!QueryError::Decode("".to_string()).is_format_mismatch());
+    }
+}
@@ -0,0 +1,833 @@
+use async_trait::async_trait;
+use futures::future::ready;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use glob::{MatchOptions, Pattern};
+use std::path::PathBuf;
+
+use crate::encryption::CustomerKey;
+
+use super::aggregate::AggregatePlan;
+use super::footer::{footer_schema, metadata_length, FOOTER_LENGTH};
+use super::local::{to_csv_record, LocalPlan, Spill};
+use super::schema::Schema;
+use super::stream::Record;
+use super::{
+    CompressionType, Expr, FileHeader, InputObjectFormat, JsonType, ObjectTarget,
+    OutputObjectFormat, QueryContent, QueryError, ScanRange, Select, ValidationError,
+};
+
+// bytes read from the end of a Parquet object, enough for the footer of most files
+const FOOTER_READ: u64 = 64 * 1024;
+
+/// Records of a single object, the stream ends after the first error.
+pub type Records = BoxStream<'static, Result<Record, QueryError>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryObject {
+    pub key: String,
+    pub size: i64,
+}
+
+/// Item of a query over several objects.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectRecord {
+    Record {
+        key: String,
+        record: Record,
+    },
+    /// The object doesn't match the requested format or compression, holds the reason.
+    Skipped {
+        key: String,
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct QueryOptions {
+    concurrency: usize,
+    fail_on_mismatch: bool,
+    spill: Option<Spill>,
+    scan_ranges: usize,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            concurrency: 8,
+            fail_on_mismatch: false,
+            spill: None,
+            scan_ranges: 1,
+        }
+    }
+}
+
+impl QueryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many objects are queried at the same time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Ends the query with an error instead of yielding `ObjectRecord::Skipped` for mismatched objects.
+    pub fn fail_on_mismatch(mut self) -> Self {
+        self.fail_on_mismatch = true;
+        self
+    }
+
+    /// Sorts keeping at most `records_in_memory` records, sorted runs are written to `dir`
+    /// and merged back as the results are read.
+    pub fn spill_to_disk(mut self, dir: PathBuf, records_in_memory: usize) -> Self {
+        self.spill = Some(Spill {
+            dir,
+            records_in_memory,
+        });
+        self
+    }
+
+    /// Splits each object into `parts` byte ranges queried concurrently, their records merged
+    /// in no particular order.
+    ///
+    /// Only uncompressed JSON Lines and CSV objects without a header line can be split, other
+    /// objects, and queries with their own `QueryContent::scan_range`, are queried whole.
+    pub fn scan_ranges(mut self, parts: usize) -> Self {
+        self.scan_ranges = parts;
+        self
+    }
+
+    pub(crate) fn concurrency_limit(&self) -> usize {
+        self.concurrency.max(1)
+    }
+}
+
+#[async_trait]
+pub trait RecordQueriable: Send + Sync {
+    /// Runs `query` on the single object it reads from.
+    async fn select_s3_records(
+        &self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+    ) -> Result<Records, QueryError>;
+
+    async fn list_s3_query_objects(
+        &self,
+        bucket: String,
+        prefix: String,
+    ) -> Result<Vec<QueryObject>, QueryError>;
+
+    /// Size of `key` in bytes, found in the bucket listing unless the implementation has a
+    /// cheaper way, e.g. a HEAD request with the object's SSE-C `customer_key`.
+    async fn s3_object_size(
+        &self,
+        bucket: String,
+        key: String,
+        _customer_key: Option<CustomerKey>,
+    ) -> Result<i64, QueryError> {
+        self.list_s3_query_objects(bucket, key.clone())
+            .await?
+            .into_iter()
+            .find(|object| object.key == key)
+            .map(|object| object.size)
+            .ok_or_else(|| QueryError::Invalid(format!("`{}` doesn't exist", key)))
+    }
+
+    /// Bytes of `key` in `range`, e.g. the footer of a Parquet object. Clients that can't read
+    /// objects directly keep this default, which fails.
+    async fn s3_object_range(
+        &self,
+        bucket: String,
+        key: String,
+        _range: ScanRange,
+        _customer_key: Option<CustomerKey>,
+    ) -> Result<Vec<u8>, QueryError> {
+        Err(QueryError::Invalid(format!(
+            "this client can't read `{}` in bucket `{}` directly",
+            key, bucket
+        )))
+    }
+
+    /// Schema of `key` for `QueryContent::check`, read from the footer of Parquet objects and
+    /// inferred from the first `records` records of JSON and CSV objects.
+    async fn infer_s3_schema(
+        &self,
+        bucket: String,
+        key: String,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        records: usize,
+    ) -> Result<Schema, QueryError> {
+        if let InputObjectFormat::Parquet = input_serialization {
+            let mut tail = self
+                .s3_object_range(
+                    bucket.clone(),
+                    key.clone(),
+                    ScanRange::Last(FOOTER_READ),
+                    None,
+                )
+                .await?;
+            let length = metadata_length(&tail)? + FOOTER_LENGTH;
+            if length > tail.len() {
+                tail = self
+                    .s3_object_range(bucket, key, ScanRange::Last(length as u64), None)
+                    .await?;
+            }
+            return footer_schema(&tail);
+        }
+
+        let query = QueryContent::select(vec![Select::Elements(vec![Expr::Wildcard])])
+            .from(&bucket, &key)
+            .limit(records.max(1));
+        let records = self
+            .select_s3_records(
+                query,
+                body_compression,
+                input_serialization,
+                OutputObjectFormat::JSON(None),
+            )
+            .await?
+            .try_collect::<Vec<Record>>()
+            .await?;
+
+        Ok(Schema::infer(&records))
+    }
+
+    /// Runs `query` on every object it targets, records are merged as they arrive and
+    /// the query `limit` applies to the merged records.
+    async fn query_s3_objects<'a>(
+        &'a self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+        options: QueryOptions,
+    ) -> Result<BoxStream<'a, Result<ObjectRecord, QueryError>>, QueryError> {
+        query.validate(&input_serialization, &body_compression)?;
+        let (target, bucket) = query
+            .from
+            .clone()
+            .ok_or_else(|| QueryError::from(ValidationError::MissingFrom))?;
+        let objects = match target {
+            ObjectTarget::Key(key) => vec![(key, None)],
+            ObjectTarget::Prefix(prefix) => self
+                .list_s3_query_objects(bucket.clone(), prefix)
+                .await?
+                .into_iter()
+                .map(|object| (object.key, Some(object.size)))
+                .collect(),
+            ObjectTarget::Glob(pattern) => {
+                let matcher = Pattern::new(&pattern)
+                    .map_err(|e| QueryError::Invalid(format!("glob `{}`: {}", pattern, e)))?;
+                self.list_s3_query_objects(bucket.clone(), literal_prefix(&pattern).to_string())
+                    .await?
+                    .into_iter()
+                    .filter(|object| matcher.matches_with(&object.key, glob_options()))
+                    .map(|object| (object.key, Some(object.size)))
+                    .collect()
+            }
+        };
+
+        // each object is queried whole or as a range at a time
+        let split = options.scan_ranges > 1
+            && query.scan_range.is_none()
+            && splits(&input_serialization, &body_compression);
+        let mut parts: Vec<(String, Option<ScanRange>)> = Vec::new();
+        for (key, size) in objects {
+            if !split || format_mismatch(&key, &input_serialization, &body_compression).is_some() {
+                parts.push((key, None));
+                continue;
+            }
+            let size = match size {
+                Some(size) => size,
+                None => {
+                    self.s3_object_size(bucket.clone(), key.clone(), query.customer_key.clone())
+                        .await?
+                }
+            };
+            let ranges = scan_ranges(size.max(0) as u64, options.scan_ranges);
+            if ranges.is_empty() {
+                parts.push((key, None));
+            } else {
+                parts.extend(ranges.into_iter().map(|range| (key.clone(), Some(range))));
+            }
+        }
+
+        let limit = query.limit;
+        let concurrency = options.concurrency_limit();
+        let fail_on_mismatch = options.fail_on_mismatch;
+        let records = stream::iter(parts)
+            .map(move |(key, range)| {
+                let mut object_query = query.clone().from(&bucket, &key);
+                if let Some(range) = range {
+                    object_query = object_query.scan_range(range);
+                }
+                object_records(
+                    self,
+                    key,
+                    object_query,
+                    body_compression.clone(),
+                    input_serialization.clone(),
+                    output_serialization.clone(),
+                    fail_on_mismatch,
+                )
+            })
+            .flatten_unordered(concurrency)
+            .scan(0, move |taken, item| {
+                if limit.is_some_and(|limit| *taken >= limit) {
+                    return ready(None);
+                }
+                if let Ok(ObjectRecord::Record { .. }) = item {
+                    *taken += 1;
+                }
+                ready(Some(item))
+            });
+
+        Ok(records.boxed())
+    }
+
+    /// Runs an aggregate only `query` on every object it targets and merges the partial results
+    /// into a single record, `Avg` is merged from each object's sum and count.
+    ///
+    /// Partials are read as JSON, `output_serialization` only shapes the merged record.
+    /// Mismatched objects are left out of the result unless `options` fails on them.
+    async fn aggregate_s3_objects(
+        &self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+        options: QueryOptions,
+    ) -> Result<Record, QueryError> {
+        let (plan, select) = AggregatePlan::new(&query.select).ok_or_else(|| {
+            QueryError::Invalid(
+                "only `Count`, `Sum`, `Min`, `Max` and `Avg` can be merged across objects"
+                    .to_string(),
+            )
+        })?;
+        let mut object_query = query.clone();
+        object_query.select = select;
+        object_query.limit = None;
+
+        let mut merge = plan.merger();
+        let mut partials = self
+            .query_s3_objects(
+                object_query,
+                body_compression,
+                input_serialization,
+                OutputObjectFormat::JSON(None),
+                options,
+            )
+            .await?;
+        while let Some(partial) = partials.try_next().await? {
+            if let ObjectRecord::Record { record, .. } = partial {
+                merge.add(&record)?;
+            }
+        }
+
+        let record = merge.finish();
+        Ok(match output_serialization {
+            OutputObjectFormat::JSON(_) => record,
+            OutputObjectFormat::CSV(_) => to_csv_record(&query.select, &record),
+        })
+    }
+
+    /// Runs `query` on every object it targets, with `group_by`, `order_by` and `distinct`
+    /// run locally on the merged records.
+    ///
+    /// The projection and where clause still run in S3 Select, read as JSON when there are local
+    /// operations. Records of mismatched objects are left out unless `options` fails on them.
+    async fn query_s3_records<'a>(
+        &'a self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+        options: QueryOptions,
+    ) -> Result<BoxStream<'a, Result<Record, QueryError>>, QueryError> {
+        let plan = match LocalPlan::new(&query)? {
+            Some(plan) => plan,
+            None if AggregatePlan::new(&query.select).is_some() => {
+                let record = self
+                    .aggregate_s3_objects(
+                        query,
+                        body_compression,
+                        input_serialization,
+                        output_serialization,
+                        options,
+                    )
+                    .await?;
+                return Ok(stream::once(ready(Ok(record))).boxed());
+            }
+            None => {
+                let records = self
+                    .query_s3_objects(
+                        query,
+                        body_compression,
+                        input_serialization,
+                        output_serialization,
+                        options,
+                    )
+                    .await?;
+                return Ok(records
+                    .try_filter_map(|record| {
+                        ready(Ok(match record {
+                            ObjectRecord::Record { record, .. } => Some(record),
+                            ObjectRecord::Skipped { .. } => None,
+                        }))
+                    })
+                    .boxed());
+            }
+        };
+
+        let mut run = plan.runner(options.spill.clone());
+        let mut records = self
+            .query_s3_objects(
+                plan.pushed.clone(),
+                body_compression,
+                input_serialization,
+                OutputObjectFormat::JSON(None),
+                options,
+            )
+            .await?;
+        while let Some(record) = records.try_next().await? {
+            if let ObjectRecord::Record { record, .. } = record {
+                run.push(record)?;
+            }
+        }
+
+        let records = run.finish()?;
+        Ok(match output_serialization {
+            OutputObjectFormat::JSON(_) => stream::iter(records).boxed(),
+            OutputObjectFormat::CSV(_) => stream::iter(records)
+                .map_ok(move |record| to_csv_record(&query.select, &record))
+                .boxed(),
+        })
+    }
+}
+
+fn object_records<'a, Q: RecordQueriable + ?Sized>(
+    queriable: &'a Q,
+    key: String,
+    query: QueryContent,
+    body_compression: CompressionType,
+    input_serialization: InputObjectFormat,
+    output_serialization: OutputObjectFormat,
+    fail_on_mismatch: bool,
+) -> BoxStream<'a, Result<ObjectRecord, QueryError>> {
+    let skip = move |key: String, reason: String| {
+        if fail_on_mismatch {
+            Err(QueryError::Invalid(format!("`{}`: {}", key, reason)))
+        } else {
+            Ok(ObjectRecord::Skipped { key, reason })
+        }
+    };
+
+    if let Some(reason) = format_mismatch(&key, &input_serialization, &body_compression) {
+        return stream::once(ready(skip(key, reason))).boxed();
+    }
+
+    stream::once(queriable.select_s3_records(
+        query,
+        body_compression,
+        input_serialization,
+        output_serialization,
+    ))
+    .flat_map(move |records| match records {
+        Ok(records) => {
+            let key = key.clone();
+            records
+                .map(move |record| match record {
+                    Ok(record) => Ok(ObjectRecord::Record {
+                        key: key.clone(),
+                        record,
+                    }),
+                    Err(e) if e.is_format_mismatch() => skip(key.clone(), e.to_string()),
+                    Err(e) => Err(e),
+                })
+                .boxed()
+        }
+        Err(e) if e.is_format_mismatch() => {
+            stream::once(ready(skip(key.clone(), e.to_string()))).boxed()
+        }
+        Err(e) => stream::once(ready(Err(e))).boxed(),
+    })
+    .boxed()
+}
+
+// S3 Select scans ranges of uncompressed JSON Lines and CSV, a header line is only in the first range
+fn splits(input: &InputObjectFormat, compression: &CompressionType) -> bool {
+    let format = match input {
+        InputObjectFormat::JSON(JsonType::Lines) => true,
+        InputObjectFormat::CSV(csv) => csv.file_header == FileHeader::None,
+        InputObjectFormat::JSON(JsonType::Document) | InputObjectFormat::Parquet => false,
+    };
+
+    format && *compression == CompressionType::NONE
+}
+
+/// `parts` ranges of about the same size covering `size` bytes, fewer for objects smaller
+/// than `parts` bytes and none for empty objects.
+pub(crate) fn scan_ranges(size: u64, parts: usize) -> Vec<ScanRange> {
+    let parts = (parts.max(1) as u64).min(size);
+    if parts == 0 {
+        return Vec::new();
+    }
+    let length = size.div_ceil(parts);
+
+    (0..size)
+        .step_by(length as usize)
+        .map(|start| ScanRange::Between(start, (start + length).min(size) - 1))
+        .collect()
+}
+
+/// Why `key` can't be read as `input` with `compression`, judging by its extensions.
+///
+/// Keys without a known extension are always queried, S3 reports the mismatch if there is one.
+pub(crate) fn format_mismatch(
+    key: &str,
+    input: &InputObjectFormat,
+    compression: &CompressionType,
+) -> Option<String> {
+    let name = key.rsplit('/').next().unwrap_or(key).to_lowercase();
+    let (name, compressed) = if let Some(name) = name.strip_suffix(".gz") {
+        (name, Some(CompressionType::GZIP))
+    } else if let Some(name) = name.strip_suffix(".bz2") {
+        (name, Some(CompressionType::BZIP2))
+    } else {
+        (name.as_str(), None)
+    };
+
+    if let Some(compressed) = compressed.filter(|compressed| compressed != compression) {
+        return Some(format!("object is {:?} compressed", compressed));
+    }
+
+    let format = match name.rsplit_once('.')?.1 {
+        "json" | "jsonl" | "ndjson" => "JSON",
+        "csv" | "tsv" => "CSV",
+        "parquet" => "Parquet",
+        _ => return None,
+    };
+    let expected = match input {
+        InputObjectFormat::JSON(_) => "JSON",
+        InputObjectFormat::CSV(_) => "CSV",
+        InputObjectFormat::Parquet => "Parquet",
+    };
+
+    if format == expected {
+        None
+    } else {
+        Some(format!("object is {}, not {}", format, expected))
+    }
+}
+
+// Keys listed for a glob start with everything before its first wildcard
+fn literal_prefix(pattern: &str) -> &str {
+    let end = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+    &pattern[..end]
+}
+
+fn glob_options() -> MatchOptions {
+    MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    }
+}
+
+#[cfg(test)]
+mod objects_test {
+    use super::*;
+    use crate::query::{Clause, Expr, JsonType, Select};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    /// Objects held in memory as JSON lines, `select_s3_records` only applies the limit.
+    struct Objects(BTreeMap<String, Vec<serde_json::Value>>);
+
+    #[async_trait]
+    impl RecordQueriable for Objects {
+        async fn select_s3_records(
+            &self,
+            query: QueryContent,
+            _: CompressionType,
+            _: InputObjectFormat,
+            _: OutputObjectFormat,
+        ) -> Result<Records, QueryError> {
+            let key = match query.from {
+                Some((ObjectTarget::Key(key), _)) => key,
+                _ => return Err(QueryError::Invalid("not a key".to_string())),
+            };
+            if key.ends_with(".broken") {
+                return Err(QueryError::Failed {
+                    code: "JSONParsingError".to_string(),
+                    message: "Unexpected token".to_string(),
+                });
+            }
+            let records = self.0[&key]
+                .iter()
+                .take(query.limit.unwrap_or(usize::MAX))
+                .map(|value| Ok(value.as_object().unwrap().clone()))
+                .collect::<Vec<_>>();
+
+            Ok(stream::iter(records).boxed())
+        }
+
+        async fn list_s3_query_objects(
+            &self,
+            _: String,
+            prefix: String,
+        ) -> Result<Vec<QueryObject>, QueryError> {
+            Ok(self
+                .0
+                .keys()
+                .filter(|key| key.starts_with(&prefix))
+                .map(|key| QueryObject {
+                    key: key.clone(),
+                    size: 0,
+                })
+                .collect())
+        }
+    }
+
+    fn objects() -> Objects {
+        let mut objects = BTreeMap::new();
+        objects.insert(
+            "logs/2020-01/a.json".to_string(),
+            vec![json!({"id": 1}), json!({"id": 2})],
+        );
+        objects.insert("logs/2020-01/b.json".to_string(), vec![json!({"id": 3})]);
+        objects.insert("logs/2020-02/c.json".to_string(), vec![json!({"id": 4})]);
+        objects.insert("logs/2020-02/d.csv".to_string(), vec![json!({"id": 5})]);
+        objects.insert("logs/2020-02/e.broken".to_string(), vec![]);
+        objects.insert("other/f.json".to_string(), vec![json!({"id": 6})]);
+        Objects(objects)
+    }
+
+    fn query() -> QueryContent {
+        QueryContent::select(vec![Select::Elements(vec![Expr::column("id")])])
+            .where_clause(Clause::IsNotNull(Expr::column("id")))
+    }
+
+    async fn run(
+        query: QueryContent,
+        options: QueryOptions,
+    ) -> Result<Vec<ObjectRecord>, QueryError> {
+        let objects = objects();
+        let records = objects
+            .query_s3_objects(
+                query,
+                CompressionType::NONE,
+                InputObjectFormat::JSON(JsonType::Lines),
+                OutputObjectFormat::JSON(None),
+                options,
+            )
+            .await?
+            .try_collect::<Vec<ObjectRecord>>()
+            .await?;
+        Ok(records)
+    }
+
+    fn ids(records: &[ObjectRecord]) -> Vec<i64> {
+        let mut ids = records
+            .iter()
+            .filter_map(|record| match record {
+                ObjectRecord::Record { record, .. } => record["id"].as_i64(),
+                ObjectRecord::Skipped { .. } => None,
+            })
+            .collect::<Vec<i64>>();
+        ids.sort();
+        ids
+    }
+
+    fn skipped(records: &[ObjectRecord]) -> Vec<&str> {
+        records
+            .iter()
+            .filter_map(|record| match record {
+                ObjectRecord::Skipped { key, .. } => Some(key.as_str()),
+                ObjectRecord::Record { .. } => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn prefix() {
+        let records = run(
+            query().from_prefix("bucket", "logs/"),
+            QueryOptions::new().concurrency(2),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(ids(&records), vec![1, 2, 3, 4]);
+        let mut skipped = skipped(&records);
+        skipped.sort();
+        assert_eq!(skipped, vec!["logs/2020-02/d.csv", "logs/2020-02/e.broken"]);
+    }
+
+    #[tokio::test]
+    async fn glob() {
+        let records = run(
+            query().from_glob("bucket", "logs/*/*.json"),
+            QueryOptions::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids(&records), vec![1, 2, 3, 4]);
+        assert!(skipped(&records).is_empty());
+
+        let records = run(query().from_glob("bucket", "*.json"), QueryOptions::new())
+            .await
+            .unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn limit_over_all_objects() {
+        let records = run(
+            query().from_glob("bucket", "logs/*/*.json").limit(3),
+            QueryOptions::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(ids(&records).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn fail_on_mismatch() {
+        let error = run(
+            query().from_prefix("bucket", "logs/2020-02/"),
+            QueryOptions::new().fail_on_mismatch(),
+        )
+        .await;
+
+        assert!(matches!(error, Err(QueryError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn aggregates_over_all_objects() {
+        // each object already holds the partial row its rewritten query would return
+        let mut partials = BTreeMap::new();
+        partials.insert(
+            "sales/a.json".to_string(),
+            vec![json!({"part0_value": 2, "part1_sum": 30, "part1_count": 2})],
+        );
+        partials.insert(
+            "sales/b.json".to_string(),
+            vec![json!({"part0_value": 1, "part1_sum": 3, "part1_count": 1})],
+        );
+        partials.insert("sales/c.csv".to_string(), vec![]);
+
+        let query = QueryContent::select(vec![
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("price")).alias("average"),
+        ])
+        .from_prefix("bucket", "sales/");
+        let record = Objects(partials)
+            .aggregate_s3_objects(
+                query,
+                CompressionType::NONE,
+                InputObjectFormat::JSON(JsonType::Lines),
+                OutputObjectFormat::JSON(None),
+                QueryOptions::new(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            serde_json::Value::Object(record),
+            json!({"_1": 3, "average": 11.0})
+        );
+
+        let plain = objects()
+            .aggregate_s3_objects(
+                query_all(),
+                CompressionType::NONE,
+                InputObjectFormat::JSON(JsonType::Lines),
+                OutputObjectFormat::JSON(None),
+                QueryOptions::new(),
+            )
+            .await;
+        assert!(matches!(plain, Err(QueryError::Invalid(_))));
+    }
+
+    fn query_all() -> QueryContent {
+        query().from_prefix("bucket", "logs/")
+    }
+
+    #[test]
+    fn mismatches() {
+        let json = InputObjectFormat::JSON(JsonType::Lines);
+
+        assert_eq!(
+            format_mismatch("a/b.json", &json, &CompressionType::NONE),
+            None
+        );
+        assert_eq!(
+            format_mismatch("a/b.json.gz", &json, &CompressionType::GZIP),
+            None
+        );
+        assert_eq!(format_mismatch("a/b", &json, &CompressionType::NONE), None);
+        assert_eq!(
+            format_mismatch("a.b/c", &json, &CompressionType::NONE),
+            None
+        );
+        assert_eq!(
+            format_mismatch("a/b.json.gz", &json, &CompressionType::NONE),
+            Some("object is GZIP compressed".to_string())
+        );
+        assert_eq!(
+            format_mismatch("a/b.parquet", &json, &CompressionType::NONE),
+            Some("object is Parquet, not JSON".to_string())
+        );
+        assert_eq!(
+            format_mismatch("a/b.CSV.bz2", &json, &CompressionType::BZIP2),
+            Some("object is CSV, not JSON".to_string())
+        );
+    }
+
+    #[test]
+    fn glob_prefixes() {
+        assert_eq!(literal_prefix("logs/2020-*/a.json"), "logs/2020-");
+        assert_eq!(literal_prefix("logs/[ab]/*.json"), "logs/");
+        assert_eq!(literal_prefix("logs/a.json"), "logs/a.json");
+    }
+
+    #[test]
+    fn splits_into_ranges() {
+        assert_eq!(
+            scan_ranges(10, 3),
+            vec![
+                ScanRange::Between(0, 3),
+                ScanRange::Between(4, 7),
+                ScanRange::Between(8, 9),
+            ]
+        );
+        assert_eq!(
+            scan_ranges(2, 4),
+            vec![ScanRange::Between(0, 0), ScanRange::Between(1, 1)]
+        );
+        assert_eq!(scan_ranges(0, 4), vec![]);
+
+        assert!(splits(
+            &InputObjectFormat::JSON(JsonType::Lines),
+            &CompressionType::NONE
+        ));
+        assert!(!splits(
+            &InputObjectFormat::JSON(JsonType::Lines),
+            &CompressionType::GZIP
+        ));
+        assert!(!splits(
+            &InputObjectFormat::JSON(JsonType::Document),
+            &CompressionType::NONE
+        ));
+        assert!(!splits(
+            &InputObjectFormat::CSV(crate::query::CsvInput::new().file_header(FileHeader::Use)),
+            &CompressionType::NONE
+        ));
+    }
+}
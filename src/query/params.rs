@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Expr, QueryContent, ValidationError, Value};
+
+/// Values for the `Expr::Param` placeholders of a query, see `QueryContent::bind`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Params {
+    values: BTreeMap<String, Value>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.values.insert(name.to_string(), value.into());
+        self
+    }
+}
+
+impl QueryContent {
+    /// Names of the `Expr::Param` placeholders in the query.
+    pub fn params(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        self.visit(&mut |e| {
+            if let Expr::Param(name) = e {
+                names.insert(name.clone());
+            }
+        });
+        names
+    }
+
+    /// Copy of the query with every placeholder replaced by its value in `params`.
+    ///
+    /// Values become literals, typed and escaped like any other `Value`, so they never end up
+    /// in the SQL as raw text. Placeholders without a value and values without a placeholder
+    /// are errors.
+    pub fn bind(&self, params: &Params) -> Result<QueryContent, ValidationError> {
+        let names = self.params();
+        if let Some(name) = params.values.keys().find(|name| !names.contains(*name)) {
+            return Err(ValidationError::UnknownParam(name.clone()));
+        }
+        if let Some(name) = names.iter().find(|name| !params.values.contains_key(*name)) {
+            return Err(ValidationError::UnboundParam(name.clone()));
+        }
+
+        let mut query = self.clone();
+        query.visit_mut(&mut |e| {
+            if let Expr::Param(name) = e {
+                *e = Expr::Literal(params.values[name.as_str()].clone());
+            }
+        });
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod params_test {
+    use super::*;
+    use crate::query::{Clause, CompressionType, InputObjectFormat, JsonType, Select};
+
+    fn template() -> QueryContent {
+        QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+            .from("bucket", "key")
+            .where_clause(
+                Clause::G(Expr::column("age"), Expr::param("min_age"))
+                    .and(Clause::E(Expr::column("city"), Expr::param("city"))),
+            )
+    }
+
+    #[test]
+    fn bind() {
+        let query = template()
+            .bind(&Params::new().set("min_age", 30).set("city", "O'Fallon"))
+            .unwrap();
+
+        assert_eq!(
+            query.sql(),
+            "SELECT s.name FROM S3Object s WHERE s.age > 30 AND s.city = 'O''Fallon'"
+        );
+        assert!(query.params().is_empty());
+    }
+
+    #[test]
+    fn missing_and_unknown() {
+        let query = template();
+        assert_eq!(
+            query.params().into_iter().collect::<Vec<String>>(),
+            vec!["city", "min_age"]
+        );
+
+        assert_eq!(
+            query.bind(&Params::new().set("min_age", 30)).err(),
+            Some(ValidationError::UnboundParam("city".to_string()))
+        );
+        assert_eq!(
+            query
+                .bind(
+                    &Params::new()
+                        .set("min_age", 30)
+                        .set("city", "x")
+                        .set("max", 1)
+                )
+                .err(),
+            Some(ValidationError::UnknownParam("max".to_string()))
+        );
+        assert_eq!(
+            query.validate(
+                &InputObjectFormat::JSON(JsonType::Lines),
+                &CompressionType::NONE
+            ),
+            Err(ValidationError::UnboundParam("city".to_string()))
+        );
+    }
+
+    #[test]
+    fn serialized_template() {
+        let json = serde_json::to_string(&template()).unwrap();
+        let query: QueryContent = serde_json::from_str(&json).unwrap();
+        let params: Params =
+            serde_json::from_str(r#"{"min_age": {"Int": 30}, "city": {"String": "Lyon"}}"#)
+                .unwrap();
+
+        assert_eq!(
+            query.bind(&params).unwrap().sql(),
+            "SELECT s.name FROM S3Object s WHERE s.age > 30 AND s.city = 'Lyon'"
+        );
+    }
+}
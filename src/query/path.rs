@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+
+use super::expr::string_literal;
+
+/// One step into a record, used by `QueryContent::from_path` and `Expr::path`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Path {
+    Index(usize),
+    WildCardIndex,
+    Name(String),
+    WildCardName,
+}
+
+impl Path {
+    /// Parses the dotted form, e.g. `address.city`, `tags[0]` or `rules[*].id`.
+    /// Segments that aren't `name`, `name[<index>]...` or `*` are kept as a single `Path::Name`,
+    /// names containing `.` have to be built with `Path::Name` directly.
+    pub fn parse(path: &str) -> Vec<Path> {
+        path.split('.').flat_map(parse_segment).collect()
+    }
+
+    /// `.name`, `['name']` when it isn't a plain identifier, `[0]`, `[*]` or `.*`.
+    pub(crate) fn to_sql(path: &[Path]) -> String {
+        path.iter()
+            .map(|segment| match segment {
+                Path::Name(name) if is_identifier(name) => format!(".{}", name),
+                Path::Name(name) => format!("[{}]", string_literal(name)),
+                Path::Index(i) => format!("[{}]", i),
+                Path::WildCardIndex => String::from("[*]"),
+                Path::WildCardName => String::from(".*"),
+            })
+            .collect()
+    }
+}
+
+fn parse_segment(segment: &str) -> Vec<Path> {
+    if segment == "*" {
+        return vec![Path::WildCardName];
+    }
+
+    let (name, mut indexes) = match segment.find('[') {
+        Some(i) => segment.split_at(i),
+        None => (segment, ""),
+    };
+    let mut path = vec![Path::Name(name.to_string())];
+
+    while !indexes.is_empty() {
+        let index = indexes
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'));
+
+        match index {
+            Some(("*", rest)) => {
+                path.push(Path::WildCardIndex);
+                indexes = rest;
+            }
+            Some((index, rest))
+                if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                match index.parse() {
+                    Ok(i) => path.push(Path::Index(i)),
+                    Err(_) => return vec![Path::Name(segment.to_string())],
+                }
+                indexes = rest;
+            }
+            _ => return vec![Path::Name(segment.to_string())],
+        }
+    }
+
+    if name.is_empty() {
+        vec![Path::Name(segment.to_string())]
+    } else {
+        path
+    }
+}
+
+/// `[A-Za-z_][A-Za-z0-9_]*` that isn't a reserved word, so it can be written without quotes.
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && RESERVED
+            .binary_search(&name.to_lowercase().as_str())
+            .is_err()
+}
+
+// S3 Select reserved keywords, sorted for `binary_search`
+const RESERVED: &[&str] = &[
+    "absolute",
+    "action",
+    "add",
+    "all",
+    "allocate",
+    "alter",
+    "and",
+    "any",
+    "are",
+    "as",
+    "asc",
+    "assertion",
+    "at",
+    "authorization",
+    "avg",
+    "bag",
+    "begin",
+    "between",
+    "bit",
+    "bit_length",
+    "blob",
+    "bool",
+    "boolean",
+    "both",
+    "by",
+    "cascade",
+    "cascaded",
+    "case",
+    "cast",
+    "catalog",
+    "char",
+    "char_length",
+    "character",
+    "character_length",
+    "check",
+    "clob",
+    "close",
+    "coalesce",
+    "collate",
+    "collation",
+    "column",
+    "commit",
+    "connect",
+    "connection",
+    "constraint",
+    "constraints",
+    "continue",
+    "convert",
+    "corresponding",
+    "count",
+    "create",
+    "cross",
+    "current",
+    "current_date",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "cursor",
+    "date",
+    "date_add",
+    "date_diff",
+    "day",
+    "deallocate",
+    "dec",
+    "decimal",
+    "declare",
+    "default",
+    "deferrable",
+    "deferred",
+    "delete",
+    "desc",
+    "describe",
+    "descriptor",
+    "diagnostics",
+    "disconnect",
+    "distinct",
+    "domain",
+    "double",
+    "drop",
+    "else",
+    "end",
+    "end-exec",
+    "escape",
+    "except",
+    "exception",
+    "exec",
+    "execute",
+    "exists",
+    "external",
+    "extract",
+    "false",
+    "fetch",
+    "first",
+    "float",
+    "for",
+    "foreign",
+    "found",
+    "from",
+    "full",
+    "get",
+    "global",
+    "go",
+    "goto",
+    "grant",
+    "group",
+    "having",
+    "hour",
+    "identity",
+    "immediate",
+    "in",
+    "indicator",
+    "initially",
+    "inner",
+    "input",
+    "insensitive",
+    "insert",
+    "int",
+    "integer",
+    "intersect",
+    "interval",
+    "into",
+    "is",
+    "isolation",
+    "join",
+    "key",
+    "language",
+    "last",
+    "leading",
+    "left",
+    "level",
+    "like",
+    "limit",
+    "list",
+    "local",
+    "lower",
+    "match",
+    "max",
+    "min",
+    "minute",
+    "missing",
+    "module",
+    "month",
+    "names",
+    "national",
+    "natural",
+    "nchar",
+    "next",
+    "no",
+    "not",
+    "null",
+    "nullif",
+    "numeric",
+    "octet_length",
+    "of",
+    "on",
+    "only",
+    "open",
+    "option",
+    "or",
+    "order",
+    "outer",
+    "output",
+    "overlaps",
+    "pad",
+    "partial",
+    "pivot",
+    "position",
+    "precision",
+    "prepare",
+    "preserve",
+    "primary",
+    "prior",
+    "privileges",
+    "procedure",
+    "public",
+    "read",
+    "real",
+    "references",
+    "relative",
+    "restrict",
+    "revoke",
+    "right",
+    "rollback",
+    "rows",
+    "schema",
+    "scroll",
+    "second",
+    "section",
+    "select",
+    "session",
+    "session_user",
+    "set",
+    "sexp",
+    "size",
+    "smallint",
+    "some",
+    "space",
+    "sql",
+    "sqlcode",
+    "sqlerror",
+    "sqlstate",
+    "string",
+    "struct",
+    "substring",
+    "sum",
+    "symbol",
+    "system_user",
+    "table",
+    "temporary",
+    "then",
+    "time",
+    "timestamp",
+    "timezone_hour",
+    "timezone_minute",
+    "to",
+    "to_string",
+    "to_timestamp",
+    "trailing",
+    "transaction",
+    "translate",
+    "trim",
+    "true",
+    "tuple",
+    "txid",
+    "union",
+    "unique",
+    "unknown",
+    "unpivot",
+    "update",
+    "upper",
+    "usage",
+    "user",
+    "using",
+    "utcnow",
+    "value",
+    "values",
+    "varchar",
+    "varying",
+    "view",
+    "when",
+    "whenever",
+    "where",
+    "with",
+    "work",
+    "write",
+    "year",
+    "zone",
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn name(name: &str) -> Path {
+        Path::Name(name.to_string())
+    }
+
+    #[test]
+    fn reserved_is_sorted() {
+        assert!(RESERVED.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn parse_dotted_paths() {
+        assert_eq!(
+            Path::parse("address.city"),
+            vec![name("address"), name("city")]
+        );
+        assert_eq!(
+            Path::parse("rules[*].tags[0][12]"),
+            vec![
+                name("rules"),
+                Path::WildCardIndex,
+                name("tags"),
+                Path::Index(0),
+                Path::Index(12),
+            ]
+        );
+        assert_eq!(Path::parse("a.*"), vec![name("a"), Path::WildCardName]);
+        assert_eq!(Path::parse("first name"), vec![name("first name")]);
+        assert_eq!(Path::parse("a[x]"), vec![name("a[x]")]);
+        assert_eq!(Path::parse("a[1"), vec![name("a[1")]);
+        assert_eq!(Path::parse("[0]"), vec![name("[0]")]);
+    }
+
+    #[test]
+    fn dotted_or_bracket_syntax() {
+        let path = vec![
+            name("address"),
+            name("first name"),
+            name("select"),
+            name("it's"),
+            Path::Index(3),
+            Path::WildCardIndex,
+            Path::WildCardName,
+            name("_id"),
+        ];
+
+        assert_eq!(
+            Path::to_sql(&path),
+            ".address['first name']['select']['it''s'][3][*].*._id"
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        assert!(is_identifier("city"));
+        assert!(is_identifier("_id2"));
+        assert!(!is_identifier("Count"));
+        assert!(!is_identifier("1st"));
+        assert!(!is_identifier("a-b"));
+        assert!(!is_identifier(""));
+    }
+}
@@ -0,0 +1,505 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as Json};
+use std::fmt;
+use std::mem;
+
+use super::path::{is_identifier, Path};
+use super::stream::Record;
+use super::{CastType, Clause, Expr, QueryContent, Select, ValidationError, Value};
+
+/// Type of a field as S3 Select reads it, CSV fields are strings until they're cast.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum FieldType {
+    Bool,
+    Int,
+    Float,
+    Decimal,
+    String,
+    Timestamp,
+    List(Box<FieldType>),
+    Struct(Vec<Field>),
+    Null, // only nulls were seen
+    Any,  // values of different types, or a map
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool => write!(f, "bool"),
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Decimal => write!(f, "decimal"),
+            Self::String => write!(f, "string"),
+            Self::Timestamp => write!(f, "timestamp"),
+            Self::List(element) => write!(f, "list<{}>", element),
+            Self::Struct(_) => write!(f, "struct"),
+            Self::Null => write!(f, "null"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub field_type: FieldType,
+    pub nullable: bool, // null or missing in some records
+}
+
+impl Field {
+    pub fn new(name: &str, field_type: FieldType, nullable: bool) -> Self {
+        Field {
+            name: name.to_string(),
+            field_type,
+            nullable,
+        }
+    }
+}
+
+/// Fields of the records in an object, see `RecordQueriable::infer_s3_schema`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Schema { fields }
+    }
+
+    /// Schema of sampled `records`, fields of a record are in name order and fields missing from
+    /// earlier records come after theirs.
+    pub fn infer(records: &[Record]) -> Self {
+        let fields = records
+            .iter()
+            .map(object_fields)
+            .reduce(merge_fields)
+            .unwrap_or_default();
+
+        Schema { fields }
+    }
+}
+
+fn object_fields(object: &Map<String, Json>) -> Vec<Field> {
+    object
+        .iter()
+        .map(|(name, value)| Field::new(name, type_of(value), value.is_null()))
+        .collect()
+}
+
+fn type_of(value: &Json) -> FieldType {
+    match value {
+        Json::Null => FieldType::Null,
+        Json::Bool(_) => FieldType::Bool,
+        Json::Number(n) if n.is_i64() || n.is_u64() => FieldType::Int,
+        Json::Number(_) => FieldType::Float,
+        Json::String(_) => FieldType::String,
+        Json::Array(values) => FieldType::List(Box::new(
+            values.iter().map(type_of).fold(FieldType::Null, merge),
+        )),
+        Json::Object(object) => FieldType::Struct(object_fields(object)),
+    }
+}
+
+fn merge(a: FieldType, b: FieldType) -> FieldType {
+    match (a, b) {
+        (FieldType::Null, t) | (t, FieldType::Null) => t,
+        (FieldType::Int, FieldType::Float) | (FieldType::Float, FieldType::Int) => FieldType::Float,
+        (FieldType::List(a), FieldType::List(b)) => FieldType::List(Box::new(merge(*a, *b))),
+        (FieldType::Struct(a), FieldType::Struct(b)) => FieldType::Struct(merge_fields(a, b)),
+        (a, b) if a == b => a,
+        _ => FieldType::Any,
+    }
+}
+
+// fields missing on either side become nullable
+fn merge_fields(mut fields: Vec<Field>, other: Vec<Field>) -> Vec<Field> {
+    for field in fields.iter_mut() {
+        field.nullable |= !other.iter().any(|o| o.name == field.name);
+    }
+    for o in other {
+        match fields.iter_mut().find(|field| field.name == o.name) {
+            Some(field) => {
+                field.field_type = merge(
+                    mem::replace(&mut field.field_type, FieldType::Null),
+                    o.field_type,
+                );
+                field.nullable |= o.nullable;
+            }
+            None => fields.push(Field {
+                nullable: true,
+                ..o
+            }),
+        }
+    }
+    fields
+}
+
+// Unquoted names are case insensitive in S3 Select
+fn find<'a>(fields: &'a [Field], name: &str) -> Option<&'a Field> {
+    fields.iter().find(|field| field.name == name).or_else(|| {
+        fields
+            .iter()
+            .find(|field| is_identifier(name) && field.name.eq_ignore_ascii_case(name))
+    })
+}
+
+// Type at `path` under `root`, `Ok(None)` when the schema can't tell, e.g. under `Any`.
+// `Err` when the path doesn't exist.
+fn resolve<'a>(root: &'a FieldType, path: &[Path]) -> Result<Option<&'a FieldType>, ()> {
+    let mut current = root;
+    for step in path {
+        current = match (current, step) {
+            (FieldType::Any, _) | (FieldType::Null, _) => return Ok(None),
+            (FieldType::Struct(fields), Path::Name(name)) => {
+                &find(fields, name).ok_or(())?.field_type
+            }
+            (FieldType::Struct(_), Path::WildCardName) => return Ok(None),
+            (FieldType::List(element), Path::Index(_))
+            | (FieldType::List(element), Path::WildCardIndex) => element,
+            _ => return Err(()),
+        };
+    }
+    Ok(Some(current))
+}
+
+fn literal_type(value: &Value) -> Option<FieldType> {
+    let field_type = match value {
+        Value::Int(_) => FieldType::Int,
+        Value::Float(_) => FieldType::Float,
+        Value::Decimal(_) => FieldType::Decimal,
+        Value::Bool(_) => FieldType::Bool,
+        Value::String(_) => FieldType::String,
+        Value::Timestamp(_) => FieldType::Timestamp,
+        Value::Null => return None,
+    };
+    Some(field_type)
+}
+
+fn cast_type(cast: &CastType) -> FieldType {
+    match cast {
+        CastType::Int => FieldType::Int,
+        CastType::Float => FieldType::Float,
+        CastType::Decimal => FieldType::Decimal,
+        CastType::Timestamp => FieldType::Timestamp,
+        CastType::String => FieldType::String,
+        CastType::Bool => FieldType::Bool,
+    }
+}
+
+fn is_numeric(field_type: &FieldType) -> bool {
+    matches!(
+        field_type,
+        FieldType::Int | FieldType::Float | FieldType::Decimal
+    )
+}
+
+// S3 Select compares numbers of any kind with each other, other types only with themselves
+fn comparable(a: &FieldType, b: &FieldType) -> bool {
+    let untyped = |t: &FieldType| matches!(t, FieldType::Null | FieldType::Any);
+
+    untyped(a)
+        || untyped(b)
+        || mem::discriminant(a) == mem::discriminant(b)
+        || (is_numeric(a) && is_numeric(b))
+}
+
+impl QueryContent {
+    /// Checks the query against `schema`, e.g. one from `RecordQueriable::infer_s3_schema`.
+    ///
+    /// Columns the schema doesn't have, which S3 Select would silently read as MISSING, and
+    /// comparisons between types that never match are errors. The first problem found is
+    /// returned, expressions the schema can't type, e.g. under an `Any` field, pass.
+    pub fn check(&self, schema: &Schema) -> Result<(), ValidationError> {
+        let record = FieldType::Struct(schema.fields.clone());
+        let root = match &self.path {
+            None => &record,
+            Some(path) => {
+                // `S3Object[*]` steps into the document, not into an array of it
+                let start = path
+                    .iter()
+                    .take_while(|step| matches!(step, Path::Index(_) | Path::WildCardIndex))
+                    .count();
+                match resolve(&record, &path[start..]) {
+                    Ok(Some(root)) => root,
+                    Ok(None) => return Ok(()),
+                    Err(()) => {
+                        return Err(ValidationError::UnknownColumn(format!(
+                            "S3Object{}",
+                            Path::to_sql(path)
+                        )))
+                    }
+                }
+            }
+        };
+
+        // ORDER BY can name output fields instead of columns
+        let aliases = self
+            .select
+            .iter()
+            .filter_map(|select| match select {
+                Select::As(_, alias) => Some(alias.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<&str>>();
+        let mut unknown = None;
+        let mut check_column = |e: &Expr| match e {
+            Expr::Column(path) if unknown.is_none() && resolve(root, path).is_err() => {
+                unknown = Some(e.to_sql())
+            }
+            _ => (),
+        };
+        self.select
+            .iter()
+            .for_each(|select| select.visit(&mut check_column));
+        if let Some(clause) = &self.clauses {
+            clause.visit(&mut check_column);
+        }
+        self.group_by
+            .iter()
+            .for_each(|e| e.visit(&mut check_column));
+        for (e, _) in &self.order_by {
+            match e {
+                Expr::Column(path) if matches!(path.as_slice(), [Path::Name(name)] if aliases.contains(&name.as_str())) =>
+                    {}
+                e => e.visit(&mut check_column),
+            }
+        }
+        if let Some(column) = unknown {
+            return Err(ValidationError::UnknownColumn(column));
+        }
+
+        for select in &self.select {
+            check_select(select, root)?;
+        }
+        match &self.clauses {
+            Some(clause) => check_clause(clause, root),
+            None => Ok(()),
+        }
+    }
+}
+
+// type of `e` when it's a column, a literal or a function with a fixed result type
+fn expr_type(e: &Expr, root: &FieldType) -> Option<FieldType> {
+    match e {
+        Expr::Column(path) => resolve(root, path).ok().flatten().cloned(),
+        Expr::Literal(value) => literal_type(value),
+        Expr::Cast(_, cast) => Some(cast_type(cast)),
+        Expr::CharLength(_) | Expr::Extract(..) | Expr::DateDiff(..) => Some(FieldType::Int),
+        Expr::Lower(_)
+        | Expr::Upper(_)
+        | Expr::Substring(..)
+        | Expr::Trim(..)
+        | Expr::Concat(..)
+        | Expr::ToString(..) => Some(FieldType::String),
+        Expr::ToTimestamp(_) | Expr::UtcNow | Expr::DateAdd(..) => Some(FieldType::Timestamp),
+        _ => None,
+    }
+}
+
+fn check_select(select: &Select, root: &FieldType) -> Result<(), ValidationError> {
+    match select {
+        Select::Sum(e) | Select::Avg(e) => match expr_type(e, root) {
+            Some(found) if !comparable(&found, &FieldType::Float) => {
+                Err(ValidationError::TypeMismatch {
+                    expr: select.to_sql(),
+                    found,
+                    expected: FieldType::Float,
+                })
+            }
+            _ => Ok(()),
+        },
+        Select::As(select, _) => check_select(select, root),
+        _ => Ok(()),
+    }
+}
+
+fn check_clause(clause: &Clause, root: &FieldType) -> Result<(), ValidationError> {
+    let compare = |a: &Expr, b: &Expr| match (expr_type(a, root), expr_type(b, root)) {
+        (Some(found), Some(expected)) if !comparable(&found, &expected) => {
+            Err(ValidationError::TypeMismatch {
+                expr: clause.clone().to_where(),
+                found,
+                expected,
+            })
+        }
+        _ => Ok(()),
+    };
+
+    match clause {
+        Clause::G(a, b)
+        | Clause::L(a, b)
+        | Clause::E(a, b)
+        | Clause::GE(a, b)
+        | Clause::LE(a, b)
+        | Clause::NotE(a, b) => compare(a, b),
+        Clause::Between(a, low, high) | Clause::NotBetween(a, low, high) => {
+            compare(a, low)?;
+            compare(a, high)
+        }
+        Clause::In(a, values) | Clause::NotIn(a, values) => {
+            values.iter().try_for_each(|value| compare(a, value))
+        }
+        Clause::Like(a, pattern, _) => {
+            compare(a, &Expr::Literal(Value::String(String::new())))?;
+            compare(a, pattern)
+        }
+        Clause::And(a, b) | Clause::Or(a, b) => {
+            check_clause(a, root)?;
+            check_clause(b, root)
+        }
+        Clause::Not(clause) => check_clause(clause, root),
+        Clause::IsNull(_) | Clause::IsNotNull(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod schema_test {
+    use super::*;
+    use crate::query::Order;
+    use serde_json::json;
+
+    fn records(values: Vec<Json>) -> Vec<Record> {
+        values
+            .into_iter()
+            .map(|value| match value {
+                Json::Object(record) => record,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn people() -> Schema {
+        Schema::infer(&records(vec![
+            json!({"id": 1, "name": "ana", "score": 1.5, "address": {"city": "lyon"}, "tags": ["a"]}),
+            json!({"id": 2, "name": null, "score": 2, "address": {"city": "oslo", "zip": "0150"}, "tags": []}),
+            json!({"id": 3, "extra": true}),
+        ]))
+    }
+
+    #[test]
+    fn infer() {
+        assert_eq!(
+            people(),
+            Schema::new(vec![
+                Field::new(
+                    "address",
+                    FieldType::Struct(vec![
+                        Field::new("city", FieldType::String, false),
+                        Field::new("zip", FieldType::String, true),
+                    ]),
+                    true
+                ),
+                Field::new("id", FieldType::Int, false),
+                Field::new("name", FieldType::String, true),
+                Field::new("score", FieldType::Float, true),
+                Field::new("tags", FieldType::List(Box::new(FieldType::String)), true),
+                Field::new("extra", FieldType::Bool, true),
+            ])
+        );
+
+        let mixed = Schema::infer(&records(vec![json!({"a": 1}), json!({"a": "1"})]));
+        assert_eq!(mixed.fields[0].field_type, FieldType::Any);
+    }
+
+    #[test]
+    fn unknown_columns() {
+        let schema = people();
+        let query = |select: Vec<Expr>| {
+            QueryContent::select(vec![Select::Elements(select)]).from("bucket", "key")
+        };
+
+        assert_eq!(
+            query(vec![
+                Expr::column("id"),
+                Expr::column("Name"),
+                Expr::column("address.city"),
+                Expr::column("tags[0]")
+            ])
+            .check(&schema),
+            Ok(())
+        );
+        assert_eq!(
+            query(vec![Expr::column("nmae")]).check(&schema),
+            Err(ValidationError::UnknownColumn("s.nmae".to_string()))
+        );
+        assert_eq!(
+            query(vec![Expr::column("id")])
+                .where_clause(Clause::IsNotNull(Expr::column("address.country")))
+                .check(&schema),
+            Err(ValidationError::UnknownColumn(
+                "s.address.country".to_string()
+            ))
+        );
+        assert_eq!(
+            query(vec![Expr::column("id.value")]).check(&schema),
+            Err(ValidationError::UnknownColumn("s.id['value']".to_string()))
+        );
+        assert_eq!(
+            query(vec![Expr::column("city")])
+                .from_path(vec![Path::WildCardIndex, Path::Name("address".to_string())])
+                .check(&schema),
+            Ok(())
+        );
+        assert_eq!(
+            QueryContent::select(vec![Select::Count(Expr::Wildcard).alias("total")])
+                .from("bucket", "key")
+                .group_by(vec![Expr::column("name")])
+                .order_by(Expr::column("total"), Order::Desc)
+                .check(&schema),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn type_mismatches() {
+        let schema = people();
+        let query = |clause: Clause| {
+            QueryContent::select(vec![Select::Elements(vec![Expr::column("id")])])
+                .from("bucket", "key")
+                .where_clause(clause)
+        };
+
+        assert_eq!(
+            query(Clause::G(Expr::column("name"), 30.into())).check(&schema),
+            Err(ValidationError::TypeMismatch {
+                expr: "s.name > 30".to_string(),
+                found: FieldType::String,
+                expected: FieldType::Int,
+            })
+        );
+        assert_eq!(
+            query(
+                Clause::G(Expr::column("score"), 30.into()).and(Clause::Like(
+                    Expr::column("name"),
+                    "a%".into(),
+                    None
+                ))
+            )
+            .check(&schema),
+            Ok(())
+        );
+        assert_eq!(
+            query(Clause::G(
+                Expr::column("name").cast(CastType::Int),
+                30.into()
+            ))
+            .check(&schema),
+            Ok(())
+        );
+        assert!(query(Clause::Like(Expr::column("id"), "1%".into(), None))
+            .check(&schema)
+            .is_err());
+        assert!(
+            query(Clause::In(Expr::column("id"), vec![1.into(), "2".into()]))
+                .check(&schema)
+                .is_err()
+        );
+        assert!(
+            QueryContent::select(vec![Select::Sum(Expr::column("address.city"))])
+                .from("bucket", "key")
+                .check(&schema)
+                .is_err()
+        );
+    }
+}
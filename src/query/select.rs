@@ -0,0 +1,405 @@
+use async_trait::async_trait;
+use futures::future::ready;
+use futures::stream::{self, Stream, StreamExt};
+use rusoto_core::param::{Params, ServiceParams};
+use rusoto_core::request::BufferedHttpResponse;
+use rusoto_core::signature::SignedRequest;
+use rusoto_core::{Client, Region, RusotoError};
+use rusoto_s3::{
+    GetObjectRequest, HeadObjectRequest, ListObjectsV2Request, S3Client, SelectObjectContentError,
+    SelectObjectContentRequest, SelectObjectContentRequestSerializer, S3,
+};
+use std::io;
+use tokio::io::AsyncReadExt;
+use xml::EventWriter;
+
+use crate::encryption::CustomerKey;
+use crate::S3_XMLNS;
+
+use super::objects::{QueryObject, RecordQueriable, Records};
+use super::stream::{EventDecoder, RecordDecoder, SelectEvent};
+use super::{
+    select_request, CompressionType, InputObjectFormat, ObjectTarget, OutputObjectFormat,
+    QueryContent, QueryError, ScanRange,
+};
+
+/// Runs S3 Select and decodes its event stream into records.
+///
+/// `rusoto_s3` 0.45 sends `SelectObjectContent` but can't read its response, so this client signs
+/// the same request and reads the response itself.
+#[derive(Clone)]
+pub struct SelectClient {
+    client: Client,
+    region: Region,
+}
+
+impl SelectClient {
+    pub fn new(region: Region) -> Self {
+        SelectClient {
+            client: Client::shared(),
+            region,
+        }
+    }
+
+    #[cfg(feature = "auth")]
+    pub fn new_with<P, D>(request_dispatcher: D, credentials_provider: P, region: Region) -> Self
+    where
+        P: rusoto_credential::ProvideAwsCredentials + Send + Sync + 'static,
+        D: rusoto_core::DispatchSignedRequest + Send + Sync + 'static,
+    {
+        SelectClient {
+            client: Client::new_with(credentials_provider, request_dispatcher),
+            region,
+        }
+    }
+
+    /// `S3Client` sharing this client's credentials and dispatcher.
+    pub fn s3_client(&self) -> S3Client {
+        S3Client::new_with_client(self.client.clone(), self.region.clone())
+    }
+
+    /// Signs and sends a bucket `subresource` request `rusoto_s3` 0.45 can't express, e.g.
+    /// `?encryption` with `BucketKeyEnabled`. Failed answers go through `error`.
+    pub(crate) async fn send_bucket_request<E>(
+        &self,
+        method: &str,
+        bucket: &str,
+        subresource: &str,
+        payload: Option<Vec<u8>>,
+        error: fn(BufferedHttpResponse) -> RusotoError<E>,
+    ) -> Result<BufferedHttpResponse, RusotoError<E>> {
+        let request_uri = format!("/{}", bucket);
+        let mut request = SignedRequest::new(method, "s3", &self.region, &request_uri);
+
+        let mut params = Params::new();
+        params.put_key(subresource);
+        request.set_params(params);
+        if payload.is_some() {
+            request.set_payload(payload);
+            request.set_content_md5_header();
+        }
+
+        let mut response = self.client.sign_and_dispatch(request).await?;
+        let response = response.buffer().await.map_err(RusotoError::HttpDispatch)?;
+        if !response.status.is_success() {
+            return Err(error(response));
+        }
+
+        Ok(response)
+    }
+
+    async fn select(
+        &self,
+        input: SelectObjectContentRequest,
+        records: RecordDecoder,
+    ) -> Result<Records, QueryError> {
+        let request_uri = format!("/{}/{}", input.bucket, input.key);
+        let mut request = SignedRequest::new("POST", "s3", &self.region, &request_uri);
+
+        request.add_optional_header(
+            "x-amz-server-side-encryption-customer-algorithm",
+            input.sse_customer_algorithm.as_ref(),
+        );
+        request.add_optional_header(
+            "x-amz-server-side-encryption-customer-key",
+            input.sse_customer_key.as_ref(),
+        );
+        request.add_optional_header(
+            "x-amz-server-side-encryption-customer-key-MD5",
+            input.sse_customer_key_md5.as_ref(),
+        );
+        let mut params = Params::new();
+        params.put_key("select");
+        params.put("select-type", "2");
+        request.set_params(params);
+
+        let mut writer = EventWriter::new(Vec::new());
+        SelectObjectContentRequestSerializer::serialize(
+            &mut writer,
+            "SelectObjectContentRequest",
+            &input,
+            S3_XMLNS,
+        )
+        .map_err(|e| QueryError::Invalid(e.to_string()))?;
+        request.set_payload(Some(writer.into_inner()));
+
+        let mut response = self
+            .client
+            .sign_and_dispatch(request)
+            .await
+            .map_err(|e| QueryError::Select(Box::new(RusotoError::from(e))))?;
+        if !response.status.is_success() {
+            let response = response
+                .buffer()
+                .await
+                .map_err(|e| QueryError::Select(Box::new(RusotoError::HttpDispatch(e))))?;
+            return Err(QueryError::Select(Box::new(
+                SelectObjectContentError::from_response(response),
+            )));
+        }
+
+        Ok(decode_records(response.body, records))
+    }
+}
+
+/// Records in an event stream body, the stream ends after the first error.
+pub(crate) fn decode_records<S, B>(body: S, mut records: RecordDecoder) -> Records
+where
+    S: Stream<Item = Result<B, io::Error>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+{
+    let mut events = EventDecoder::default();
+    let mut ended = false;
+
+    body.map(Some)
+        .chain(stream::once(ready(None))) // `None` marks the end of the body
+        .flat_map(move |chunk| {
+            let mut decoded = Vec::new();
+            match chunk {
+                Some(Ok(chunk)) => match events.push(chunk.as_ref()) {
+                    Ok(chunk_events) => {
+                        for event in chunk_events {
+                            match event {
+                                SelectEvent::Records(payload) => match records.push(&payload) {
+                                    Ok(payload) => decoded.extend(payload.into_iter().map(Ok)),
+                                    Err(e) => decoded.push(Err(e)),
+                                },
+                                SelectEvent::Failed { code, message } => {
+                                    decoded.push(Err(QueryError::Failed { code, message }))
+                                }
+                                SelectEvent::End => ended = true,
+                                SelectEvent::Other(_) => (),
+                            }
+                        }
+                    }
+                    Err(e) => decoded.push(Err(e)),
+                },
+                Some(Err(e)) => {
+                    decoded.push(Err(QueryError::Select(Box::new(RusotoError::from(e)))))
+                }
+                None => {
+                    match events.finish().and_then(|_| records.finish()) {
+                        Ok(rest) => decoded.extend(rest.into_iter().map(Ok)),
+                        Err(e) => decoded.push(Err(e)),
+                    }
+                    if !ended {
+                        decoded.push(Err(QueryError::Decode(
+                            "event stream ended without an `End` event".to_string(),
+                        )));
+                    }
+                }
+            }
+            stream::iter(decoded)
+        })
+        .scan(false, |failed, record| {
+            if *failed {
+                return ready(None);
+            }
+            *failed = record.is_err();
+            ready(Some(record))
+        })
+        .boxed()
+}
+
+#[async_trait]
+impl RecordQueriable for SelectClient {
+    async fn select_s3_records(
+        &self,
+        query: QueryContent,
+        body_compression: CompressionType,
+        input_serialization: InputObjectFormat,
+        output_serialization: OutputObjectFormat,
+    ) -> Result<Records, QueryError> {
+        query.validate(&input_serialization, &body_compression)?;
+        let expression = query.build().map_err(QueryError::Invalid)?;
+        let (key, bucket) = match query.from.clone() {
+            Some((ObjectTarget::Key(key), bucket)) => (key, bucket),
+            _ => {
+                return Err(QueryError::Invalid(
+                    "S3 Select reads a single object, use `query_s3_objects`".to_string(),
+                ))
+            }
+        };
+
+        let records = RecordDecoder::new(&output_serialization, &query.select)?;
+        let input = select_request(
+            &query,
+            expression,
+            bucket,
+            key,
+            body_compression,
+            input_serialization,
+            output_serialization,
+        );
+
+        self.select(input, records).await
+    }
+
+    async fn s3_object_size(
+        &self,
+        bucket: String,
+        key: String,
+        customer_key: Option<CustomerKey>,
+    ) -> Result<i64, QueryError> {
+        let head = HeadObjectRequest {
+            bucket,
+            key,
+            sse_customer_algorithm: customer_key.as_ref().map(CustomerKey::algorithm),
+            sse_customer_key: customer_key.as_ref().map(CustomerKey::key),
+            sse_customer_key_md5: customer_key.as_ref().map(CustomerKey::key_md5),
+            ..Default::default()
+        };
+        let output = self
+            .s3_client()
+            .head_object(head)
+            .await
+            .map_err(|e| QueryError::Head(Box::new(e)))?;
+
+        Ok(output.content_length.unwrap_or_default())
+    }
+
+    async fn s3_object_range(
+        &self,
+        bucket: String,
+        key: String,
+        range: ScanRange,
+        customer_key: Option<CustomerKey>,
+    ) -> Result<Vec<u8>, QueryError> {
+        let get = GetObjectRequest {
+            bucket,
+            key,
+            range: Some(range.to_header()),
+            sse_customer_algorithm: customer_key.as_ref().map(CustomerKey::algorithm),
+            sse_customer_key: customer_key.as_ref().map(CustomerKey::key),
+            sse_customer_key_md5: customer_key.as_ref().map(CustomerKey::key_md5),
+            ..Default::default()
+        };
+        let output = self
+            .s3_client()
+            .get_object(get)
+            .await
+            .map_err(|e| QueryError::Get(Box::new(e)))?;
+
+        let mut bytes = Vec::new();
+        if let Some(body) = output.body {
+            body.into_async_read()
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(QueryError::Io)?;
+        }
+        Ok(bytes)
+    }
+
+    async fn list_s3_query_objects(
+        &self,
+        bucket: String,
+        prefix: String,
+    ) -> Result<Vec<QueryObject>, QueryError> {
+        let s3 = self.s3_client();
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let list = ListObjectsV2Request {
+                bucket: bucket.clone(),
+                prefix: Some(prefix.clone()),
+                continuation_token,
+                ..Default::default()
+            };
+            let output = s3
+                .list_objects_v2(list)
+                .await
+                .map_err(|e| QueryError::List(Box::new(e)))?;
+
+            objects.extend(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|object| {
+                        Some(QueryObject {
+                            key: object.key?,
+                            size: object.size.unwrap_or_default(),
+                        })
+                    }),
+            );
+
+            continuation_token = output.next_continuation_token;
+            if continuation_token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod select_test {
+    use super::*;
+    use crate::query::stream::stream_test::{end, message, records};
+    use crate::query::stream::Record;
+    use futures::TryStreamExt;
+
+    fn body(chunks: Vec<Vec<u8>>) -> impl Stream<Item = Result<Vec<u8>, io::Error>> {
+        stream::iter(chunks.into_iter().map(Ok))
+    }
+
+    #[tokio::test]
+    async fn records_until_end() {
+        let mut bytes = records("{\"name\":\"india\"}\n{\"na");
+        bytes.extend(records("me\":\"china\"}\n"));
+        bytes.extend(end());
+        let chunks = bytes.chunks(11).map(<[u8]>::to_vec).collect();
+
+        let decoded = decode_records(body(chunks), RecordDecoder::json(None))
+            .try_collect::<Vec<Record>>()
+            .await
+            .unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[1]["name"], "china");
+    }
+
+    #[tokio::test]
+    async fn missing_end() {
+        let decoded = decode_records(
+            body(vec![records("{\"a\":1}\n")]),
+            RecordDecoder::json(None),
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(decoded.len(), 2);
+        assert!(decoded[0].is_ok());
+        assert!(matches!(decoded[1], Err(QueryError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn stops_at_errors() {
+        let error = message(
+            &[
+                (":message-type", "error"),
+                (":error-code", "InvalidCompressionFormat"),
+                (
+                    ":error-message",
+                    "GZIP is not applicable to the queried object",
+                ),
+            ],
+            b"",
+        );
+
+        let decoded = decode_records(
+            body(vec![
+                records("{\"a\":1}\n"),
+                error,
+                records("{\"a\":2}\n"),
+                end(),
+            ]),
+            RecordDecoder::json(None),
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(decoded.len(), 2);
+        assert!(decoded[1].as_ref().unwrap_err().is_format_mismatch());
+    }
+}
@@ -0,0 +1,589 @@
+use serde_json::{Map, Value as Json};
+
+use super::local::csv_names;
+use super::{CsvOutput, OutputObjectFormat, QueryError, Select};
+
+/// A record returned by S3 Select, CSV input without headers comes back keyed `_1`, `_2`, ...
+/// with every field a string. CSV output rows are keyed as `CsvOutput` describes.
+pub type Record = Map<String, Json>;
+
+// The prelude holds the total and headers length followed by its own checksum
+const PRELUDE_LENGTH: usize = 12;
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Events S3 Select sends back, `Stats`, `Progress` and `Cont` are only consumed to keep the stream going.
+#[derive(Debug, PartialEq)]
+pub(crate) enum SelectEvent {
+    Records(Vec<u8>),
+    End,
+    Failed { code: String, message: String }, // error sent after the response started
+    Other(String),
+}
+
+/// Splits an `application/vnd.amazon.eventstream` body into events.
+#[derive(Default)]
+pub(crate) struct EventDecoder {
+    buffer: Vec<u8>,
+}
+
+impl EventDecoder {
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Result<Vec<SelectEvent>, QueryError> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while let Some((event, length)) = decode_message(&self.buffer[offset..])? {
+            events.push(event);
+            offset += length;
+        }
+        self.buffer.drain(..offset);
+
+        Ok(events)
+    }
+
+    pub(crate) fn finish(&self) -> Result<(), QueryError> {
+        if self.buffer.is_empty() {
+            Ok(())
+        } else {
+            Err(QueryError::Decode(format!(
+                "event stream ended in the middle of a message, {} bytes left",
+                self.buffer.len()
+            )))
+        }
+    }
+}
+
+fn decode_message(bytes: &[u8]) -> Result<Option<(SelectEvent, usize)>, QueryError> {
+    if bytes.len() < PRELUDE_LENGTH {
+        return Ok(None);
+    }
+
+    let total_length = read_u32(&bytes[0..4]) as usize;
+    let headers_length = read_u32(&bytes[4..8]) as usize;
+    if total_length < PRELUDE_LENGTH + headers_length + CHECKSUM_LENGTH {
+        return Err(QueryError::Decode(format!(
+            "invalid event message length {}",
+            total_length
+        )));
+    }
+    if bytes.len() < total_length {
+        return Ok(None);
+    }
+    if crc32(&bytes[0..8]) != read_u32(&bytes[8..12]) {
+        return Err(QueryError::Decode(
+            "event prelude checksum mismatch".to_string(),
+        ));
+    }
+    let message_end = total_length - CHECKSUM_LENGTH;
+    if crc32(&bytes[..message_end]) != read_u32(&bytes[message_end..total_length]) {
+        return Err(QueryError::Decode(
+            "event message checksum mismatch".to_string(),
+        ));
+    }
+
+    let headers_end = PRELUDE_LENGTH + headers_length;
+    let headers = decode_headers(&bytes[PRELUDE_LENGTH..headers_end])?;
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let payload = &bytes[headers_end..message_end];
+
+    let event = match header(":message-type") {
+        Some("error") => SelectEvent::Failed {
+            code: header(":error-code").unwrap_or_default().to_string(),
+            message: header(":error-message").unwrap_or_default().to_string(),
+        },
+        Some("exception") => SelectEvent::Failed {
+            code: header(":exception-type").unwrap_or_default().to_string(),
+            message: String::from_utf8_lossy(payload).to_string(),
+        },
+        _ => match header(":event-type") {
+            Some("Records") => SelectEvent::Records(payload.to_vec()),
+            Some("End") => SelectEvent::End,
+            Some(other) => SelectEvent::Other(other.to_string()),
+            None => {
+                return Err(QueryError::Decode(
+                    "event message without `:event-type` header".to_string(),
+                ))
+            }
+        },
+    };
+
+    Ok(Some((event, total_length)))
+}
+
+// Only string headers matter to S3 Select, other values are skipped by their encoded length
+fn decode_headers(mut bytes: &[u8]) -> Result<Vec<(String, String)>, QueryError> {
+    let truncated = || QueryError::Decode("truncated event header".to_string());
+    let mut headers = Vec::new();
+
+    while !bytes.is_empty() {
+        let name_length = bytes[0] as usize;
+        let name = bytes.get(1..1 + name_length).ok_or_else(truncated)?;
+        let value_type = *bytes.get(1 + name_length).ok_or_else(truncated)?;
+        bytes = &bytes[2 + name_length..];
+
+        let value_length = match value_type {
+            0 | 1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            6 | 7 => {
+                let length = bytes.get(0..2).ok_or_else(truncated)?;
+                2 + u16::from_be_bytes([length[0], length[1]]) as usize
+            }
+            other => {
+                return Err(QueryError::Decode(format!(
+                    "unknown event header type {}",
+                    other
+                )))
+            }
+        };
+        let value = bytes.get(..value_length).ok_or_else(truncated)?;
+
+        if value_type == 7 {
+            headers.push((
+                String::from_utf8_lossy(name).to_string(),
+                String::from_utf8_lossy(&value[2..]).to_string(),
+            ));
+        }
+        bytes = &bytes[value_length..];
+    }
+
+    Ok(headers)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+// CRC-32 (IEEE), used by both event stream checksums
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Turns the `Records` payloads of JSON or CSV output into records, a record may span several payloads.
+pub(crate) struct RecordDecoder {
+    format: Format,
+    buffer: Vec<u8>,
+}
+
+enum Format {
+    Json { delimiter: String },
+    Csv { csv: CsvBytes, names: Vec<String> },
+}
+
+// CSV output characters, each a single byte
+struct CsvBytes {
+    field: u8,
+    quote: u8,
+    escape: u8,
+    record: u8,
+}
+
+impl RecordDecoder {
+    /// Decoder for the records of `select` in `output`.
+    pub(crate) fn new(output: &OutputObjectFormat, select: &[Select]) -> Result<Self, QueryError> {
+        match output {
+            OutputObjectFormat::JSON(delimiter) => Ok(Self::json(delimiter.clone())),
+            OutputObjectFormat::CSV(csv) => Self::csv(csv, csv_names(select)),
+        }
+    }
+
+    pub(crate) fn json(delimiter: Option<String>) -> Self {
+        RecordDecoder {
+            format: Format::Json {
+                delimiter: delimiter.unwrap_or_else(|| "\n".to_string()),
+            },
+            buffer: Vec::new(),
+        }
+    }
+
+    /// `names` are the output fields by position, later ones are named `_n`.
+    pub(crate) fn csv(csv: &CsvOutput, names: Vec<String>) -> Result<Self, QueryError> {
+        Ok(RecordDecoder {
+            format: Format::Csv {
+                csv: CsvBytes {
+                    field: csv_byte(csv.field_delimiter, "field delimiter")?,
+                    quote: csv_byte(csv.quote_character, "quote character")?,
+                    escape: csv_byte(csv.quote_escape_character, "quote escape character")?,
+                    record: csv_byte(csv.record_delimiter, "record delimiter")?,
+                },
+                names,
+            },
+            buffer: Vec::new(),
+        })
+    }
+
+    pub(crate) fn push(&mut self, payload: &[u8]) -> Result<Vec<Record>, QueryError> {
+        self.buffer.extend_from_slice(payload);
+        self.decode(false)
+    }
+
+    /// Records left once the output is over, fails if the last one is incomplete.
+    pub(crate) fn finish(&mut self) -> Result<Vec<Record>, QueryError> {
+        let records = self.decode(true)?;
+
+        if self.buffer.is_empty() {
+            Ok(records)
+        } else {
+            Err(QueryError::Decode(format!(
+                "incomplete record at the end of the output: {}",
+                String::from_utf8_lossy(&self.buffer)
+            )))
+        }
+    }
+
+    fn decode(&mut self, last: bool) -> Result<Vec<Record>, QueryError> {
+        let (records, offset) = match &self.format {
+            Format::Json { delimiter } => json_records(&self.buffer, delimiter.as_bytes(), last)?,
+            Format::Csv { csv, names } => csv_records(&self.buffer, csv, names, last)?,
+        };
+        self.buffer.drain(..offset);
+
+        Ok(records)
+    }
+}
+
+// Records at the start of `bytes` and the length they take
+fn json_records(
+    bytes: &[u8],
+    delimiter: &[u8],
+    last: bool,
+) -> Result<(Vec<Record>, usize), QueryError> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    loop {
+        offset += separator_length(&bytes[offset..], delimiter);
+        if offset == bytes.len() {
+            break;
+        }
+
+        let mut values = serde_json::Deserializer::from_slice(&bytes[offset..]).into_iter::<Json>();
+        match values.next() {
+            // a bare number at the end of a payload may continue in the next one
+            Some(Ok(value))
+                if !last && !value.is_object() && offset + values.byte_offset() == bytes.len() =>
+            {
+                break
+            }
+            Some(Ok(value)) => {
+                offset += values.byte_offset();
+                records.push(into_record(value));
+            }
+            Some(Err(e)) if e.is_eof() => break,
+            Some(Err(e)) => return Err(QueryError::Decode(e.to_string())),
+            None => break,
+        }
+    }
+
+    Ok((records, offset))
+}
+
+// Whitespace and record delimiters between two records
+fn separator_length(bytes: &[u8], delimiter: &[u8]) -> usize {
+    let mut length = 0;
+    loop {
+        let rest = &bytes[length..];
+        if !delimiter.is_empty() && rest.starts_with(delimiter) {
+            length += delimiter.len();
+        } else if rest.first().is_some_and(u8::is_ascii_whitespace) {
+            length += 1;
+        } else {
+            return length;
+        }
+    }
+}
+
+fn csv_records(
+    bytes: &[u8],
+    csv: &CsvBytes,
+    names: &[String],
+    last: bool,
+) -> Result<(Vec<Record>, usize), QueryError> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some((fields, length)) = csv_row(&bytes[offset..], csv, last) {
+        let fields = fields
+            .into_iter()
+            .map(|field| String::from_utf8(field).map_err(|e| QueryError::Decode(e.to_string())))
+            .collect::<Result<Vec<String>, QueryError>>()?;
+        records.push(csv_record(names, fields));
+        offset += length;
+    }
+
+    Ok((records, offset))
+}
+
+// Fields of the row at the start of `bytes` and its length with the record delimiter, the row
+// isn't over until the delimiter unless it's the `last` one
+fn csv_row(bytes: &[u8], csv: &CsvBytes, last: bool) -> Option<(Vec<Vec<u8>>, usize)> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let mut fields = Vec::new();
+    let mut field = Vec::new();
+    let mut quoted = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        if quoted && (byte == csv.escape || byte == csv.quote) {
+            let escaped = match next {
+                // a quote or escape at the end of a payload may be escaping the next byte
+                None if !last => return None,
+                Some(next) if byte == csv.escape && next == csv.quote => true,
+                Some(next) if byte == csv.escape && csv.escape != csv.quote => next == csv.escape,
+                _ => false,
+            };
+            if escaped {
+                field.push(bytes[i + 1]);
+                i += 2;
+                continue;
+            }
+            if byte == csv.quote {
+                quoted = false;
+            } else {
+                field.push(byte);
+            }
+        } else if quoted {
+            field.push(byte);
+        } else if byte == csv.quote {
+            quoted = true;
+        } else if byte == csv.field {
+            fields.push(std::mem::take(&mut field));
+        } else if byte == csv.record {
+            fields.push(field);
+            return Some((fields, i + 1));
+        } else {
+            field.push(byte);
+        }
+        i += 1;
+    }
+
+    if last && !quoted {
+        fields.push(field);
+        Some((fields, bytes.len()))
+    } else {
+        None
+    }
+}
+
+/// A CSV output row keyed by `names` and `_n` past them, empty fields are null.
+pub(crate) fn csv_record(names: &[String], fields: Vec<String>) -> Record {
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let name = names
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("_{}", i + 1));
+            let value = if field.is_empty() {
+                Json::Null
+            } else {
+                Json::String(field)
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+pub(crate) fn csv_byte(c: char, name: &str) -> Result<u8, QueryError> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(QueryError::Invalid(format!(
+            "CSV {} `{}` isn't a single byte character",
+            name, c
+        )))
+    }
+}
+
+/// Scalars selected without an alias come back bare, they are keyed `_1` like S3 does for columns.
+pub(crate) fn into_record(value: Json) -> Record {
+    match value {
+        Json::Object(record) => record,
+        other => {
+            let mut record = Record::new();
+            record.insert("_1".to_string(), other);
+            record
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod stream_test {
+    use super::*;
+
+    pub(crate) fn message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+        let mut encoded_headers = Vec::new();
+        for (name, value) in headers {
+            encoded_headers.push(name.len() as u8);
+            encoded_headers.extend_from_slice(name.as_bytes());
+            encoded_headers.push(7);
+            encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            encoded_headers.extend_from_slice(value.as_bytes());
+        }
+
+        let total_length = PRELUDE_LENGTH + encoded_headers.len() + payload.len() + CHECKSUM_LENGTH;
+        let mut message = Vec::new();
+        message.extend_from_slice(&(total_length as u32).to_be_bytes());
+        message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+        message.extend_from_slice(&crc32(&message).to_be_bytes());
+        message.extend_from_slice(&encoded_headers);
+        message.extend_from_slice(payload);
+        message.extend_from_slice(&crc32(&message).to_be_bytes());
+        message
+    }
+
+    pub(crate) fn records(payload: &str) -> Vec<u8> {
+        message(
+            &[
+                (":message-type", "event"),
+                (":event-type", "Records"),
+                (":content-type", "application/octet-stream"),
+            ],
+            payload.as_bytes(),
+        )
+    }
+
+    pub(crate) fn end() -> Vec<u8> {
+        message(&[(":message-type", "event"), (":event-type", "End")], b"")
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn events_across_chunks() {
+        let mut body = records("{\"a\":1}\n");
+        body.extend(message(&[(":event-type", "Stats")], b"<Stats/>"));
+        body.extend(end());
+
+        let mut decoder = EventDecoder::default();
+        let mut events = Vec::new();
+        for chunk in body.chunks(7) {
+            events.extend(decoder.push(chunk).unwrap());
+        }
+
+        assert!(decoder.finish().is_ok());
+        assert_eq!(
+            events,
+            vec![
+                SelectEvent::Records(b"{\"a\":1}\n".to_vec()),
+                SelectEvent::Other("Stats".to_string()),
+                SelectEvent::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn error_events() {
+        let body = message(
+            &[
+                (":message-type", "error"),
+                (":error-code", "JSONParsingError"),
+                (":error-message", "Unexpected token"),
+            ],
+            b"",
+        );
+
+        assert_eq!(
+            EventDecoder::default().push(&body).unwrap(),
+            vec![SelectEvent::Failed {
+                code: "JSONParsingError".to_string(),
+                message: "Unexpected token".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn corrupted_messages() {
+        let mut body = end();
+        let last = body.len() - 5;
+        body[last] ^= 1;
+
+        assert!(EventDecoder::default().push(&body).is_err());
+
+        let mut decoder = EventDecoder::default();
+        assert!(decoder.push(&end()[..10]).unwrap().is_empty());
+        assert!(decoder.finish().is_err());
+    }
+
+    #[test]
+    fn records_split_and_delimited() {
+        let mut decoder = RecordDecoder::json(Some(",".to_string()));
+        let mut records = decoder.push(b"{\"name\":\"a,b\"},{\"na").unwrap();
+        records.extend(decoder.push(b"me\":\"c\"},7").unwrap());
+        records.extend(decoder.push(b"5,").unwrap());
+        records.extend(decoder.finish().unwrap());
+        assert_eq!(
+            serde_json::to_string(&records).unwrap(),
+            r#"[{"name":"a,b"},{"name":"c"},{"_1":75}]"#
+        );
+    }
+
+    #[test]
+    fn csv_records_split_and_quoted() {
+        let names = vec!["id".to_string(), "name".to_string()];
+        let mut decoder = RecordDecoder::csv(&CsvOutput::new(), names).unwrap();
+        // the quote ending the payload may start an escaped `""`
+        let mut records = decoder.push(b"1,\"a,\"").unwrap();
+        assert!(records.is_empty());
+        records.extend(decoder.push(b"\"b\"\n2,").unwrap());
+        records.extend(decoder.push(b"\n3,c,x").unwrap());
+        records.extend(decoder.finish().unwrap());
+        assert_eq!(
+            serde_json::to_string(&records).unwrap(),
+            r#"[{"id":"1","name":"a,\"b"},{"id":"2","name":null},{"_3":"x","id":"3","name":"c"}]"#
+        );
+
+        let escaped = CsvOutput::new()
+            .field_delimiter('|')
+            .quote_escape_character('\\')
+            .record_delimiter(';');
+        let mut decoder = RecordDecoder::csv(&escaped, Vec::new()).unwrap();
+        let records = decoder.push(b"\"a\\\"|b\"|c;").unwrap();
+        assert_eq!(
+            serde_json::to_string(&records).unwrap(),
+            r#"[{"_1":"a\"|b","_2":"c"}]"#
+        );
+
+        let mut decoder = RecordDecoder::csv(&CsvOutput::new(), Vec::new()).unwrap();
+        assert!(decoder.push(b"\"open\n").unwrap().is_empty());
+        assert!(decoder.finish().is_err());
+    }
+
+    #[test]
+    fn incomplete_records() {
+        let mut decoder = RecordDecoder::json(None);
+
+        assert_eq!(decoder.push(b"{\"a\":1}\n{\"a\":").unwrap().len(), 1);
+        assert!(decoder.finish().is_err());
+
+        let mut decoder = RecordDecoder::json(None);
+        assert!(decoder.push(b"42").unwrap().is_empty());
+        assert_eq!(decoder.finish().unwrap().len(), 1);
+        assert!(RecordDecoder::json(None).push(b"{\"a\" 1}\n").is_err());
+    }
+}
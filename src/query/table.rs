@@ -0,0 +1,635 @@
+use arrow_schema::{DataType, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::Session;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
+use datafusion::execution::TaskContext;
+use datafusion::logical_expr::expr::{Between, InList, Like};
+use datafusion::logical_expr::{
+    BinaryExpr, Expr as LogicalExpr, Operator, TableProviderFilterPushDown,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::{ExecutionPlan, SendableRecordBatchStream};
+use datafusion::scalar::ScalarValue;
+use futures::channel::mpsc;
+use futures::future::ready;
+use futures::stream::{StreamExt, TryStreamExt};
+use futures::SinkExt;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use super::batches::{record_batches, ArrowOptions};
+use super::objects::{ObjectRecord, QueryOptions, RecordQueriable};
+use super::path::Path;
+use super::{
+    CastType, Clause, CompressionType, CsvOutput, Expr, InputObjectFormat, JsonType,
+    OutputObjectFormat, QueryContent, Select, Value,
+};
+
+/// DataFusion table over every object under a prefix, read with `RecordQueriable::query_s3_objects`.
+///
+/// Queries run on the given tokio 0.2 runtime, `SelectClient` requests need its reactor while
+/// DataFusion polls its streams on tokio 1.
+///
+/// Projections, LIMIT and the filters a `Clause` can express run in S3 Select, everything else,
+/// e.g. joins, GROUP BY and ORDER BY, runs in DataFusion. Pushed filters are applied again by
+/// DataFusion, so S3 Select only has to drop records that can't match.
+pub struct S3Table<C> {
+    client: Arc<C>,
+    bucket: String,
+    prefix: String,
+    schema: SchemaRef,
+    input_serialization: InputObjectFormat,
+    body_compression: CompressionType,
+    options: QueryOptions,
+    runtime: tokio::runtime::Handle,
+}
+
+impl<C> S3Table<C> {
+    /// Table of the uncompressed JSON Lines objects under `prefix`, see `input` for other formats.
+    pub fn new(
+        client: Arc<C>,
+        bucket: &str,
+        prefix: &str,
+        schema: SchemaRef,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        S3Table {
+            client,
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            schema,
+            input_serialization: InputObjectFormat::JSON(JsonType::Lines),
+            body_compression: CompressionType::NONE,
+            options: QueryOptions::default(),
+            runtime,
+        }
+    }
+
+    /// Format of the objects, CSV columns are named by the schema fields and empty cells are null.
+    pub fn input(
+        mut self,
+        input_serialization: InputObjectFormat,
+        body_compression: CompressionType,
+    ) -> Self {
+        self.input_serialization = input_serialization;
+        self.body_compression = body_compression;
+        self
+    }
+
+    pub fn options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // `columns` are indices in the table schema
+    fn query(
+        &self,
+        columns: &[usize],
+        filters: &[LogicalExpr],
+        limit: Option<usize>,
+    ) -> QueryContent {
+        let projection = columns
+            .iter()
+            .map(|i| column(self.schema.field(*i).name()))
+            .collect::<Vec<Expr>>();
+        let clauses = filters
+            .iter()
+            .filter_map(|filter| self.to_clause(filter))
+            .collect::<Vec<Clause>>();
+
+        let mut query = QueryContent::select(vec![Select::Elements(projection)])
+            .from_prefix(&self.bucket, &self.prefix);
+        if let Some(clause) = Clause::all(clauses) {
+            query = query.where_clause(clause);
+        }
+        match limit {
+            Some(limit) if limit > 0 => query.limit(limit),
+            _ => query,
+        }
+    }
+
+    fn to_clause(&self, filter: &LogicalExpr) -> Option<Clause> {
+        let clause = match filter {
+            LogicalExpr::BinaryExpr(BinaryExpr { left, op, right }) => match op {
+                Operator::And => self.to_clause(left)?.and(self.to_clause(right)?),
+                Operator::Or => self.to_clause(left)?.or(self.to_clause(right)?),
+                Operator::Eq => Clause::E(self.to_expr(left)?, self.to_expr(right)?),
+                Operator::NotEq => Clause::NotE(self.to_expr(left)?, self.to_expr(right)?),
+                Operator::Lt => Clause::L(self.to_expr(left)?, self.to_expr(right)?),
+                Operator::LtEq => Clause::LE(self.to_expr(left)?, self.to_expr(right)?),
+                Operator::Gt => Clause::G(self.to_expr(left)?, self.to_expr(right)?),
+                Operator::GtEq => Clause::GE(self.to_expr(left)?, self.to_expr(right)?),
+                _ => return None,
+            },
+            LogicalExpr::Not(e) => Clause::Not(Box::new(self.to_clause(e)?)),
+            // missing fields are null in the batches, `IS NULL` is true for both in S3 Select
+            LogicalExpr::IsNull(e) => Clause::E(self.to_expr(e)?, Value::Null.into()),
+            LogicalExpr::IsNotNull(e) => Clause::NotE(self.to_expr(e)?, Value::Null.into()),
+            LogicalExpr::Between(Between {
+                expr,
+                negated,
+                low,
+                high,
+            }) => {
+                let (e, low, high) = (self.to_expr(expr)?, self.to_expr(low)?, self.to_expr(high)?);
+                if *negated {
+                    Clause::NotBetween(e, low, high)
+                } else {
+                    Clause::Between(e, low, high)
+                }
+            }
+            LogicalExpr::InList(InList {
+                expr,
+                list,
+                negated,
+            }) => {
+                let e = self.to_expr(expr)?;
+                let list = list
+                    .iter()
+                    .map(|value| self.to_expr(value))
+                    .collect::<Option<Vec<Expr>>>()?;
+                if *negated {
+                    Clause::NotIn(e, list)
+                } else {
+                    Clause::In(e, list)
+                }
+            }
+            LogicalExpr::Like(Like {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+                case_insensitive: false,
+            }) => {
+                let like = Clause::Like(self.to_expr(expr)?, self.to_expr(pattern)?, *escape_char);
+                if *negated {
+                    Clause::Not(Box::new(like))
+                } else {
+                    like
+                }
+            }
+            _ => return None,
+        };
+
+        Some(clause)
+    }
+
+    fn to_expr(&self, e: &LogicalExpr) -> Option<Expr> {
+        match e {
+            LogicalExpr::Column(c) => {
+                let field = self.schema.field_with_name(&c.name).ok()?;
+                let e = column(field.name());
+                // CSV fields are strings, compare them as the type the schema gives them. Empty
+                // cells of nullable fields are null in the batches, S3 Select fails to cast them
+                match (&self.input_serialization, field.data_type()) {
+                    (InputObjectFormat::CSV(_), DataType::Utf8) => Some(e),
+                    (InputObjectFormat::CSV(_), data_type) if field.is_nullable() => {
+                        Some(e.null_if("").cast(cast_type(data_type)?))
+                    }
+                    (InputObjectFormat::CSV(_), data_type) => Some(e.cast(cast_type(data_type)?)),
+                    _ => Some(e),
+                }
+            }
+            LogicalExpr::Literal(value) => literal(value).map(Expr::Literal),
+            _ => None,
+        }
+    }
+}
+
+fn column(name: &str) -> Expr {
+    Expr::path(vec![Path::Name(name.to_string())])
+}
+
+fn cast_type(data_type: &DataType) -> Option<CastType> {
+    match data_type {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32 => Some(CastType::Int),
+        DataType::Float32 | DataType::Float64 => Some(CastType::Float),
+        DataType::Boolean => Some(CastType::Bool),
+        _ => None,
+    }
+}
+
+// null literals aren't pushed, `= NULL` and `IS NULL` differ
+fn literal(value: &ScalarValue) -> Option<Value> {
+    let value = match value {
+        ScalarValue::Int8(Some(i)) => Value::Int(*i as i64),
+        ScalarValue::Int16(Some(i)) => Value::Int(*i as i64),
+        ScalarValue::Int32(Some(i)) => Value::Int(*i as i64),
+        ScalarValue::Int64(Some(i)) => Value::Int(*i),
+        ScalarValue::UInt8(Some(i)) => Value::Int(*i as i64),
+        ScalarValue::UInt16(Some(i)) => Value::Int(*i as i64),
+        ScalarValue::UInt32(Some(i)) => Value::Int(*i as i64),
+        ScalarValue::Float32(Some(f)) => Value::Float(*f as f64),
+        ScalarValue::Float64(Some(f)) => Value::Float(*f),
+        ScalarValue::Boolean(Some(b)) => Value::Bool(*b),
+        ScalarValue::Utf8(Some(s))
+        | ScalarValue::LargeUtf8(Some(s))
+        | ScalarValue::Utf8View(Some(s)) => Value::String(s.clone()),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+impl<C> fmt::Debug for S3Table<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("S3Table")
+            .field("bucket", &self.bucket)
+            .field("prefix", &self.prefix)
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<C> TableProvider for S3Table<C>
+where
+    C: RecordQueriable + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&LogicalExpr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| match self.to_clause(filter) {
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[LogicalExpr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let columns = match projection {
+            Some(columns) => columns.clone(),
+            None => (0..self.schema.fields().len()).collect(),
+        };
+        let schema = Arc::new(self.schema.project(&columns)?);
+        // S3 Select needs something to select, e.g. for `COUNT(*)`, it's dropped from the batches
+        let selected = if columns.is_empty() { vec![0] } else { columns };
+        let partition = Scan {
+            client: self.client.clone(),
+            query: self.query(&selected, filters, limit),
+            selected: Arc::new(self.schema.project(&selected)?),
+            schema: schema.clone(),
+            input_serialization: self.input_serialization.clone(),
+            body_compression: self.body_compression.clone(),
+            options: self.options.clone(),
+            runtime: self.runtime.clone(),
+        };
+
+        Ok(Arc::new(StreamingTableExec::try_new(
+            schema,
+            vec![Arc::new(partition)],
+            None,
+            vec![],
+            false,
+            limit,
+        )?))
+    }
+}
+
+// the records of a scan, as a single partition
+struct Scan<C> {
+    client: Arc<C>,
+    query: QueryContent,
+    selected: SchemaRef, // fields in the query, `schema` when it isn't empty
+    schema: SchemaRef,
+    input_serialization: InputObjectFormat,
+    body_compression: CompressionType,
+    options: QueryOptions,
+    runtime: tokio::runtime::Handle,
+}
+
+impl<C> fmt::Debug for Scan<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scan")
+            .field("query", &self.query.sql())
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+impl<C> PartitionStream for Scan<C>
+where
+    C: RecordQueriable + Send + Sync + 'static,
+{
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let (mut sender, receiver) = mpsc::channel(ctx.session_config().batch_size());
+        let client = self.client.clone();
+        let query = self.query.clone();
+        let input_serialization = self.input_serialization.clone();
+        let body_compression = self.body_compression.clone();
+        let options = self.options.clone();
+        // CSV output has empty cells null, as JSON output they're empty strings
+        let output_serialization = match input_serialization {
+            InputObjectFormat::CSV(_) => OutputObjectFormat::CSV(CsvOutput::new()),
+            _ => OutputObjectFormat::JSON(None),
+        };
+        let forward = async move {
+            let records = client
+                .query_s3_objects(
+                    query,
+                    body_compression,
+                    input_serialization,
+                    output_serialization,
+                    options,
+                )
+                .await;
+            let records = match records {
+                Ok(records) => records,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            let _ = records
+                .filter_map(|record| {
+                    ready(match record {
+                        Ok(ObjectRecord::Record { record, .. }) => Some(Ok(Ok(record))),
+                        Ok(ObjectRecord::Skipped { .. }) => None,
+                        Err(e) => Some(Ok(Err(e))),
+                    })
+                })
+                .forward(sender)
+                .await;
+        };
+
+        self.runtime.spawn(forward);
+        let records = receiver.boxed();
+
+        let options = ArrowOptions::new()
+            .schema(self.selected.clone())
+            .batch_size(ctx.session_config().batch_size());
+        let dropped = self.schema.fields().is_empty();
+        let batches = record_batches(records, options)
+            .map_err(|e| DataFusionError::External(Box::new(e)))
+            .and_then(move |batch| {
+                ready(if dropped {
+                    batch.project(&[]).map_err(DataFusionError::from)
+                } else {
+                    Ok(batch)
+                })
+            });
+
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), batches))
+    }
+}
+
+#[cfg(test)]
+mod table_test {
+    use super::*;
+    use crate::query::{CsvInput, FileHeader, SelectEmulator};
+    use arrow_array::{Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{Field, Schema};
+    use datafusion::arrow::compute::concat_batches;
+    use datafusion::logical_expr::{col, lit};
+    use datafusion::prelude::{SessionConfig, SessionContext};
+    use tokio::runtime::Handle;
+
+    fn people() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("city", DataType::Utf8, true),
+        ]))
+    }
+
+    fn emulator() -> Arc<SelectEmulator> {
+        let mut emulator = SelectEmulator::new();
+        let objects = [
+            (
+                "people/1.json",
+                "{\"id\": 1, \"name\": \"ana\", \"city\": \"lyon\"}\n{\"id\": 2, \"name\": \"bo\", \"city\": \"oslo\"}",
+            ),
+            (
+                "people/2.json",
+                "{\"id\": 3, \"name\": \"cy\", \"city\": \"lyon\"}\n{\"id\": 4, \"city\": \"rome\"}",
+            ),
+            ("cities.csv", "name,country\nlyon,fr\noslo,no\nrome,it"),
+            (
+                "towns/towns.csv",
+                "name,people\nalba,1200\nbuxy,\ncluny,4500",
+            ),
+        ];
+        for (key, body) in objects {
+            emulator
+                .put_object("bucket", key, body.as_bytes().to_vec())
+                .unwrap();
+        }
+        Arc::new(emulator)
+    }
+
+    fn context() -> SessionContext {
+        let emulator = emulator();
+        let ctx = SessionContext::new_with_config(SessionConfig::new().with_target_partitions(1));
+        ctx.register_table(
+            "people",
+            Arc::new(S3Table::new(
+                emulator.clone(),
+                "bucket",
+                "people/",
+                people(),
+                Handle::current(),
+            )),
+        )
+        .unwrap();
+        let cities = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("country", DataType::Utf8, false),
+        ]));
+        let csv = InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Use));
+        ctx.register_table(
+            "cities",
+            Arc::new(
+                S3Table::new(emulator, "bucket", "cities.csv", cities, Handle::current())
+                    .input(csv, CompressionType::NONE),
+            ),
+        )
+        .unwrap();
+        ctx
+    }
+
+    #[tokio::test]
+    async fn pushdown() {
+        let table = S3Table::new(emulator(), "bucket", "people/", people(), Handle::current());
+        let filters = vec![
+            col("id").gt(lit(1)).and(col("name").is_not_null()),
+            col("city").in_list(vec![lit("lyon"), lit("rome")], false),
+            col("name").ilike(lit("a%")),
+        ];
+
+        assert_eq!(
+            table.query(&[0, 1], &filters, Some(5)).sql(),
+            "SELECT s.id, s.name FROM S3Object s WHERE s.id > 1 AND s.name IS NOT NULL AND s.city IN ('lyon', 'rome') LIMIT 5"
+        );
+        assert_eq!(
+            table
+                .supports_filters_pushdown(&filters.iter().collect::<Vec<&LogicalExpr>>())
+                .unwrap(),
+            vec![
+                TableProviderFilterPushDown::Inexact,
+                TableProviderFilterPushDown::Inexact,
+                TableProviderFilterPushDown::Unsupported,
+            ]
+        );
+
+        let csv = InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Use));
+        let table = table.input(csv, CompressionType::NONE);
+        assert_eq!(
+            table.query(&[2], &[col("id").lt_eq(lit(2))], None).sql(),
+            "SELECT s.city FROM S3Object s WHERE CAST(s.id AS INT) <= 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_csv_cells() {
+        let towns = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("people", DataType::Int64, true),
+        ]));
+        let csv = InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Use));
+        let table = S3Table::new(emulator(), "bucket", "towns/", towns, Handle::current())
+            .input(csv, CompressionType::NONE);
+        assert_eq!(
+            table
+                .query(&[0], &[col("people").gt(lit(1000))], None)
+                .sql(),
+            "SELECT s.name FROM S3Object s WHERE CAST(NULLIF(s.people, '') AS INT) > 1000"
+        );
+
+        let ctx = SessionContext::new_with_config(SessionConfig::new().with_target_partitions(1));
+        ctx.register_table("towns", Arc::new(table)).unwrap();
+        let batches = ctx
+            .sql("SELECT name FROM towns WHERE people > 1000 ORDER BY name")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = concat(&batches);
+        let names = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            (0..names.len())
+                .map(|i| names.value(i))
+                .collect::<Vec<&str>>(),
+            vec!["alba", "cluny"]
+        );
+
+        let count = ctx
+            .sql("SELECT COUNT(people) FROM towns")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count = count[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(count.value(0), 2);
+    }
+
+    #[tokio::test]
+    async fn sql() {
+        let batches = context()
+            .sql(
+                "SELECT c.country, COUNT(*) AS people, MAX(p.id) AS last \
+                 FROM people p JOIN cities c ON p.city = c.name \
+                 WHERE p.id > 1 GROUP BY c.country ORDER BY c.country",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = concat(&batches);
+
+        let countries = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let last = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(
+            (0..countries.len())
+                .map(|i| (countries.value(i), last.value(i)))
+                .collect::<Vec<(&str, i64)>>(),
+            vec![("fr", 3), ("it", 4), ("no", 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn count_and_limit() {
+        let ctx = context();
+        let count = ctx
+            .sql("SELECT COUNT(*) FROM people WHERE name IS NULL")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count = count[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(count.value(0), 1);
+
+        let rows = ctx
+            .sql("SELECT id FROM people LIMIT 3")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap()
+            .iter()
+            .map(RecordBatch::num_rows)
+            .sum::<usize>();
+        assert_eq!(rows, 3);
+    }
+
+    fn concat(batches: &[RecordBatch]) -> RecordBatch {
+        concat_batches(&batches[0].schema(), batches).unwrap()
+    }
+}
@@ -0,0 +1,500 @@
+use std::fmt;
+
+use super::path::Path;
+use super::schema::FieldType;
+use super::{
+    build_select_element, CompressionType, Expr, FileHeader, InputObjectFormat, JsonType,
+    QueryContent, QueryError, Select,
+};
+
+/// Longest SQL expression S3 Select accepts, in bytes.
+pub const MAX_EXPRESSION_BYTES: usize = 256 * 1024;
+
+/// Why S3 Select would refuse a query, see `QueryContent::validate`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    MissingFrom,
+    EmptySelect,
+    /// A plain column next to aggregates without GROUP BY, holds the column.
+    MixedAggregates(String),
+    ZeroLimit,
+    /// The SQL sent to S3, holds its length in bytes.
+    ExpressionTooLong(usize),
+    /// Whole object compression on Parquet, which compresses its columns itself.
+    CompressedParquet(CompressionType),
+    /// `*` anywhere but as a projection or in `Count`, holds the expression around it.
+    MisplacedWildcard(String),
+    /// An `Expr::Param` without a value, see `QueryContent::bind`.
+    UnboundParam(String),
+    /// A value given to `QueryContent::bind` for a placeholder the query doesn't have.
+    UnknownParam(String),
+    /// A column the schema doesn't have, S3 Select would read it as MISSING.
+    UnknownColumn(String),
+    /// An expression mixing types that never compare, e.g. a string column with a number.
+    TypeMismatch {
+        expr: String,
+        found: FieldType,
+        expected: FieldType,
+    },
+    /// Something the input format can't have, e.g. a nested column in CSV.
+    UnsupportedForFormat {
+        feature: String,
+        format: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingFrom => write!(f, "query has no FROM object, use `QueryContent::from`"),
+            Self::EmptySelect => write!(f, "query selects nothing"),
+            Self::MixedAggregates(column) => write!(
+                f,
+                "`{}` is mixed with aggregates, aggregate it or add it to GROUP BY",
+                column
+            ),
+            Self::ZeroLimit => write!(f, "LIMIT 0 returns nothing, S3 Select refuses it"),
+            Self::ExpressionTooLong(length) => write!(
+                f,
+                "SQL expression is {} bytes, S3 Select accepts up to {}",
+                length, MAX_EXPRESSION_BYTES
+            ),
+            Self::CompressedParquet(compression) => write!(
+                f,
+                "Parquet objects can't be {:?} compressed as a whole, only their columns can",
+                compression
+            ),
+            Self::MisplacedWildcard(expr) => write!(
+                f,
+                "`*` in `{}` is only valid as a projection or in `Count`",
+                expr
+            ),
+            Self::UnboundParam(name) => write!(f, "parameter `{}` has no value, bind it", name),
+            Self::UnknownParam(name) => write!(f, "query has no parameter `{}`", name),
+            Self::UnknownColumn(column) => write!(f, "schema has no column `{}`", column),
+            Self::TypeMismatch {
+                expr,
+                found,
+                expected,
+            } => write!(f, "`{}` has {} where {} is expected", expr, found, expected),
+            Self::UnsupportedForFormat { feature, format } => {
+                write!(f, "{} isn't supported for {} input", feature, format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for QueryError {
+    fn from(e: ValidationError) -> Self {
+        QueryError::Invalid(e.to_string())
+    }
+}
+
+impl QueryContent {
+    /// Checks the query against S3 Select's limits and the rules of `input_serialization`,
+    /// the first problem found is returned.
+    pub fn validate(
+        &self,
+        input_serialization: &InputObjectFormat,
+        body_compression: &CompressionType,
+    ) -> Result<(), ValidationError> {
+        if self.from.is_none() {
+            return Err(ValidationError::MissingFrom);
+        }
+        let empty = |select: &Select| match select {
+            Select::Elements(exprs) => exprs.is_empty(),
+            Select::As(select, _) => {
+                matches!(select.as_ref(), Select::Elements(exprs) if exprs.is_empty())
+            }
+            _ => false,
+        };
+        if self.select.is_empty() || self.select.iter().any(empty) {
+            return Err(ValidationError::EmptySelect);
+        }
+        if self.limit == Some(0) {
+            return Err(ValidationError::ZeroLimit);
+        }
+        if let Some(name) = self.params().into_iter().next() {
+            return Err(ValidationError::UnboundParam(name));
+        }
+
+        let mut columns = Vec::new();
+        let mut has_aggregates = false;
+        for select in &self.select {
+            let select = match select {
+                Select::As(select, _) => select.as_ref(),
+                select => select,
+            };
+            match select {
+                Select::Elements(exprs) => {
+                    for e in exprs {
+                        if *e != Expr::Wildcard {
+                            wildcard(e, e)?;
+                        }
+                        columns.push(e);
+                    }
+                }
+                Select::Count(Expr::Wildcard) => has_aggregates = true,
+                Select::Count(e)
+                | Select::Sum(e)
+                | Select::Avg(e)
+                | Select::Max(e)
+                | Select::Min(e) => {
+                    has_aggregates = true;
+                    wildcard(e, e)?;
+                }
+                Select::As(..) => (),
+            }
+        }
+        if has_aggregates && self.group_by.is_empty() {
+            if let Some(column) = columns.first() {
+                return Err(ValidationError::MixedAggregates(column.to_sql()));
+            }
+        }
+        if let Some(clause) = &self.clauses {
+            let mut found = false;
+            clause.visit(&mut |e| found |= *e == Expr::Wildcard);
+            if found {
+                return Err(ValidationError::MisplacedWildcard(
+                    clause.clone().to_where(),
+                ));
+            }
+        }
+
+        let length = self.sql().len();
+        if length > MAX_EXPRESSION_BYTES {
+            return Err(ValidationError::ExpressionTooLong(length));
+        }
+
+        self.validate_format(input_serialization, body_compression)
+    }
+
+    fn validate_format(
+        &self,
+        input: &InputObjectFormat,
+        compression: &CompressionType,
+    ) -> Result<(), ValidationError> {
+        let format = match (input, compression) {
+            (InputObjectFormat::Parquet, CompressionType::NONE) => "Parquet",
+            (InputObjectFormat::Parquet, compression) => {
+                return Err(ValidationError::CompressedParquet(compression.clone()))
+            }
+            (InputObjectFormat::CSV(_), _) => "CSV",
+            (InputObjectFormat::JSON(JsonType::Lines), _) => "JSON Lines",
+            (InputObjectFormat::JSON(JsonType::Document), _) => "JSON Document",
+        };
+        let unsupported = |feature: String, format: &str| ValidationError::UnsupportedForFormat {
+            feature,
+            format: format.to_string(),
+        };
+
+        if self.scan_range.is_some() {
+            match (input, compression) {
+                (InputObjectFormat::JSON(JsonType::Lines), CompressionType::NONE)
+                | (InputObjectFormat::CSV(_), CompressionType::NONE) => (),
+                (_, CompressionType::NONE) => {
+                    return Err(unsupported("a scan range".to_string(), format))
+                }
+                (_, compression) => {
+                    return Err(unsupported(
+                        "a scan range".to_string(),
+                        &format!("{:?} compressed", compression),
+                    ))
+                }
+            }
+        }
+
+        let csv = match input {
+            InputObjectFormat::CSV(csv) => csv,
+            _ => return Ok(()),
+        };
+        if self.path.is_some() {
+            return Err(unsupported("a FROM path".to_string(), format));
+        }
+        // CSV records are flat, columns are named by the header or by position
+        let mut column = None;
+        let mut check = |e: &Expr| {
+            let path = match e {
+                Expr::Column(path) if column.is_none() => path,
+                _ => return,
+            };
+            let valid = match path.as_slice() {
+                [] => true,
+                [Path::Name(name)] => csv.file_header == FileHeader::Use || is_position(name),
+                _ => false,
+            };
+            if !valid {
+                column = Some(e.to_sql());
+            }
+        };
+        self.visit(&mut check);
+
+        match column {
+            Some(column) if csv.file_header == FileHeader::Use => {
+                return Err(unsupported(format!("nested column `{}`", column), format))
+            }
+            Some(column) => {
+                return Err(unsupported(
+                    format!("named column `{}` without a header line", column),
+                    format,
+                ))
+            }
+            None => (),
+        }
+
+        // CSV fields are strings, functions on numbers or timestamps need a CAST first
+        let mut function = None;
+        for select in &self.select {
+            let select = match select {
+                Select::As(select, _) => select.as_ref(),
+                select => select,
+            };
+            match select {
+                Select::Sum(e @ Expr::Column(_)) => function = Some(build_select_element("Sum", e)),
+                Select::Avg(e @ Expr::Column(_)) => function = Some(build_select_element("Avg", e)),
+                _ => (),
+            }
+            if function.is_some() {
+                break;
+            }
+        }
+        self.visit(&mut |e| {
+            if function.is_none() && uncast_column(e) {
+                function = Some(e.to_sql());
+            }
+        });
+
+        match function {
+            Some(function) => Err(unsupported(
+                format!("`{}` on a column without CAST", function),
+                format,
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// functions taking numbers or timestamps with a column as is for an argument
+fn uncast_column(e: &Expr) -> bool {
+    let column = |e: &Expr| matches!(e, Expr::Column(_));
+
+    match e {
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Mod(a, b)
+        | Expr::DateAdd(_, a, b)
+        | Expr::DateDiff(_, a, b) => column(a) || column(b),
+        Expr::Neg(a) | Expr::Extract(_, a) | Expr::ToString(a, _) => column(a),
+        _ => false,
+    }
+}
+
+fn is_position(name: &str) -> bool {
+    name.strip_prefix('_')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) && n != "0")
+}
+
+// `*` nested anywhere in `e`
+fn wildcard(e: &Expr, context: &Expr) -> Result<(), ValidationError> {
+    let mut found = false;
+    e.visit(&mut |e| found |= *e == Expr::Wildcard);
+
+    if found {
+        Err(ValidationError::MisplacedWildcard(context.to_sql()))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod validate_test {
+    use super::*;
+    use crate::query::{CastType, Clause, CsvInput, ExtractPart, ScanRange};
+
+    fn lines() -> InputObjectFormat {
+        InputObjectFormat::JSON(JsonType::Lines)
+    }
+
+    fn select(elements: Vec<Select>) -> QueryContent {
+        QueryContent::select(elements).from("bucket", "key")
+    }
+
+    fn validate(query: &QueryContent) -> Result<(), ValidationError> {
+        query.validate(&lines(), &CompressionType::NONE)
+    }
+
+    #[test]
+    fn valid() {
+        let query = select(vec![
+            Select::Count(Expr::Wildcard),
+            Select::Avg(Expr::column("age")).alias("average"),
+        ])
+        .where_clause(Clause::IsNotNull(Expr::column("age")))
+        .limit(1);
+        assert_eq!(validate(&query), Ok(()));
+
+        let grouped = select(vec![
+            Select::Elements(vec![Expr::column("country")]),
+            Select::Count(Expr::Wildcard),
+        ])
+        .group_by(vec![Expr::column("country")]);
+        assert_eq!(validate(&grouped), Ok(()));
+    }
+
+    #[test]
+    fn query_rules() {
+        let elements = || vec![Select::Elements(vec![Expr::column("id")])];
+
+        assert_eq!(
+            validate(&QueryContent::select(elements())),
+            Err(ValidationError::MissingFrom)
+        );
+        assert_eq!(validate(&select(vec![])), Err(ValidationError::EmptySelect));
+        assert_eq!(
+            validate(&select(vec![Select::Elements(vec![])]).limit(2)),
+            Err(ValidationError::EmptySelect)
+        );
+        assert_eq!(
+            validate(&select(elements()).limit(0)),
+            Err(ValidationError::ZeroLimit)
+        );
+        assert_eq!(
+            validate(&select(vec![
+                Select::Elements(vec![Expr::column("id")]),
+                Select::Count(Expr::Wildcard),
+            ])),
+            Err(ValidationError::MixedAggregates("s.id".to_string()))
+        );
+        assert_eq!(
+            validate(&select(vec![Select::Sum(Expr::Wildcard)])),
+            Err(ValidationError::MisplacedWildcard("*".to_string()))
+        );
+        assert_eq!(
+            validate(&select(elements()).where_clause(Clause::IsNull(Expr::Wildcard))),
+            Err(ValidationError::MisplacedWildcard(
+                "* IS MISSING".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn expression_length() {
+        let values = (0..50_000).map(Expr::from).collect::<Vec<Expr>>();
+        let query = select(vec![Select::Elements(vec![Expr::column("id")])])
+            .where_clause(Clause::In(Expr::column("id"), values));
+
+        assert!(matches!(
+            validate(&query),
+            Err(ValidationError::ExpressionTooLong(length)) if length > MAX_EXPRESSION_BYTES
+        ));
+    }
+
+    #[test]
+    fn format_rules() {
+        let query = select(vec![Select::Elements(vec![Expr::column("id")])]);
+        let csv = |header| InputObjectFormat::CSV(CsvInput::new().file_header(header));
+
+        assert_eq!(
+            query.validate(&InputObjectFormat::Parquet, &CompressionType::GZIP),
+            Err(ValidationError::CompressedParquet(CompressionType::GZIP))
+        );
+        assert_eq!(
+            query.validate(&csv(FileHeader::Use), &CompressionType::GZIP),
+            Ok(())
+        );
+        assert_eq!(
+            query
+                .clone()
+                .scan_range(ScanRange::From(10))
+                .validate(&lines(), &CompressionType::BZIP2)
+                .unwrap_err()
+                .to_string(),
+            "a scan range isn't supported for BZIP2 compressed input"
+        );
+        assert!(query
+            .clone()
+            .scan_range(ScanRange::From(10))
+            .validate(
+                &InputObjectFormat::JSON(JsonType::Document),
+                &CompressionType::NONE
+            )
+            .is_err());
+
+        let nested = select(vec![Select::Elements(vec![Expr::column("address.city")])]);
+        assert_eq!(
+            nested.validate(&csv(FileHeader::Use), &CompressionType::NONE),
+            Err(ValidationError::UnsupportedForFormat {
+                feature: "nested column `s.address.city`".to_string(),
+                format: "CSV".to_string(),
+            })
+        );
+        assert!(query
+            .validate(&csv(FileHeader::Ignore), &CompressionType::NONE)
+            .is_err());
+        let positional = select(vec![Select::Sum(Expr::column("_2").cast(CastType::Int))]);
+        assert_eq!(
+            positional.validate(&csv(FileHeader::Ignore), &CompressionType::NONE),
+            Ok(())
+        );
+        assert!(query
+            .clone()
+            .from_path(vec![Path::WildCardIndex])
+            .validate(&csv(FileHeader::Use), &CompressionType::NONE)
+            .is_err());
+    }
+
+    #[test]
+    fn csv_functions_need_casts() {
+        let csv = InputObjectFormat::CSV(CsvInput::new().file_header(FileHeader::Use));
+        let validate = |select: Select| {
+            QueryContent::select(vec![select])
+                .from("bucket", "key")
+                .validate(&csv, &CompressionType::NONE)
+        };
+        let unsupported = |function: &str| {
+            Err(ValidationError::UnsupportedForFormat {
+                feature: format!("`{}` on a column without CAST", function),
+                format: "CSV".to_string(),
+            })
+        };
+
+        assert_eq!(
+            validate(Select::Sum(Expr::column("price")).alias("total")),
+            unsupported("Sum(s.price)")
+        );
+        assert_eq!(
+            validate(Select::Elements(vec![
+                Expr::column("price") * Expr::from(2)
+            ])),
+            unsupported("s.price * 2")
+        );
+        assert_eq!(
+            validate(Select::Elements(vec![
+                Expr::column("sold_at").extract(ExtractPart::Year)
+            ])),
+            unsupported("EXTRACT(year FROM s.sold_at)")
+        );
+        assert_eq!(
+            validate(Select::Sum(Expr::column("price").cast(CastType::Float))),
+            Ok(())
+        );
+        assert_eq!(
+            validate(Select::Elements(vec![
+                Expr::column("sold_at")
+                    .cast(CastType::Timestamp)
+                    .extract(ExtractPart::Year),
+                Expr::column("name").upper(),
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            select(vec![Select::Sum(Expr::column("price"))])
+                .validate(&lines(), &CompressionType::NONE),
+            Ok(())
+        );
+    }
+}
@@ -1,11 +1,83 @@
 use async_trait::async_trait;
 use rusoto_core::RusotoError;
 use rusoto_s3::{
-    GetObjectError, GetObjectOutput, GetObjectRequest, HeadObjectError, HeadObjectRequest,
-    ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, S3Client, S3,
+    GetObjectError, GetObjectLegalHoldError, GetObjectLegalHoldRequest, GetObjectOutput,
+    GetObjectRequest, GetObjectRetentionError, GetObjectRetentionRequest, GetObjectTaggingError,
+    GetObjectTaggingRequest, HeadObjectError, HeadObjectRequest, ListObjectsV2Error,
+    ListObjectsV2Output, ListObjectsV2Request, S3Client, S3,
 };
 use tokio::io::AsyncReadExt;
 
+use crate::encryption::CustomerKey;
+use crate::lock::Retention;
+use crate::replication::ReplicationStatus;
+use crate::tagging::{from_tag_set, Tags};
+
+/// Conditions and SSE-C key for `read_s3_object_with`, `read_s3_object_body_with` and
+/// `has_s3_object_with`.
+#[derive(Clone, Debug, Default)]
+pub struct ReadOptions {
+    if_id_matches: Option<String>,
+    if_modified_since: Option<String>,
+    if_unmodified_since: Option<String>,
+    customer_key: Option<CustomerKey>,
+}
+
+impl ReadOptions {
+    pub fn new() -> Self {
+        ReadOptions::default()
+    }
+
+    pub fn if_id_matches(mut self, e_tag: String) -> Self {
+        self.if_id_matches = Some(e_tag);
+        self
+    }
+
+    pub fn if_modified_since(mut self, date: String) -> Self {
+        self.if_modified_since = Some(date);
+        self
+    }
+
+    pub fn if_unmodified_since(mut self, date: String) -> Self {
+        self.if_unmodified_since = Some(date);
+        self
+    }
+
+    /// Key the object was written with, see `ObjectEncryption::Customer`.
+    pub fn customer_key(mut self, key: CustomerKey) -> Self {
+        self.customer_key = Some(key);
+        self
+    }
+
+    fn get_object_request(self, bucket_name: String, key: String) -> GetObjectRequest {
+        GetObjectRequest {
+            bucket: bucket_name,
+            key,
+            if_match: self.if_id_matches,
+            if_modified_since: self.if_modified_since,
+            if_unmodified_since: self.if_unmodified_since,
+            sse_customer_algorithm: self.customer_key.as_ref().map(CustomerKey::algorithm),
+            sse_customer_key: self.customer_key.as_ref().map(CustomerKey::key),
+            sse_customer_key_md5: self.customer_key.as_ref().map(CustomerKey::key_md5),
+            ..Default::default()
+        }
+    }
+
+    fn head_object_request(self, bucket_name: String, key: String) -> HeadObjectRequest {
+        HeadObjectRequest {
+            bucket: bucket_name,
+            key,
+            if_match: self.if_id_matches,
+            if_modified_since: self.if_modified_since,
+            if_unmodified_since: self.if_unmodified_since,
+            sse_customer_algorithm: self.customer_key.as_ref().map(CustomerKey::algorithm),
+            sse_customer_key: self.customer_key.as_ref().map(CustomerKey::key),
+            sse_customer_key_md5: self.customer_key.as_ref().map(CustomerKey::key_md5),
+            ..Default::default()
+        }
+    }
+}
+
 #[async_trait]
 pub trait Readable: S3 {
     async fn read_s3_object(
@@ -17,6 +89,13 @@ pub trait Readable: S3 {
         if_unmodified_since: Option<String>,
     ) -> Result<GetObjectOutput, RusotoError<GetObjectError>>;
 
+    async fn read_s3_object_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        options: ReadOptions,
+    ) -> Result<GetObjectOutput, RusotoError<GetObjectError>>;
+
     async fn read_s3_object_body(
         &self,
         bucket_name: String,
@@ -26,6 +105,13 @@ pub trait Readable: S3 {
         if_unmodified_since: Option<String>,
     ) -> Option<String>;
 
+    async fn read_s3_object_body_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        options: ReadOptions,
+    ) -> Option<String>;
+
     async fn has_s3_object(
         &self,
         bucket_name: String,
@@ -35,11 +121,48 @@ pub trait Readable: S3 {
         if_unmodified_since: Option<String>,
     ) -> Result<(), RusotoError<HeadObjectError>>;
 
+    async fn has_s3_object_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        options: ReadOptions,
+    ) -> Result<(), RusotoError<HeadObjectError>>;
+
     async fn show_s3_objects(
         &self,
         bucket_name: String,
         max_keys: Option<i64>,
     ) -> Result<ListObjectsV2Output, RusotoError<ListObjectsV2Error>>;
+
+    async fn read_s3_object_tags(
+        &self,
+        bucket_name: String,
+        key: String,
+    ) -> Result<Tags, RusotoError<GetObjectTaggingError>>;
+
+    async fn read_s3_object_retention(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+    ) -> Result<Option<Retention>, RusotoError<GetObjectRetentionError>>;
+
+    async fn read_s3_object_legal_hold(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+    ) -> Result<bool, RusotoError<GetObjectLegalHoldError>>;
+
+    /// `None` when the object isn't covered by any replication rule. SSE-C objects need the key
+    /// they were written with.
+    async fn read_s3_object_replication_status(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        customer_key: Option<CustomerKey>,
+    ) -> Result<Option<ReplicationStatus>, RusotoError<HeadObjectError>>;
 }
 
 #[async_trait]
@@ -52,15 +175,24 @@ impl Readable for S3Client {
         if_modified_since: Option<String>,
         if_unmodified_since: Option<String>,
     ) -> Result<GetObjectOutput, RusotoError<GetObjectError>> {
-        let get_object = GetObjectRequest {
-            bucket: bucket_name,
-            key,
-            if_match: if_id_matches,
+        let options = ReadOptions {
+            if_id_matches,
             if_modified_since,
             if_unmodified_since,
-            ..Default::default()
+            customer_key: None,
         };
 
+        self.read_s3_object_with(bucket_name, key, options).await
+    }
+
+    async fn read_s3_object_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        options: ReadOptions,
+    ) -> Result<GetObjectOutput, RusotoError<GetObjectError>> {
+        let get_object = options.get_object_request(bucket_name, key);
+
         self.get_object(get_object).await
     }
 
@@ -72,15 +204,25 @@ impl Readable for S3Client {
         if_modified_since: Option<String>,
         if_unmodified_since: Option<String>,
     ) -> Option<String> {
-        let get_object = GetObjectRequest {
-            bucket: bucket_name,
-            key,
-            if_match: if_id_matches,
+        let options = ReadOptions {
+            if_id_matches,
             if_modified_since,
             if_unmodified_since,
-            ..Default::default()
+            customer_key: None,
         };
 
+        self.read_s3_object_body_with(bucket_name, key, options)
+            .await
+    }
+
+    async fn read_s3_object_body_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        options: ReadOptions,
+    ) -> Option<String> {
+        let get_object = options.get_object_request(bucket_name, key);
+
         match self.get_object(get_object).await {
             Err(_) => None,
             Ok(obj) => {
@@ -105,15 +247,24 @@ impl Readable for S3Client {
         if_modified_since: Option<String>,
         if_unmodified_since: Option<String>,
     ) -> Result<(), RusotoError<HeadObjectError>> {
-        let head_object = HeadObjectRequest {
-            bucket: bucket_name,
-            key,
-            if_match: if_id_matches,
+        let options = ReadOptions {
+            if_id_matches,
             if_modified_since,
             if_unmodified_since,
-            ..Default::default()
+            customer_key: None,
         };
 
+        self.has_s3_object_with(bucket_name, key, options).await
+    }
+
+    async fn has_s3_object_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        options: ReadOptions,
+    ) -> Result<(), RusotoError<HeadObjectError>> {
+        let head_object = options.head_object_request(bucket_name, key);
+
         match self.head_object(head_object).await {
             Err(e) => Err(e),
             Ok(_) => Ok(()),
@@ -133,4 +284,83 @@ impl Readable for S3Client {
 
         self.list_objects_v2(list_objs).await
     }
+
+    async fn read_s3_object_tags(
+        &self,
+        bucket_name: String,
+        key: String,
+    ) -> Result<Tags, RusotoError<GetObjectTaggingError>> {
+        let get_tagging = GetObjectTaggingRequest {
+            bucket: bucket_name,
+            key,
+            ..Default::default()
+        };
+
+        let output = self.get_object_tagging(get_tagging).await?;
+
+        Ok(from_tag_set(output.tag_set))
+    }
+
+    async fn read_s3_object_retention(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+    ) -> Result<Option<Retention>, RusotoError<GetObjectRetentionError>> {
+        let get_retention = GetObjectRetentionRequest {
+            bucket: bucket_name,
+            key,
+            version_id,
+            ..Default::default()
+        };
+
+        let output = self.get_object_retention(get_retention).await?;
+
+        Ok(output.retention.and_then(Retention::from_object_lock))
+    }
+
+    async fn read_s3_object_legal_hold(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+    ) -> Result<bool, RusotoError<GetObjectLegalHoldError>> {
+        let get_legal_hold = GetObjectLegalHoldRequest {
+            bucket: bucket_name,
+            key,
+            version_id,
+            ..Default::default()
+        };
+
+        let output = self.get_object_legal_hold(get_legal_hold).await?;
+
+        let status = output.legal_hold.and_then(|legal_hold| legal_hold.status);
+
+        Ok(status.as_deref() == Some("ON"))
+    }
+
+    async fn read_s3_object_replication_status(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        customer_key: Option<CustomerKey>,
+    ) -> Result<Option<ReplicationStatus>, RusotoError<HeadObjectError>> {
+        let head_object = HeadObjectRequest {
+            bucket: bucket_name,
+            key,
+            version_id,
+            sse_customer_algorithm: customer_key.as_ref().map(CustomerKey::algorithm),
+            sse_customer_key: customer_key.as_ref().map(CustomerKey::key),
+            sse_customer_key_md5: customer_key.as_ref().map(CustomerKey::key_md5),
+            ..Default::default()
+        };
+
+        let output = self.head_object(head_object).await?;
+
+        Ok(output
+            .replication_status
+            .as_deref()
+            .and_then(ReplicationStatus::parse))
+    }
 }
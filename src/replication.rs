@@ -0,0 +1,409 @@
+use rusoto_s3::{
+    DeleteMarkerReplication, Destination, Metrics, ReplicationConfiguration,
+    ReplicationRuleAndOperator, ReplicationRuleFilter, ReplicationTime, ReplicationTimeValue, Tag,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::tagging::{from_tag_set, to_tag_set, Tags};
+
+// Replication Time Control only supports a 15 minutes threshold
+const RTC_MINUTES: i64 = 15;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum StorageClass {
+    Standard,
+    ReducedRedundancy,
+    StandardIa,
+    OnezoneIa,
+    IntelligentTiering,
+    Glacier,
+    DeepArchive,
+}
+
+impl StorageClass {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Standard => "STANDARD",
+            Self::ReducedRedundancy => "REDUCED_REDUNDANCY",
+            Self::StandardIa => "STANDARD_IA",
+            Self::OnezoneIa => "ONEZONE_IA",
+            Self::IntelligentTiering => "INTELLIGENT_TIERING",
+            Self::Glacier => "GLACIER",
+            Self::DeepArchive => "DEEP_ARCHIVE",
+        }
+    }
+
+    fn parse(storage_class: &str) -> Option<Self> {
+        match storage_class {
+            "STANDARD" => Some(Self::Standard),
+            "REDUCED_REDUNDANCY" => Some(Self::ReducedRedundancy),
+            "STANDARD_IA" => Some(Self::StandardIa),
+            "ONEZONE_IA" => Some(Self::OnezoneIa),
+            "INTELLIGENT_TIERING" => Some(Self::IntelligentTiering),
+            "GLACIER" => Some(Self::Glacier),
+            "DEEP_ARCHIVE" => Some(Self::DeepArchive),
+            _ => None,
+        }
+    }
+}
+
+/// Which objects a rule replicates.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ReplicationFilter {
+    All,
+    Prefix(String),
+    Tag(String, String),
+    And(Option<String>, Tags), // prefix and every tag must match
+}
+
+impl ReplicationFilter {
+    fn to_rule_filter(&self) -> ReplicationRuleFilter {
+        match self {
+            Self::All => ReplicationRuleFilter {
+                prefix: Some(String::new()),
+                ..Default::default()
+            },
+            Self::Prefix(prefix) => ReplicationRuleFilter {
+                prefix: Some(prefix.clone()),
+                ..Default::default()
+            },
+            Self::Tag(key, value) => ReplicationRuleFilter {
+                tag: Some(Tag {
+                    key: key.clone(),
+                    value: value.clone(),
+                }),
+                ..Default::default()
+            },
+            Self::And(prefix, tags) => ReplicationRuleFilter {
+                and: Some(ReplicationRuleAndOperator {
+                    prefix: prefix.clone(),
+                    tags: Some(to_tag_set(tags.clone())),
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn from_rule_filter(filter: ReplicationRuleFilter) -> Self {
+        match filter {
+            ReplicationRuleFilter { and: Some(and), .. } => {
+                Self::And(and.prefix, from_tag_set(and.tags.unwrap_or_default()))
+            }
+            ReplicationRuleFilter { tag: Some(tag), .. } => Self::Tag(tag.key, tag.value),
+            ReplicationRuleFilter {
+                prefix: Some(prefix),
+                ..
+            } if !prefix.is_empty() => Self::Prefix(prefix),
+            _ => Self::All,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReplicationDestination {
+    pub bucket_arn: String,
+    pub account: Option<String>, // destination bucket owner, for cross account replication
+    pub storage_class: Option<StorageClass>,
+    pub replication_time_control: bool,
+}
+
+impl ReplicationDestination {
+    pub fn new(bucket_arn: &str) -> Self {
+        ReplicationDestination {
+            bucket_arn: bucket_arn.to_string(),
+            account: None,
+            storage_class: None,
+            replication_time_control: false,
+        }
+    }
+
+    pub fn account(mut self, account: &str) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    pub fn storage_class(mut self, storage_class: StorageClass) -> Self {
+        self.storage_class = Some(storage_class);
+        self
+    }
+
+    /// Replicates 99.99% of objects within 15 minutes, S3 requires replication metrics for it.
+    pub fn with_replication_time_control(mut self) -> Self {
+        self.replication_time_control = true;
+        self
+    }
+
+    fn to_destination(&self) -> Destination {
+        let rtc_minutes = ReplicationTimeValue {
+            minutes: Some(RTC_MINUTES),
+        };
+
+        Destination {
+            bucket: self.bucket_arn.clone(),
+            account: self.account.clone(),
+            storage_class: self.storage_class.as_ref().map(|s| s.as_str().to_string()),
+            replication_time: Some(ReplicationTime {
+                status: status(self.replication_time_control),
+                time: rtc_minutes.clone(),
+            })
+            .filter(|_| self.replication_time_control),
+            metrics: Some(Metrics {
+                status: status(self.replication_time_control),
+                event_threshold: rtc_minutes,
+            })
+            .filter(|_| self.replication_time_control),
+            ..Default::default()
+        }
+    }
+
+    fn from_destination(destination: Destination) -> Self {
+        ReplicationDestination {
+            bucket_arn: destination.bucket,
+            account: destination.account,
+            storage_class: destination
+                .storage_class
+                .as_deref()
+                .and_then(StorageClass::parse),
+            replication_time_control: destination
+                .replication_time
+                .is_some_and(|rtc| rtc.status == "Enabled"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReplicationRule {
+    pub id: Option<String>,
+    pub priority: Option<i64>, // higher wins when rules overlap
+    pub enabled: bool,
+    pub filter: ReplicationFilter,
+    pub destination: ReplicationDestination,
+    pub replicate_delete_markers: bool,
+}
+
+impl ReplicationRule {
+    pub fn new(filter: ReplicationFilter, destination: ReplicationDestination) -> Self {
+        ReplicationRule {
+            id: None,
+            priority: None,
+            enabled: true,
+            filter,
+            destination,
+            replicate_delete_markers: false,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn priority(mut self, priority: i64) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn with_delete_markers(mut self) -> Self {
+        self.replicate_delete_markers = true;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Replication {
+    pub role_arn: String, // IAM role S3 assumes to replicate
+    pub rules: Vec<ReplicationRule>,
+}
+
+impl Replication {
+    pub fn new(role_arn: &str, rules: Vec<ReplicationRule>) -> Self {
+        Replication {
+            role_arn: role_arn.to_string(),
+            rules,
+        }
+    }
+
+    pub(crate) fn to_configuration(&self) -> ReplicationConfiguration {
+        // rules using `Filter` must all have a distinct priority, defaults skip the given ones
+        let given = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.priority)
+            .collect::<HashSet<i64>>();
+        let mut defaults = (0..).filter(|priority| !given.contains(priority));
+
+        ReplicationConfiguration {
+            role: self.role_arn.clone(),
+            rules: self
+                .rules
+                .iter()
+                .map(|rule| rusoto_s3::ReplicationRule {
+                    id: rule.id.clone(),
+                    priority: rule.priority.or_else(|| defaults.next()),
+                    status: status(rule.enabled),
+                    filter: Some(rule.filter.to_rule_filter()),
+                    destination: rule.destination.to_destination(),
+                    delete_marker_replication: Some(DeleteMarkerReplication {
+                        status: Some(status(rule.replicate_delete_markers)),
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn from_configuration(config: ReplicationConfiguration) -> Self {
+        Replication {
+            role_arn: config.role,
+            rules: config
+                .rules
+                .into_iter()
+                .map(|rule| ReplicationRule {
+                    id: rule.id,
+                    priority: rule.priority,
+                    enabled: rule.status == "Enabled",
+                    filter: rule
+                        .filter
+                        .map_or(ReplicationFilter::All, ReplicationFilter::from_rule_filter),
+                    destination: ReplicationDestination::from_destination(rule.destination),
+                    replicate_delete_markers: rule
+                        .delete_marker_replication
+                        .and_then(|markers| markers.status)
+                        .is_some_and(|status| status == "Enabled"),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Replication state of an object, from the `x-amz-replication-status` header.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ReplicationStatus {
+    Pending,
+    Completed,
+    Failed,
+    Replica, // the object is itself a replica
+}
+
+impl ReplicationStatus {
+    pub(crate) fn parse(status: &str) -> Option<Self> {
+        match status {
+            "PENDING" => Some(Self::Pending),
+            "COMPLETED" | "COMPLETE" => Some(Self::Completed),
+            "FAILED" => Some(Self::Failed),
+            "REPLICA" => Some(Self::Replica),
+            _ => None,
+        }
+    }
+}
+
+fn status(enabled: bool) -> String {
+    if enabled {
+        "Enabled".to_string()
+    } else {
+        "Disabled".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn replication() -> Replication {
+        let mut tags = Tags::new();
+        tags.insert("dr".to_string(), "true".to_string());
+
+        Replication::new(
+            "arn:aws:iam::123456789012:role/replication",
+            vec![
+                ReplicationRule::new(
+                    ReplicationFilter::Prefix("reports/".to_string()),
+                    ReplicationDestination::new("arn:aws:s3:::reports-dr")
+                        .account("210987654321")
+                        .storage_class(StorageClass::StandardIa)
+                        .with_replication_time_control(),
+                )
+                .id("reports")
+                .priority(1)
+                .with_delete_markers(),
+                ReplicationRule::new(
+                    ReplicationFilter::And(Some("logs/".to_string()), tags),
+                    ReplicationDestination::new("arn:aws:s3:::logs-dr"),
+                )
+                .id("logs")
+                .priority(2)
+                .disabled(),
+            ],
+        )
+    }
+
+    #[test]
+    fn configuration_roundtrip() {
+        let config = replication().to_configuration();
+        let rtc = config.rules[0].destination.replication_time.as_ref();
+
+        assert_eq!(rtc.unwrap().time.minutes, Some(15));
+        assert!(config.rules[0].destination.metrics.is_some());
+        assert!(config.rules[1].destination.replication_time.is_none());
+        assert_eq!(config.rules[1].status, "Disabled");
+        assert_eq!(Replication::from_configuration(config), replication());
+    }
+
+    #[test]
+    fn default_priorities() {
+        let replication = Replication::new(
+            "arn:aws:iam::123456789012:role/replication",
+            vec![
+                ReplicationRule::new(
+                    ReplicationFilter::All,
+                    ReplicationDestination::new("arn:aws:s3:::a"),
+                ),
+                ReplicationRule::new(
+                    ReplicationFilter::All,
+                    ReplicationDestination::new("arn:aws:s3:::b"),
+                ),
+            ],
+        );
+        let config = replication.to_configuration();
+
+        assert_eq!(config.rules[0].priority, Some(0));
+        assert_eq!(config.rules[1].priority, Some(1));
+        assert_eq!(
+            config.rules[0].filter.as_ref().unwrap().prefix,
+            Some(String::new())
+        );
+    }
+
+    #[test]
+    fn default_priorities_skip_given_ones() {
+        let rule = |bucket_arn: &str| {
+            ReplicationRule::new(
+                ReplicationFilter::All,
+                ReplicationDestination::new(bucket_arn),
+            )
+        };
+        let replication = Replication::new(
+            "arn:aws:iam::123456789012:role/replication",
+            vec![
+                rule("arn:aws:s3:::a"),
+                rule("arn:aws:s3:::b").priority(0),
+                rule("arn:aws:s3:::c"),
+                rule("arn:aws:s3:::d").priority(2),
+            ],
+        );
+        let priorities = replication
+            .to_configuration()
+            .rules
+            .iter()
+            .map(|rule| rule.priority)
+            .collect::<Vec<Option<i64>>>();
+
+        assert_eq!(priorities, vec![Some(1), Some(0), Some(3), Some(2)]);
+    }
+}
@@ -0,0 +1,136 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rusoto_s3::Tag;
+use std::collections::HashMap;
+
+pub type Tags = HashMap<String, String>;
+
+pub const MAX_OBJECT_TAGS: usize = 10;
+pub const MAX_BUCKET_TAGS: usize = 50;
+pub const MAX_KEY_LENGTH: usize = 128;
+pub const MAX_VALUE_LENGTH: usize = 256;
+
+// `x-amz-tagging` is a url query string, only unreserved characters are left as is
+const TAGGING_QUERY: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Checks S3 tag restrictions before any request is sent, `max_tags` is `MAX_OBJECT_TAGS` or `MAX_BUCKET_TAGS`.
+pub fn validate_tags(tags: &Tags, max_tags: usize) -> Result<(), String> {
+    if tags.len() > max_tags {
+        return Err(format!(
+            "{} tags given, at most {} are allowed",
+            tags.len(),
+            max_tags
+        ));
+    }
+
+    for (key, value) in tags {
+        if key.is_empty() {
+            return Err("tag key can't be empty".to_string());
+        }
+        if key.starts_with("aws:") {
+            return Err(format!("tag key `{}` uses the reserved `aws:` prefix", key));
+        }
+        if key.chars().count() > MAX_KEY_LENGTH {
+            return Err(format!(
+                "tag key `{}` is longer than {} characters",
+                key, MAX_KEY_LENGTH
+            ));
+        }
+        if value.chars().count() > MAX_VALUE_LENGTH {
+            return Err(format!(
+                "tag value for `{}` is longer than {} characters",
+                key, MAX_VALUE_LENGTH
+            ));
+        }
+        if let Some(c) = key.chars().chain(value.chars()).find(|c| !is_allowed(*c)) {
+            return Err(format!("tag `{}` contains invalid character `{}`", key, c));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_allowed(c: char) -> bool {
+    c.is_alphanumeric() || c == ' ' || "+-=._:/@".contains(c)
+}
+
+pub(crate) fn to_tag_set(tags: Tags) -> Vec<Tag> {
+    tags.into_iter()
+        .map(|(key, value)| Tag { key, value })
+        .collect()
+}
+
+pub(crate) fn from_tag_set(tag_set: Vec<Tag>) -> Tags {
+    tag_set
+        .into_iter()
+        .map(|tag| (tag.key, tag.value))
+        .collect()
+}
+
+pub(crate) fn to_query(tags: &Tags) -> String {
+    tags.iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                utf8_percent_encode(key, TAGGING_QUERY),
+                utf8_percent_encode(value, TAGGING_QUERY)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> Tags {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_tags() {
+        let tags = tags(&[
+            ("cost-center", "team:data/ingest"),
+            ("owner", "julia@s3ql"),
+            ("team name", "data ingest"),
+        ]);
+
+        assert!(validate_tags(&tags, MAX_OBJECT_TAGS).is_ok());
+    }
+
+    #[test]
+    fn too_many_tags() {
+        let tags = (0..11)
+            .map(|i| (format!("key{}", i), "value".to_string()))
+            .collect::<Tags>();
+
+        assert!(validate_tags(&tags, MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags, MAX_BUCKET_TAGS).is_ok());
+    }
+
+    #[test]
+    fn invalid_tags() {
+        assert!(validate_tags(&tags(&[("", "value")]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[("aws:key", "value")]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[("key", "a*b")]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[(&"k".repeat(129), "value")]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[("key", &"v".repeat(257))]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[("key", "a\tb")]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[("key\n", "value")]), MAX_OBJECT_TAGS).is_err());
+        assert!(validate_tags(&tags(&[("key", "a\u{a0}b")]), MAX_OBJECT_TAGS).is_err());
+    }
+
+    #[test]
+    fn tagging_query() {
+        let query = to_query(&tags(&[("team", "data ingest/raw")]));
+
+        assert_eq!(query, "team=data%20ingest%2Fraw");
+    }
+}
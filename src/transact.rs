@@ -1,14 +1,116 @@
 use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use rusoto_core::{ByteStream, RusotoError};
 
-use rusoto_s3::{PutObjectError, PutObjectOutput, PutObjectRequest, S3Client, S3};
+use rusoto_s3::{
+    DeleteObjectError, DeleteObjectRequest, DeleteObjectTaggingError, DeleteObjectTaggingRequest,
+    ObjectLockLegalHold, PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldRequest,
+    PutObjectOutput, PutObjectRequest, PutObjectRetentionError, PutObjectRetentionRequest,
+    PutObjectTaggingError, PutObjectTaggingRequest, S3Client, Tagging, S3,
+};
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::encryption::ObjectEncryption;
+use crate::lock::{legal_hold_status, ObjectLockError, Retention};
+use crate::tagging::{to_query, to_tag_set, validate_tags, Tags, MAX_OBJECT_TAGS};
+use crate::website::{site_files, SiteError, SiteOptions};
 
 pub struct InsertResponse {
     pub object: PutObjectOutput,
     pub id: String,
 }
 
+/// Typed settings for `insert_s3_object_with`.
+#[derive(Clone, Default)]
+pub struct InsertOptions {
+    metadata: Option<HashMap<String, String>>,
+    encryption: Option<ObjectEncryption>,
+    tags: Option<Tags>,
+    retention: Option<Retention>,
+    legal_hold: Option<bool>,
+}
+
+impl InsertOptions {
+    pub fn new() -> Self {
+        InsertOptions::default()
+    }
+
+    pub fn metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn encryption(mut self, encryption: ObjectEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub fn tags(mut self, tags: Tags) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Needs a bucket created with object lock, see `Bucket::create_s3_locked_bucket`.
+    pub fn retention(mut self, retention: Retention) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub fn legal_hold(mut self, on: bool) -> Self {
+        self.legal_hold = Some(on);
+        self
+    }
+
+    fn put_object_request(
+        self,
+        bucket_name: String,
+        key: String,
+        body: Option<String>,
+    ) -> Result<PutObjectRequest, String> {
+        if let Some(tags) = &self.tags {
+            validate_tags(tags, MAX_OBJECT_TAGS)?;
+        }
+
+        // S3 refuses uploads under object lock retention without `Content-MD5`
+        let body = body.unwrap_or_default().into_bytes();
+        let mut put_object = PutObjectRequest {
+            bucket: bucket_name,
+            key,
+            metadata: self.metadata,
+            tagging: self.tags.as_ref().map(to_query),
+            object_lock_mode: self.retention.as_ref().map(|r| r.mode.as_str().to_string()),
+            object_lock_retain_until_date: self
+                .retention
+                .as_ref()
+                .map(Retention::retain_until_date),
+            object_lock_legal_hold_status: self.legal_hold.map(legal_hold_status),
+            content_md5: Some(base64::encode(*md5::compute(&body))),
+            body: Some(ByteStream::from(body)),
+            ..Default::default()
+        };
+
+        match self.encryption {
+            Some(ObjectEncryption::S3) => {
+                put_object.server_side_encryption = Some("AES256".to_string());
+            }
+            Some(ObjectEncryption::KMS { key_id, context }) => {
+                put_object.server_side_encryption = Some("aws:kms".to_string());
+                put_object.ssekms_key_id = key_id;
+                put_object.ssekms_encryption_context = context;
+            }
+            Some(ObjectEncryption::Customer(customer_key)) => {
+                put_object.sse_customer_algorithm = Some(customer_key.algorithm());
+                put_object.sse_customer_key = Some(customer_key.key());
+                put_object.sse_customer_key_md5 = Some(customer_key.key_md5());
+            }
+            None => (),
+        }
+
+        Ok(put_object)
+    }
+}
+
 #[async_trait]
 pub trait Transact: S3 {
     async fn insert_s3_object(
@@ -20,6 +122,15 @@ pub trait Transact: S3 {
         object_request: Option<PutObjectRequest>,
     ) -> Result<InsertResponse, RusotoError<PutObjectError>>;
 
+    /// Puts always write a new version, so object lock never refuses them.
+    async fn insert_s3_object_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        body: Option<String>,
+        options: InsertOptions,
+    ) -> Result<InsertResponse, RusotoError<PutObjectError>>;
+
     async fn update_s3_object_body(
         &self,
         bucket_name: String,
@@ -35,6 +146,53 @@ pub trait Transact: S3 {
         metadata: Option<HashMap<String, String>>,
         object_request: Option<PutObjectRequest>,
     ) -> Result<InsertResponse, RusotoError<PutObjectError>>;
+
+    async fn put_s3_object_tags(
+        &self,
+        bucket_name: String,
+        key: String,
+        tags: Tags,
+    ) -> Result<(), RusotoError<PutObjectTaggingError>>;
+
+    async fn drop_s3_object_tags(
+        &self,
+        bucket_name: String,
+        key: String,
+    ) -> Result<(), RusotoError<DeleteObjectTaggingError>>;
+
+    /// Deleting without `version_id` only adds a delete marker, object lock protects versions.
+    async fn drop_s3_object(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        bypass_governance: bool,
+    ) -> Result<(), ObjectLockError<DeleteObjectError>>;
+
+    async fn put_s3_object_retention(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        retention: Retention,
+        bypass_governance: bool,
+    ) -> Result<(), ObjectLockError<PutObjectRetentionError>>;
+
+    async fn put_s3_object_legal_hold(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        on: bool,
+    ) -> Result<(), RusotoError<PutObjectLegalHoldError>>;
+
+    /// Uploads every file under `dir` and returns the written keys, see `Bucket::put_s3_bucket_website`.
+    async fn upload_s3_site(
+        &self,
+        bucket_name: String,
+        dir: PathBuf,
+        options: SiteOptions,
+    ) -> Result<Vec<String>, SiteError>;
 }
 
 #[async_trait]
@@ -89,6 +247,26 @@ impl Transact for S3Client {
         }
     }
 
+    async fn insert_s3_object_with(
+        &self,
+        bucket_name: String,
+        key: String,
+        body: Option<String>,
+        options: InsertOptions,
+    ) -> Result<InsertResponse, RusotoError<PutObjectError>> {
+        let put_object = options
+            .put_object_request(bucket_name, key, body)
+            .map_err(RusotoError::Validation)?;
+
+        match self.put_object(put_object).await {
+            Err(e) => Err(e),
+            Ok(resp) => Ok(InsertResponse {
+                id: resp.e_tag.clone().unwrap_or_default(),
+                object: resp,
+            }),
+        }
+    }
+
     async fn update_s3_object_body(
         &self,
         bucket_name: String,
@@ -162,4 +340,164 @@ impl Transact for S3Client {
             }
         }
     }
+
+    async fn put_s3_object_tags(
+        &self,
+        bucket_name: String,
+        key: String,
+        tags: Tags,
+    ) -> Result<(), RusotoError<PutObjectTaggingError>> {
+        validate_tags(&tags, MAX_OBJECT_TAGS).map_err(RusotoError::Validation)?;
+
+        let put_tagging = PutObjectTaggingRequest {
+            bucket: bucket_name,
+            key,
+            tagging: Tagging {
+                tag_set: to_tag_set(tags),
+            },
+            ..Default::default()
+        };
+
+        match self.put_object_tagging(put_tagging).await {
+            Err(e) => Err(e),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn drop_s3_object_tags(
+        &self,
+        bucket_name: String,
+        key: String,
+    ) -> Result<(), RusotoError<DeleteObjectTaggingError>> {
+        let delete_tagging = DeleteObjectTaggingRequest {
+            bucket: bucket_name,
+            key,
+            ..Default::default()
+        };
+
+        match self.delete_object_tagging(delete_tagging).await {
+            Err(e) => Err(e),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn drop_s3_object(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        bypass_governance: bool,
+    ) -> Result<(), ObjectLockError<DeleteObjectError>> {
+        let delete_object = DeleteObjectRequest {
+            bucket: bucket_name.clone(),
+            key: key.clone(),
+            version_id: version_id.clone(),
+            bypass_governance_retention: Some(bypass_governance).filter(|bypass| *bypass),
+            ..Default::default()
+        };
+
+        match self.delete_object(delete_object).await {
+            Err(e) => Err(ObjectLockError::check(self, e, bucket_name, key, version_id).await),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn put_s3_object_retention(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        retention: Retention,
+        bypass_governance: bool,
+    ) -> Result<(), ObjectLockError<PutObjectRetentionError>> {
+        let put_retention = PutObjectRetentionRequest {
+            bucket: bucket_name.clone(),
+            key: key.clone(),
+            version_id: version_id.clone(),
+            retention: Some(retention.to_object_lock()),
+            bypass_governance_retention: Some(bypass_governance).filter(|bypass| *bypass),
+            ..Default::default()
+        };
+
+        match self.put_object_retention(put_retention).await {
+            Err(e) => Err(ObjectLockError::check(self, e, bucket_name, key, version_id).await),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn put_s3_object_legal_hold(
+        &self,
+        bucket_name: String,
+        key: String,
+        version_id: Option<String>,
+        on: bool,
+    ) -> Result<(), RusotoError<PutObjectLegalHoldError>> {
+        let put_legal_hold = PutObjectLegalHoldRequest {
+            bucket: bucket_name,
+            key,
+            version_id,
+            legal_hold: Some(ObjectLockLegalHold {
+                status: Some(legal_hold_status(on)),
+            }),
+            ..Default::default()
+        };
+
+        match self.put_object_legal_hold(put_legal_hold).await {
+            Err(e) => Err(e),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn upload_s3_site(
+        &self,
+        bucket_name: String,
+        dir: PathBuf,
+        options: SiteOptions,
+    ) -> Result<Vec<String>, SiteError> {
+        let files = site_files(dir.clone()).await.map_err(SiteError::Io)?;
+
+        let mut keys = stream::iter(files)
+            .map(|file| {
+                let bucket = bucket_name.clone();
+                let key = options.key(&dir, &file);
+                let content_type = options.content_type_of(&file);
+                let cache_control = options.cache_control_of(&file);
+
+                async move {
+                    let body = tokio::fs::read(&file).await.map_err(SiteError::Io)?;
+                    let put_object = PutObjectRequest {
+                        bucket,
+                        key: key.clone(),
+                        body: Some(ByteStream::from(body)),
+                        content_type: Some(content_type),
+                        cache_control,
+                        ..Default::default()
+                    };
+
+                    match self.put_object(put_object).await {
+                        Err(e) => Err(SiteError::S3(e)),
+                        Ok(_) => Ok(key),
+                    }
+                }
+            })
+            .buffer_unordered(options.concurrency_limit())
+            .try_collect::<Vec<String>>()
+            .await?;
+
+        for (key, location) in options.redirects() {
+            let put_object = PutObjectRequest {
+                bucket: bucket_name.clone(),
+                key: key.clone(),
+                body: Some(ByteStream::from(Vec::new())),
+                website_redirect_location: Some(location),
+                ..Default::default()
+            };
+
+            self.put_object(put_object).await.map_err(SiteError::S3)?;
+            keys.push(key);
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
 }
@@ -0,0 +1,464 @@
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    Condition, ErrorDocument, GetBucketWebsiteOutput, IndexDocument, PutObjectError, Redirect,
+    RedirectAllRequestsTo, WebsiteConfiguration,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Protocol {
+    Http,
+    Https,
+}
+
+impl Protocol {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Http => "http",
+            Self::Https => "https",
+        }
+    }
+
+    fn parse(protocol: &str) -> Option<Self> {
+        match protocol {
+            "http" => Some(Self::Http),
+            "https" => Some(Self::Https),
+            _ => None,
+        }
+    }
+}
+
+/// How a routing rule rewrites the requested key.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum KeyRewrite {
+    Prefix(String), // replaces only the matched `key_prefix`
+    Key(String),    // replaces the whole key
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RoutingRule {
+    pub key_prefix: Option<String>,
+    pub http_error_code: Option<u16>,
+    pub host: Option<String>,
+    pub protocol: Option<Protocol>,
+    pub rewrite: Option<KeyRewrite>,
+    pub http_redirect_code: Option<u16>,
+}
+
+impl RoutingRule {
+    pub fn new() -> Self {
+        RoutingRule::default()
+    }
+
+    pub fn when_key_prefix(mut self, key_prefix: &str) -> Self {
+        self.key_prefix = Some(key_prefix.to_string());
+        self
+    }
+
+    pub fn when_http_error(mut self, code: u16) -> Self {
+        self.http_error_code = Some(code);
+        self
+    }
+
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn rewrite(mut self, rewrite: KeyRewrite) -> Self {
+        self.rewrite = Some(rewrite);
+        self
+    }
+
+    pub fn http_redirect_code(mut self, code: u16) -> Self {
+        self.http_redirect_code = Some(code);
+        self
+    }
+
+    fn to_rule(&self) -> rusoto_s3::RoutingRule {
+        let condition = Condition {
+            key_prefix_equals: self.key_prefix.clone(),
+            http_error_code_returned_equals: self.http_error_code.map(|c| c.to_string()),
+        };
+
+        rusoto_s3::RoutingRule {
+            condition: Some(condition)
+                .filter(|_| self.key_prefix.is_some() || self.http_error_code.is_some()),
+            redirect: Redirect {
+                host_name: self.host.clone(),
+                protocol: self.protocol.as_ref().map(|p| p.as_str().to_string()),
+                replace_key_prefix_with: match &self.rewrite {
+                    Some(KeyRewrite::Prefix(prefix)) => Some(prefix.clone()),
+                    _ => None,
+                },
+                replace_key_with: match &self.rewrite {
+                    Some(KeyRewrite::Key(key)) => Some(key.clone()),
+                    _ => None,
+                },
+                http_redirect_code: self.http_redirect_code.map(|c| c.to_string()),
+            },
+        }
+    }
+
+    fn from_rule(rule: rusoto_s3::RoutingRule) -> Self {
+        let condition = rule.condition.unwrap_or_default();
+        let redirect = rule.redirect;
+
+        RoutingRule {
+            key_prefix: condition.key_prefix_equals,
+            http_error_code: condition
+                .http_error_code_returned_equals
+                .and_then(|c| c.parse().ok()),
+            host: redirect.host_name,
+            protocol: redirect.protocol.as_deref().and_then(Protocol::parse),
+            rewrite: match (redirect.replace_key_prefix_with, redirect.replace_key_with) {
+                (Some(prefix), _) => Some(KeyRewrite::Prefix(prefix)),
+                (None, Some(key)) => Some(KeyRewrite::Key(key)),
+                (None, None) => None,
+            },
+            http_redirect_code: redirect.http_redirect_code.and_then(|c| c.parse().ok()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Website {
+    Documents {
+        index: String, // suffix appended to requests for a "directory", e.g. `index.html`
+        error: Option<String>,
+        routing_rules: Vec<RoutingRule>,
+    },
+    RedirectAll {
+        host: String,
+        protocol: Option<Protocol>,
+    },
+}
+
+impl Website {
+    pub fn documents(index: &str, error: Option<&str>) -> Self {
+        Website::Documents {
+            index: index.to_string(),
+            error: error.map(String::from),
+            routing_rules: Vec::new(),
+        }
+    }
+
+    pub fn redirect_all(host: &str, protocol: Option<Protocol>) -> Self {
+        Website::RedirectAll {
+            host: host.to_string(),
+            protocol,
+        }
+    }
+
+    /// Routing rules only apply to `Website::Documents`.
+    pub fn routing_rule(mut self, rule: RoutingRule) -> Self {
+        if let Website::Documents { routing_rules, .. } = &mut self {
+            routing_rules.push(rule);
+        }
+        self
+    }
+
+    pub(crate) fn to_configuration(&self) -> WebsiteConfiguration {
+        match self {
+            Self::Documents {
+                index,
+                error,
+                routing_rules,
+            } => WebsiteConfiguration {
+                index_document: Some(IndexDocument {
+                    suffix: index.clone(),
+                }),
+                error_document: error.as_ref().map(|key| ErrorDocument { key: key.clone() }),
+                routing_rules: Some(routing_rules.iter().map(RoutingRule::to_rule).collect())
+                    .filter(|rules: &Vec<rusoto_s3::RoutingRule>| !rules.is_empty()),
+                redirect_all_requests_to: None,
+            },
+            Self::RedirectAll { host, protocol } => WebsiteConfiguration {
+                redirect_all_requests_to: Some(RedirectAllRequestsTo {
+                    host_name: host.clone(),
+                    protocol: protocol.as_ref().map(|p| p.as_str().to_string()),
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub(crate) fn from_output(output: GetBucketWebsiteOutput) -> Option<Self> {
+        if let Some(redirect) = output.redirect_all_requests_to {
+            return Some(Website::RedirectAll {
+                host: redirect.host_name,
+                protocol: redirect.protocol.as_deref().and_then(Protocol::parse),
+            });
+        }
+
+        Some(Website::Documents {
+            index: output.index_document?.suffix,
+            error: output.error_document.map(|error| error.key),
+            routing_rules: output
+                .routing_rules
+                .unwrap_or_default()
+                .into_iter()
+                .map(RoutingRule::from_rule)
+                .collect(),
+        })
+    }
+}
+
+/// Settings for `Transact::upload_s3_site`, extensions are matched without the dot and ignoring case.
+#[derive(Clone)]
+pub struct SiteOptions {
+    key_prefix: String,
+    content_types: HashMap<String, String>,
+    cache_control: HashMap<String, String>,
+    default_cache_control: Option<String>,
+    redirects: HashMap<String, String>,
+    concurrency: usize,
+}
+
+impl Default for SiteOptions {
+    fn default() -> Self {
+        SiteOptions {
+            key_prefix: String::new(),
+            content_types: HashMap::new(),
+            cache_control: HashMap::new(),
+            default_cache_control: None,
+            redirects: HashMap::new(),
+            concurrency: 8,
+        }
+    }
+}
+
+impl SiteOptions {
+    pub fn new() -> Self {
+        SiteOptions::default()
+    }
+
+    pub fn key_prefix(mut self, key_prefix: &str) -> Self {
+        self.key_prefix = key_prefix.to_string();
+        self
+    }
+
+    /// Overrides the content type guessed from the extension.
+    pub fn content_type(mut self, extension: &str, content_type: &str) -> Self {
+        self.content_types
+            .insert(extension.to_lowercase(), content_type.to_string());
+        self
+    }
+
+    pub fn cache_control(mut self, extension: &str, cache_control: &str) -> Self {
+        self.cache_control
+            .insert(extension.to_lowercase(), cache_control.to_string());
+        self
+    }
+
+    pub fn default_cache_control(mut self, cache_control: &str) -> Self {
+        self.default_cache_control = Some(cache_control.to_string());
+        self
+    }
+
+    /// Writes an empty object at `key` that the website endpoint redirects to `location`,
+    /// either another key starting with `/` or a full url.
+    pub fn redirect(mut self, key: &str, location: &str) -> Self {
+        self.redirects.insert(key.to_string(), location.to_string());
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub(crate) fn concurrency_limit(&self) -> usize {
+        self.concurrency
+    }
+
+    pub(crate) fn redirects(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        self.redirects
+            .iter()
+            .map(move |(key, location)| (self.key_prefix.clone() + key, location.clone()))
+    }
+
+    pub(crate) fn key(&self, dir: &Path, file: &Path) -> String {
+        let relative = file.strip_prefix(dir).unwrap_or(file);
+        let key = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/");
+
+        self.key_prefix.clone() + &key
+    }
+
+    pub(crate) fn content_type_of(&self, file: &Path) -> String {
+        extension(file)
+            .and_then(|ext| self.content_types.get(&ext).cloned())
+            .unwrap_or_else(|| {
+                mime_guess::from_path(file)
+                    .first_or_octet_stream()
+                    .to_string()
+            })
+    }
+
+    pub(crate) fn cache_control_of(&self, file: &Path) -> Option<String> {
+        extension(file)
+            .and_then(|ext| self.cache_control.get(&ext).cloned())
+            .or_else(|| self.default_cache_control.clone())
+    }
+}
+
+fn extension(file: &Path) -> Option<String> {
+    file.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// Every file under `dir`, sorted so uploads are deterministic. The walk runs on the blocking pool.
+pub(crate) async fn site_files(dir: PathBuf) -> io::Result<Vec<PathBuf>> {
+    let mut files = tokio::task::spawn_blocking(move || walk(&dir))
+        .await
+        .map_err(io::Error::other)??;
+
+    files.sort();
+    Ok(files)
+}
+
+// symlinked files are uploaded as the file they point to, symlinked directories are skipped
+// so a link to a parent can't loop
+fn walk(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            files.extend(walk(&path)?);
+        } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+#[derive(Debug)]
+pub enum SiteError {
+    Io(io::Error),
+    S3(RusotoError<PutObjectError>),
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::S3(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SiteError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn website_roundtrip() {
+        let website = Website::documents("index.html", Some("404.html")).routing_rule(
+            RoutingRule::new()
+                .when_key_prefix("docs/")
+                .rewrite(KeyRewrite::Prefix("documents/".to_string()))
+                .http_redirect_code(301),
+        );
+        let config = website.to_configuration();
+
+        let output = GetBucketWebsiteOutput {
+            error_document: config.error_document,
+            index_document: config.index_document,
+            redirect_all_requests_to: config.redirect_all_requests_to,
+            routing_rules: config.routing_rules,
+        };
+
+        assert_eq!(Website::from_output(output), Some(website));
+    }
+
+    #[test]
+    fn redirect_all() {
+        let config = Website::redirect_all("example.com", Some(Protocol::Https)).to_configuration();
+
+        assert!(config.index_document.is_none());
+        assert_eq!(
+            config.redirect_all_requests_to.unwrap().protocol,
+            Some("https".to_string())
+        );
+    }
+
+    #[test]
+    fn site_object_settings() {
+        let options = SiteOptions::new()
+            .key_prefix("reports/")
+            .content_type("md", "text/markdown")
+            .cache_control("HTML", "no-cache")
+            .default_cache_control("max-age=86400");
+        let dir = Path::new("/tmp/site");
+
+        assert_eq!(
+            options.key(dir, Path::new("/tmp/site/2021/index.html")),
+            "reports/2021/index.html"
+        );
+        assert_eq!(
+            options.content_type_of(Path::new("a/index.html")),
+            "text/html"
+        );
+        assert_eq!(
+            options.content_type_of(Path::new("README.md")),
+            "text/markdown"
+        );
+        assert_eq!(
+            options.content_type_of(Path::new("data.unknown-ext")),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            options.cache_control_of(Path::new("index.html")),
+            Some("no-cache".to_string())
+        );
+        assert_eq!(
+            options.cache_control_of(Path::new("app.js")),
+            Some("max-age=86400".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn site_files_skip_linked_dirs() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("s3ql-site-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("css")).unwrap();
+        std::fs::write(dir.join("index.html"), "<h1>s3ql</h1>").unwrap();
+        std::fs::write(dir.join("css/site.css"), "h1 {}").unwrap();
+        symlink(&dir, dir.join("css/parent")).unwrap();
+        symlink(dir.join("index.html"), dir.join("home.html")).unwrap();
+
+        let files = site_files(dir.clone()).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                dir.join("css/site.css"),
+                dir.join("home.html"),
+                dir.join("index.html")
+            ]
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use s3ql::{bucket::*, region, s3_client};
+use rusoto_s3::{PutBucketVersioningRequest, VersioningConfiguration, S3};
+use s3ql::{
+    bucket::*, encryption::*, notification::*, region, replication::*, s3_client, select_client,
+    tagging::*,
+};
 
 fn client() -> rusoto_s3::S3Client {
     let region = region("us-east-1".to_owned(), "http://localhost:4566".to_owned());
@@ -52,3 +56,168 @@ async fn list_buckets() {
     let list = s3.show_s3_buckets().await;
     assert!(list.is_ok());
 }
+
+#[tokio::test]
+async fn bucket_encryption() {
+    let name = "testEncryptedBucket".to_string();
+    let s3 = client();
+    let _ = s3.create_s3_bucket(name.clone(), None).await;
+
+    let put = s3
+        .put_s3_bucket_encryption(name.clone(), BucketEncryption::S3)
+        .await;
+    assert!(put.is_ok());
+
+    let encryption = s3.get_s3_bucket_encryption(name).await;
+    assert_eq!(encryption.unwrap(), Some(BucketEncryption::S3));
+}
+
+#[tokio::test]
+async fn bucket_key_encryption() {
+    let name = "testBucketKeyBucket".to_string();
+    let region = region("us-east-1".to_owned(), "http://localhost:4566".to_owned());
+    let select = select_client(region);
+    let _ = select
+        .s3_client()
+        .create_s3_bucket(name.clone(), None)
+        .await;
+
+    let kms = BucketEncryption::KMS {
+        key_id: None,
+        bucket_key: Some(true),
+    };
+    let put = select
+        .put_s3_bucket_encryption(name.clone(), kms.clone())
+        .await;
+    assert!(put.is_ok());
+
+    let encryption = select.get_s3_bucket_encryption(name.clone()).await;
+    assert_eq!(encryption.unwrap(), Some(kms.clone()));
+
+    let rejected = client().put_s3_bucket_encryption(name, kms).await;
+    assert!(matches!(
+        rejected,
+        Err(rusoto_core::RusotoError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn bucket_tags() {
+    let name = "testTaggedBucket".to_string();
+    let s3 = client();
+    let _ = s3.create_s3_bucket(name.clone(), None).await;
+
+    let mut tags = Tags::new();
+    tags.insert("cost-center".to_string(), "data".to_string());
+    assert!(s3
+        .put_s3_bucket_tags(name.clone(), tags.clone())
+        .await
+        .is_ok());
+    assert_eq!(s3.get_s3_bucket_tags(name.clone()).await.unwrap(), tags);
+
+    assert!(s3.drop_s3_bucket_tags(name).await.is_ok());
+}
+
+#[tokio::test]
+async fn list_buckets_with_details() {
+    let name = "testDetailedBucket".to_string();
+    let s3 = client();
+    let _ = s3.create_s3_bucket(name.clone(), None).await;
+
+    let list = s3
+        .show_s3_buckets_with(
+            BucketFilter::new()
+                .name("testDetailed*")
+                .with_region()
+                .with_versioning()
+                .with_tags()
+                .concurrency(2),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].name, name);
+    assert_eq!(list[0].region, Some("us-east-1".to_string()));
+    assert_eq!(list[0].versioning, Some(Versioning::Unversioned));
+}
+
+#[tokio::test]
+async fn bucket_notifications() {
+    let name = "testNotifiedBucket".to_string();
+    let s3 = client();
+    let _ = s3.create_s3_bucket(name.clone(), None).await;
+
+    let queue = NotificationRule::new(
+        NotificationTarget::Sqs("arn:aws:sqs:us-east-1:000000000000:ingest".to_string()),
+        vec![NotificationEvent::ObjectCreated],
+    )
+    .id("ingest")
+    .suffix(".json");
+    let topic = NotificationRule::new(
+        NotificationTarget::Sns("arn:aws:sns:us-east-1:000000000000:alerts".to_string()),
+        vec![NotificationEvent::ObjectRemoved],
+    )
+    .id("alerts");
+
+    let put = s3
+        .put_s3_bucket_notifications(name.clone(), vec![queue.clone()])
+        .await;
+    assert!(put.is_ok());
+
+    let select = select_client(region(
+        "us-east-1".to_owned(),
+        "http://localhost:4566".to_owned(),
+    ));
+    let add = select.add_s3_bucket_notification(name.clone(), topic).await;
+    assert!(add.is_ok());
+
+    let rules = s3.get_s3_bucket_notifications(name).await.unwrap();
+    assert_eq!(rules.len(), 2);
+    assert!(rules.contains(&queue));
+}
+
+#[tokio::test]
+async fn bucket_replication() {
+    let source = "testReplicatedBucket".to_string();
+    let destination = "testReplicaBucket".to_string();
+    let s3 = client();
+
+    for name in &[source.clone(), destination.clone()] {
+        let _ = s3.create_s3_bucket(name.clone(), None).await;
+        let _ = s3
+            .put_bucket_versioning(PutBucketVersioningRequest {
+                bucket: name.clone(),
+                versioning_configuration: VersioningConfiguration {
+                    status: Some("Enabled".to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .await;
+    }
+
+    let replication = Replication::new(
+        "arn:aws:iam::000000000000:role/replication",
+        vec![ReplicationRule::new(
+            ReplicationFilter::Prefix("reports/".to_string()),
+            ReplicationDestination::new(&format!("arn:aws:s3:::{}", destination))
+                .storage_class(StorageClass::StandardIa),
+        )
+        .id("reports")
+        .with_delete_markers()],
+    );
+
+    let put = s3
+        .put_s3_bucket_replication(source.clone(), replication.clone())
+        .await;
+    assert!(put.is_ok());
+
+    let get = s3.get_s3_bucket_replication(source.clone()).await;
+    assert_eq!(
+        get.unwrap().unwrap().rules[0].id,
+        Some("reports".to_string())
+    );
+
+    assert!(s3.drop_s3_bucket_replication(source).await.is_ok());
+}
@@ -1,4 +1,5 @@
-use s3ql::{bucket::*, query::*, region, s3_client, transact::*};
+use futures::TryStreamExt;
+use s3ql::{bucket::*, query::*, region, s3_client, select_client, transact::*};
 
 fn client() -> rusoto_s3::S3Client {
     let region = region("us-east-1".to_owned(), "http://localhost:4566".to_owned());
@@ -30,21 +31,182 @@ async fn select_object() {
     );
 
     assert!(insert.await.is_ok());
-    let query = QueryContent::select(vec![Select::Elements(vec!["name".to_string()])])
+    let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
         .from(BUCKET, "select-key")
         .limit(2)
-        .where_clause(Clause::IsNotNull("count".to_string()));
+        .where_clause(Clause::IsNotNull(Expr::column("count")));
 
     let select = s3
         .query_s3_object_content(
-            query,
+            query.clone(),
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Document),
+            OutputObjectFormat::JSON(Some(",".to_string())),
+        )
+        .await;
+
+    // rusoto_s3 can't read the response, `SelectClient` runs the query instead
+    assert!(matches!(
+        select,
+        Err(rusoto_core::RusotoError::Validation(_))
+    ));
+
+    let select = select_client(region(
+        "us-east-1".to_owned(),
+        "http://localhost:4566".to_owned(),
+    ));
+    let records = select
+        .select_s3_records(
+            query.clone(),
             CompressionType::NONE,
             InputObjectFormat::JSON(JsonType::Document),
             OutputObjectFormat::JSON(Some(",".to_string())),
         )
+        .await
+        .unwrap()
+        .try_collect::<Vec<Record>>()
+        .await;
+
+    println!("{:?}", records);
+
+    assert_eq!(records.unwrap().len(), 2);
+
+    let rows = select
+        .select_s3_records(
+            query,
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Document),
+            OutputObjectFormat::CSV(CsvOutput::new()),
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<Record>>()
+        .await
+        .unwrap();
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["name"], "india");
+}
+
+#[ignore] // Only runs on localstack pro and aws. Any issues PLEASE REPORT
+#[tokio::test]
+async fn select_objects_by_prefix() {
+    let s3 = client();
+    let select = select_client(region(
+        "us-east-1".to_owned(),
+        "http://localhost:4566".to_owned(),
+    ));
+    let bucket = s3
+        .create_s3_bucket("selectPrefixBucket".to_string(), None)
         .await;
 
-    println!("{:?}", select);
+    assert!(bucket.is_ok());
+
+    for (key, body) in [
+        ("logs/a.json", "{\"name\": \"india\", \"count\":1000}"),
+        ("logs/b.json", "{\"name\": \"china\", \"count\":1300}"),
+        ("logs/c.csv", "name,count\nghana,10"),
+    ] {
+        let insert = s3.insert_s3_object(
+            "selectPrefixBucket".to_string(),
+            None,
+            key.to_string(),
+            Some(body.to_string()),
+            None,
+        );
+        assert!(insert.await.is_ok());
+    }
+
+    let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+        .from_prefix("selectPrefixBucket", "logs/");
+
+    let records = select
+        .query_s3_objects(
+            query,
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Lines),
+            OutputObjectFormat::JSON(None),
+            QueryOptions::new().concurrency(2),
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<ObjectRecord>>()
+        .await
+        .unwrap();
+
+    assert_eq!(records.len(), 3);
+    assert!(records.contains(&ObjectRecord::Skipped {
+        key: "logs/c.csv".to_string(),
+        reason: "object is CSV, not JSON".to_string(),
+    }));
+}
+
+#[tokio::test]
+async fn select_object_offline() {
+    let mut emulator = SelectEmulator::new();
+    let body = "{\"name\": \"world\"}
+{\"name\": \"india\", \"count\":1000}
+{\"name\": \"china\", \"count\":1300}
+{\"name\": \"ghana\"}
+{\"hello\": \"brasil\", \"count\":200}";
+    emulator.put_object(BUCKET, "select-key", body).unwrap();
+
+    let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+        .from(BUCKET, "select-key")
+        .limit(2)
+        .where_clause(Clause::IsNotNull(Expr::column("count")));
+
+    let records = emulator
+        .select_s3_records(
+            query,
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Document),
+            OutputObjectFormat::JSON(Some(",".to_string())),
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<Record>>()
+        .await
+        .unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["name"], "india");
+    assert_eq!(records[1]["name"], "china");
+}
+
+#[tokio::test]
+async fn select_objects_by_prefix_offline() {
+    let mut emulator = SelectEmulator::new();
+    for (key, body) in [
+        ("logs/a.json", "{\"name\": \"india\", \"count\":1000}"),
+        ("logs/b.json", "{\"name\": \"china\", \"count\":1300}"),
+        ("logs/c.csv", "name,count\nghana,10"),
+    ] {
+        emulator
+            .put_object("selectPrefixBucket", key, body)
+            .unwrap();
+    }
+
+    let query = QueryContent::select(vec![Select::Elements(vec![Expr::column("name")])])
+        .from_prefix("selectPrefixBucket", "logs/");
+
+    let records = emulator
+        .query_s3_objects(
+            query,
+            CompressionType::NONE,
+            InputObjectFormat::JSON(JsonType::Lines),
+            OutputObjectFormat::JSON(None),
+            QueryOptions::new().concurrency(2),
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<ObjectRecord>>()
+        .await
+        .unwrap();
 
-    assert!(select.is_ok());
+    assert_eq!(records.len(), 3);
+    assert!(records.contains(&ObjectRecord::Skipped {
+        key: "logs/c.csv".to_string(),
+        reason: "object is CSV, not JSON".to_string(),
+    }));
 }
@@ -124,3 +124,15 @@ async fn read_object() {
 
     assert!(read_obj.unwrap().e_tag.is_some());
 }
+
+#[tokio::test]
+async fn read_object_replication_status() {
+    let s3 = client().await;
+    insert(&s3).await;
+
+    let status = s3
+        .read_s3_object_replication_status(BUCKET.to_string(), "key1".to_string(), None, None)
+        .await;
+
+    assert_eq!(status.unwrap(), None);
+}
@@ -1,4 +1,7 @@
-use s3ql::{bucket::*, read::*, region, s3_client, transact::*};
+use s3ql::{
+    bucket::*, encryption::*, lock::*, read::*, region, s3_client, tagging::*, transact::*,
+    website::*,
+};
 use std::collections::HashMap;
 
 fn client() -> rusoto_s3::S3Client {
@@ -146,3 +149,188 @@ async fn update_object_meta() {
         "2007-19-01T11:12:00-000"
     );
 }
+
+#[tokio::test]
+async fn insert_customer_encrypted_object() {
+    let s3 = client();
+    let _ = s3.create_s3_bucket(BUCKET.to_string(), None).await;
+    let customer_key = CustomerKey::new(vec![7u8; 32]).unwrap();
+
+    let insert = s3.insert_s3_object_with(
+        BUCKET.to_string(),
+        "sse-c-key".to_string(),
+        Some("{\"hello\": \"secret\"}".to_string()),
+        InsertOptions::new().encryption(ObjectEncryption::Customer(customer_key.clone())),
+    );
+    assert!(insert.await.is_ok());
+
+    let read_obj = s3
+        .read_s3_object_body_with(
+            BUCKET.to_string(),
+            "sse-c-key".to_string(),
+            ReadOptions::new().customer_key(customer_key.clone()),
+        )
+        .await;
+    assert_eq!(read_obj.unwrap(), "{\"hello\": \"secret\"}");
+
+    let status = s3
+        .read_s3_object_replication_status(
+            BUCKET.to_string(),
+            "sse-c-key".to_string(),
+            None,
+            Some(customer_key),
+        )
+        .await;
+    assert_eq!(status.unwrap(), None);
+}
+
+#[tokio::test]
+async fn insert_tagged_object() {
+    let s3 = client();
+    let _ = s3.create_s3_bucket(BUCKET.to_string(), None).await;
+
+    let mut tags = Tags::new();
+    tags.insert("cost-center".to_string(), "data ingest".to_string());
+    let insert = s3.insert_s3_object_with(
+        BUCKET.to_string(),
+        "tagged-key".to_string(),
+        None,
+        InsertOptions::new().tags(tags.clone()),
+    );
+    assert!(insert.await.is_ok());
+
+    let read_tags = s3
+        .read_s3_object_tags(BUCKET.to_string(), "tagged-key".to_string())
+        .await;
+    assert_eq!(read_tags.unwrap(), tags);
+
+    assert!(s3
+        .drop_s3_object_tags(BUCKET.to_string(), "tagged-key".to_string())
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn insert_invalid_tags() {
+    let s3 = client();
+    let mut tags = Tags::new();
+    tags.insert("aws:reserved".to_string(), "value".to_string());
+
+    let insert = s3
+        .insert_s3_object_with(
+            BUCKET.to_string(),
+            "invalid-tags-key".to_string(),
+            None,
+            InsertOptions::new().tags(tags),
+        )
+        .await;
+
+    assert!(matches!(
+        insert,
+        Err(rusoto_core::RusotoError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn insert_locked_object() {
+    let bucket = "lockedTransactObjectsBucket".to_string();
+    let s3 = client();
+    let _ = s3.create_s3_locked_bucket(bucket.clone()).await;
+
+    let retention = DefaultRetention::new(RetentionMode::Governance, RetentionPeriod::Days(1));
+    assert!(s3
+        .put_s3_bucket_retention(bucket.clone(), retention.clone())
+        .await
+        .is_ok());
+    assert_eq!(
+        s3.get_s3_bucket_retention(bucket.clone()).await.unwrap(),
+        Some(retention)
+    );
+
+    let insert = s3
+        .insert_s3_object_with(
+            bucket.clone(),
+            "locked-key".to_string(),
+            Some("{\"hello\": \"compliance\"}".to_string()),
+            InsertOptions::new().legal_hold(true),
+        )
+        .await
+        .unwrap();
+    let version_id = insert.object.version_id;
+
+    let legal_hold = s3
+        .read_s3_object_legal_hold(bucket.clone(), "locked-key".to_string(), version_id.clone())
+        .await;
+    assert!(legal_hold.unwrap());
+
+    let delete = s3
+        .drop_s3_object(bucket, "locked-key".to_string(), version_id, false)
+        .await;
+    assert!(matches!(delete, Err(ObjectLockError::Locked(_))));
+}
+
+#[tokio::test]
+async fn upload_site() {
+    let bucket_name = "testSiteBucket".to_string();
+    let dir = std::env::temp_dir().join("s3ql-site");
+    let s3 = client();
+    let _ = s3.create_s3_bucket(bucket_name.clone(), None).await;
+
+    std::fs::create_dir_all(dir.join("css")).unwrap();
+    std::fs::write(dir.join("index.html"), "<h1>s3ql</h1>").unwrap();
+    std::fs::write(dir.join("css/site.css"), "h1 { color: red; }").unwrap();
+
+    let website = s3
+        .put_s3_bucket_website(
+            bucket_name.clone(),
+            Website::documents("index.html", Some("404.html")),
+        )
+        .await;
+    assert!(website.is_ok());
+
+    let options = SiteOptions::new()
+        .cache_control("html", "no-cache")
+        .redirect("old/index.html", "/index.html");
+    let keys = s3
+        .upload_s3_site(bucket_name.clone(), dir, options)
+        .await
+        .unwrap();
+    assert_eq!(keys, vec!["css/site.css", "index.html", "old/index.html"]);
+
+    let index = s3
+        .read_s3_object(
+            bucket_name.clone(),
+            "index.html".to_string(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(index.content_type, Some("text/html".to_string()));
+    assert_eq!(index.cache_control, Some("no-cache".to_string()));
+
+    let redirect = s3
+        .read_s3_object(
+            bucket_name.clone(),
+            "old/index.html".to_string(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        redirect.website_redirect_location,
+        Some("/index.html".to_string())
+    );
+
+    let get = s3.get_s3_bucket_website(bucket_name.clone()).await;
+    assert_eq!(
+        get.unwrap(),
+        Some(Website::documents("index.html", Some("404.html")))
+    );
+
+    let drop = s3.drop_s3_bucket_website(bucket_name).await;
+    assert!(drop.is_ok());
+}